@@ -0,0 +1,108 @@
+//! Compares hashing macro names and looking them up in a map using [`hash::FxHasher`] against the
+//! default hasher. Run with `cargo bench -p posixutils-m4 --bench hash`.
+
+use std::collections::{hash_map::RandomState, HashMap};
+use std::hash::BuildHasher;
+use std::hint::black_box;
+use std::time::Instant;
+
+#[path = "../src/hash.rs"]
+#[allow(dead_code)]
+mod hash;
+
+/// Names like those of an autoconf run: the builtins (with the `m4_` prefix of `-P`) and many
+/// user defined macros.
+fn macro_names() -> Vec<Vec<u8>> {
+    let builtins = [
+        "changecom",
+        "changequote",
+        "decr",
+        "define",
+        "defn",
+        "divert",
+        "divnum",
+        "dnl",
+        "dumpdef",
+        "errprint",
+        "eval",
+        "format",
+        "ifdef",
+        "ifelse",
+        "include",
+        "incr",
+        "index",
+        "indir",
+        "len",
+        "m4exit",
+        "m4wrap",
+        "patsubst",
+        "popdef",
+        "pushdef",
+        "regexp",
+        "shift",
+        "sinclude",
+        "substr",
+        "syscmd",
+        "sysval",
+        "translit",
+        "undefine",
+        "undivert",
+    ];
+    let mut names: Vec<Vec<u8>> = builtins
+        .iter()
+        .map(|name| format!("m4_{name}").into_bytes())
+        .collect();
+    names.extend((0..600).map(|i| format!("AX_MACRO_{i}_CHECK").into_bytes()));
+    names
+}
+
+/// Words which are not macros but start like one, as most words looked up are.
+fn undefined_words(names: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    names
+        .iter()
+        .map(|name| name[..name.len() / 2].to_vec())
+        .collect()
+}
+
+/// The best time per iteration of `f` (called with each of `words`) over a number of runs, in
+/// nanoseconds.
+fn time_per_word(words: &[Vec<u8>], mut f: impl FnMut(&[u8]) -> u64) -> f64 {
+    const ROUNDS: u32 = 2000;
+    let best = (0..20)
+        .map(|_| {
+            let start = Instant::now();
+            let mut result = 0;
+            for _ in 0..ROUNDS {
+                for word in black_box(words) {
+                    result ^= f(word);
+                }
+            }
+            black_box(result);
+            start.elapsed()
+        })
+        .min()
+        .expect("at least one run");
+    best.as_secs_f64() * 1e9 / f64::from(ROUNDS * words.len() as u32)
+}
+
+/// Report the time to hash a name, and to look up defined and undefined names in a map of
+/// `names`, using `S`.
+fn report<S: BuildHasher + Default>(hasher: &str, names: &[Vec<u8>]) {
+    let map: HashMap<&[u8], u64, S> = (0..)
+        .zip(names)
+        .map(|(i, name)| (name.as_slice(), i))
+        .collect();
+    let lookup = |word: &[u8]| map.get(word).copied().unwrap_or_default();
+    let hash = time_per_word(names, |word| map.hasher().hash_one(word));
+    let defined = time_per_word(names, lookup);
+    let undefined = time_per_word(&undefined_words(names), lookup);
+    println!(
+        "{hasher:<8} hash {hash:5.1} ns, lookup defined {defined:5.1} ns, undefined {undefined:5.1} ns"
+    );
+}
+
+fn main() {
+    let names = macro_names();
+    report::<RandomState>("default", &names);
+    report::<hash::FxBuildHasher>("FxHasher", &names);
+}
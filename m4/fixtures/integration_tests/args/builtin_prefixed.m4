@@ -0,0 +1 @@
+m4_builtin(`len', `abc') m4_builtin(`m4_len', `abc')
@@ -0,0 +1,5 @@
+define(`x', `X')dnl
+changecom(`@')dnl
+x @ x
+changecom`'dnl
+x # x
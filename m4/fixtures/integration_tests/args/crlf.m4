@@ -0,0 +1,7 @@
+define(`x', `expanded')dnl
+first x # comment
+define(`y',
+`two
+lines')dnl
+y __line__
+last
@@ -0,0 +1,3 @@
+define(`foo', `Hello $1')dnl
+foo(`x')
+len(`abc')
@@ -0,0 +1,4 @@
+define(`twice', `substr(`$1')$1')dnl
+twice(`say "hi"')
+include(`fixtures/integration_tests/args/diagnostics_json_inner.m4')dnl
+define(`loop', `loop(loop(x))')loop
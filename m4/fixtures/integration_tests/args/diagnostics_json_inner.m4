@@ -0,0 +1 @@
+incr(`x')
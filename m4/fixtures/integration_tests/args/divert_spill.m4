@@ -0,0 +1,14 @@
+dnl A diversion larger than the in-memory limit, which is moved to a temporary file.
+define(`chunk', `the diverted text, enough to be moved into a temporary file
+')dnl
+define(`double', `define(`chunk', defn(`chunk')defn(`chunk'))')dnl
+double()double()double()double()double()double()double()double()double()double()double()double()double()double()dnl
+divert(1)dnl
+chunk`'dnl
+divert(2)dnl
+small diversion
+divert(0)dnl
+start
+undivert(2)dnl
+undivert(1)dnl
+end
@@ -0,0 +1,4 @@
+before
+define(`x', `dnl')x
+after
+dnl
\ No newline at end of file
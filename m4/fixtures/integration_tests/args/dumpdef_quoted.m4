@@ -0,0 +1 @@
+define(`b', `B')dumpdef(`b')dnl
@@ -0,0 +1,3 @@
+before
+include(`error_location_inner.m4')dnl
+after
@@ -0,0 +1,3 @@
+dnl Included by error_location_include.m4, expanding more than the limit of 20 bytes.
+define(`big', `0123456789abcdefghijklmnopqrstuvwxyz')dnl
+big
@@ -0,0 +1 @@
+esyscmd(`echo hello')
@@ -0,0 +1,2 @@
+eval(`1/0')
+after
@@ -0,0 +1,2 @@
+eval(`0x1f')
+eval(`010')
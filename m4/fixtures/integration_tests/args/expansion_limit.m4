@@ -0,0 +1,5 @@
+define(`double', `$1$1')dnl
+define(`grow', `grow(double(`$1'))')dnl
+double(`within the limit')
+grow(`x')
+not reached
@@ -0,0 +1,2 @@
+index(hello)
+after
@@ -0,0 +1 @@
+wrong directory
\ No newline at end of file
@@ -0,0 +1,5 @@
+define(`zebra', `z')dnl
+define(`apple', `a')dnl
+define(`mango', `m')dnl
+pushdef(`apple', `A')dnl
+undefine(`len')dnl
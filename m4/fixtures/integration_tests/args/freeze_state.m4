@@ -0,0 +1,5 @@
+define(`x', `hello')dnl
+divert(1)diverted
+divert(0)x
+m4wrap(`wrapped
+')dnl
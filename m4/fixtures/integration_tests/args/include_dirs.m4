@@ -0,0 +1,2 @@
+include(`file.m4')
+include(`include_dirs_second.m4')
@@ -0,0 +1 @@
+from the second include directory
@@ -0,0 +1 @@
+incr(`2147483647') decr(`-2147483648')
@@ -0,0 +1,3 @@
+define(`a b', `$1 $2')dnl
+traceon(`a b')dnl
+indir(`a b', `x', `y')
@@ -0,0 +1 @@
+__file__ __line__ __program__
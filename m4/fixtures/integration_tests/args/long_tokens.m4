@@ -0,0 +1,9 @@
+define(`x', `abcdefg
+')define(`y', `abcdefgh')dnl
+define(`double', `define(`$1', defn(`$1')defn(`$1'))')dnl
+define(`double14', `double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')double(`$1')')dnl
+double14(`x')double14(`y')dnl
+define(`c', `#'defn(`y'))dnl
+len(defn(`x')) len(defn(`y'))
+defn(`x')c
+end
@@ -0,0 +1 @@
+m4exit(`42')
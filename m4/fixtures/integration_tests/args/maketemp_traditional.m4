@@ -0,0 +1 @@
+maketemp(`/tmp/m4-XXXXXX')
\ No newline at end of file
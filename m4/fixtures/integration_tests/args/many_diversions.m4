@@ -0,0 +1,11 @@
+dnl Many small diversions flushed at exit, with a large one in the middle which is moved to a
+dnl temporary file.
+define(`chunk', `the diverted text, enough to be moved into a temporary file
+')dnl
+define(`double', `define(`chunk', defn(`chunk')defn(`chunk'))')dnl
+double()double()double()double()double()double()double()double()double()double()double()double()double()double()dnl
+define(`loop', `ifelse(`$1', `0', `', `divert($1)diversion $1
+ifelse(`$1', `100', `chunk()')loop(decr($1))')')dnl
+loop(200)dnl
+divert(0)dnl
+start
@@ -0,0 +1,3 @@
+first __line__
+include(`location.m4')dnl
+last __line__
@@ -0,0 +1 @@
+after the missing file
@@ -0,0 +1,2 @@
+ifdef(`debugfile', `gnu', `traditional')
+changecom(`#', `', `x')dnl
@@ -0,0 +1 @@
+define(`f', `g(f(x))')define(`g',`$1')f
@@ -0,0 +1,6 @@
+before
+errprint(`diagnostic
+')partial dnl
+__file__
+syscmd(`echo child')dnl
+after
@@ -0,0 +1,6 @@
+ifdef(`__gnu__', `gnu', `not gnu')
+ifdef(`__unix__', `unix-like', `no __unix__')
+ifdef(`unix', `traditional unix', `no unix')
+ifdef(`__windows__', `windows', `not windows')
+[__gnu__][__unix__]
+dumpdef(`__gnu__', `__unix__')dnl
@@ -0,0 +1,3 @@
+m4_define(`x', `y')m4_dnl
+x define(`a') m4_len(`abc')
+m4_dumpdef(`m4_len')m4_dnl
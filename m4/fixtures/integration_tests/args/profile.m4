@@ -0,0 +1,4 @@
+define(`greet', `hello $1')dnl
+greet(`a') greet(`b')
+ifdef(`greet', `yes')
+m4exit(`3')
@@ -0,0 +1,3 @@
+define(`fact', `ifelse(`$1', `0', `1', `eval($1 * $0(decr($1)))')')dnl
+fact(3)
+fact(10)
@@ -0,0 +1,4 @@
+greeting
+popdef([greeting])greeting
+define([x], [y])x
+len([abc])
@@ -0,0 +1,2 @@
+greeting
+tab oct
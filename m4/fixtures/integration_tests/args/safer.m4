@@ -0,0 +1,5 @@
+syscmd(`echo unsafe')dnl
+mkstemp(`/tmp/safer.XXXXXX')dnl
+debugfile(`/tmp/safer.debug')dnl
+debugfile(`')dnl
+done
@@ -0,0 +1,4 @@
+define(`greeting', `hello from file 1')dnl
+divert(1)dnl
+diverted from file 1
+divert(0)dnl
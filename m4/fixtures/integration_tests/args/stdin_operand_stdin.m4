@@ -0,0 +1 @@
+stdin says __file__
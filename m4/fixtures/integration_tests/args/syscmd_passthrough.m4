@@ -0,0 +1,8 @@
+1 syscmd(`echo "hello world"')
+2 sysval
+3 syscmd(`exit 1')
+4 sysval
+5 define(`x', syscmd(`echo "hello"'))x x x
+6 divert(1)hello syscmd(`echo "world"')undivert(1)
+7 syscmd(`echo "to stderr" >&2')
+8 syscmd()sysval
@@ -0,0 +1 @@
+syscmd(`kill -15 $$')after kill sysval
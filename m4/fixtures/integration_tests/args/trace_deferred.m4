@@ -0,0 +1,7 @@
+foo
+define(`foo', `Hi')dnl
+foo
+undefine(`foo')dnl
+foo
+define(`foo', `Again')dnl
+foo
@@ -0,0 +1,15 @@
+t
+define(`before', `B')dnl
+traceon`'dnl
+define(`after', `A')dnl
+before after
+traceoff(`before')dnl
+before after
+traceoff`'dnl
+traceon(`later')dnl
+define(`later', `L')dnl
+later(1, 2)
+undefine(`later')define(`later', `L2')dnl
+later
+traceoff(`later', `t')dnl
+later
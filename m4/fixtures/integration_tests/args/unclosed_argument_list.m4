@@ -0,0 +1 @@
+define(`f', `x')f(a, (b
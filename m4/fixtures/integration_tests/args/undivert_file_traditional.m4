@@ -0,0 +1,2 @@
+undivert(`fixtures/integration_tests/args/undivert_file.txt')dnl
+end
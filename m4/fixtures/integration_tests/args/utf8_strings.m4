@@ -0,0 +1,3 @@
+len(`héllo') index(`héllo wörld', `wö') substr(`héllo', 1, 3) translit(`héllo', `é-ë', `E-G') translit(`αβγ', `α-γ', `a-c')
+len(`a_bc') substr(`a_bc', 1, 2) index(`a_bc', `c') translit(`a_b', `_', `X')
+len(`ab') substr(`ab', 2) translit(`xy', `', `-') index(`', `')
@@ -0,0 +1,2 @@
+define(`a', `$1 $10 ${x} $2')dnl
+pushdef(`b', `$12')dnl
@@ -0,0 +1,5 @@
+define(`x', `1', `extra')dnl
+incr()
+substr(`abc')
+indir(`nosuch')
+done
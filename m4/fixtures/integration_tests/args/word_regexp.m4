@@ -0,0 +1,2 @@
+define(`1', `one')dnl
+1 2
@@ -0,0 +1,7 @@
+define(`show', `$#:[$1][$2][$3]')dnl
+show(a, (b, c), d)
+show(`a, b', c)
+show(  a  ,	 b,
+ c)
+show((a, `)'), b)
+show(a(b(c, d)), e)
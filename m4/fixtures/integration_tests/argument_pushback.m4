@@ -0,0 +1,10 @@
+define(`long', `an argument which is longer than sixty-four characters, with `nested' quotes')dnl
+define(`x', before `quoted `nested' text' after)dnl
+defn(`x')
+ifelse(`a', `a', `an argument which is longer than sixty-four characters and is `long'')
+ifelse(`a', `b', `', `the last argument, which is also longer than sixty-four characters: long')
+ifdef(`long', `defined: long', `undefined')
+translit(`an argument which is longer than sixty-four characters', `a-z', `A-Z')
+patsubst(`an argument which is longer than sixty-four characters', `\w+', `[\&]')
+define(`args', `$#: $@')dnl
+args(`1', `two `2'', long)
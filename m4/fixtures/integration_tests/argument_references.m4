@@ -0,0 +1,3 @@
+define(`ten', `$10 ${10} $1${0}0 ${x} $')dnl
+ten(a,b,c,d,e,f,g,h,i,j)
+ten(a)
@@ -0,0 +1,12 @@
+pushdef(`len', `shadowed')dnl
+1 len(`abc') builtin(`len', `abc')
+undefine(`len')dnl
+2 len(`abc') builtin(`len', `abcde')
+define(`mydefine', `builtin(`define', $@)')dnl
+undefine(`define')dnl
+mydefine(`x', `defined without define')dnl
+3 x
+4 builtin(`builtin', `eval', `1 + 2')
+5 builtin(`nosuch')
+6 builtin(`divnum')
+7 builtin
@@ -0,0 +1,13 @@
+define(`x', `X')dnl
+changecom(`/*', `*/')dnl
+x /* x
+ x */ x
+changecom(`//')dnl
+x // x
+x
+changecom(`##', `')dnl
+x ## x
+changecom(`', `@')dnl
+x ## x @ x
+changecom`'dnl
+x # x
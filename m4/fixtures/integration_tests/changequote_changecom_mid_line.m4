@@ -0,0 +1,9 @@
+dnl Changing the quotes or comment delimiters applies to the rest of the line, and to the rest of
+dnl an expansion which has already been pushed back.
+before `quoted' changequote([, ])after [quoted] `not quoted'
+define([m], [changequote(<, >)<a>[b]])m and <c> changequote`'dnl
+define(`q', `changequote(`<<', `>>')<<x y>> `z'')q <<again>>changequote
+text changecom(`@@', `!')# not a comment @@ comment `x' changequote(`[') ! after `x'
+changecom`'# not a comment either
+define(`w', `changecom(`%')% in the expansion
+')w after changecom
@@ -0,0 +1,13 @@
+define(`x', `X')dnl
+changequote(`<<', `>>')dnl
+<<x>> <<a <<nested>> b>>
+changequote(<<[>>)dnl
+[x' `x'
+changequote`'dnl
+`x'
+changequote(`[', `')dnl
+[x'
+changequote([')dnl
+`x' [x]
+changequote`'dnl
+`x'
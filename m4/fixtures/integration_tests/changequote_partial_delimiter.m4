@@ -0,0 +1,5 @@
+dnl Text which starts like a multi-character delimiter but does not complete it is output as is.
+changequote(`[[', `]]')dnl
+[x [[quoted]] [y]z ]]
+changecom(`/*', `*/')dnl
+/x /* comment */ a/
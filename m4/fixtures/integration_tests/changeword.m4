@@ -0,0 +1,10 @@
+define(`x1', `one')dnl
+x1
+changeword(`[_a-zA-Z0-9]+')dnl
+define(`1', `zero')dnl
+1 x1
+changeword(`@\([a-z]+\)')dnl
+@define(`hi', `HI')@dnl
+@hi hi
+@changeword(`')dnl
+1 x1
@@ -0,0 +1,9 @@
+define(`show', `$#:[$1][$2]')dnl
+show(a # , b)
+, c)
+show(a, # ( unbalanced
+b)
+changecom(`/*', `*/')dnl
+show(x /* ) , */ y, z)
+changecom`'dnl
+show(p # , q)
@@ -0,0 +1,7 @@
+define(`foo', `Hello $1')dnl
+traceon(`foo')dnl
+foo(`a')
+debugfile(`')dnl
+foo(`b')
+debugfile`'dnl
+foo(`c')
@@ -0,0 +1,18 @@
+define(`foo', `bar')dnl
+traceon(`foo')dnl
+foo(1)
+debugmode(`ae')dnl
+foo(2)
+debugmode(`+q')dnl
+foo(3)
+debugmode(`-e')dnl
+foo(4)
+debugmode(`')dnl
+foo(5)
+debugmode(`+x')dnl
+foo(6)
+debugmode`'dnl
+foo(7)
+debugmode(`z')dnl
+debugmode(`a', `e')dnl
+foo(8)
@@ -0,0 +1,12 @@
+dnl Deeply nested expressions and long chains of indirect macro calls are evaluated without
+dnl overflowing the native stack.
+changequote([, ])dnl
+define([expr], [1])dnl
+define([chain], [[len]])dnl
+define([grow], [define([expr], [-(]defn([expr])[)])define([chain], [[indir], ]defn([chain]))])dnl
+define([nest], [ifelse([$1], [0], [], [grow()nest(decr([$1]))])])dnl
+nest(2000)dnl
+len(defn([expr]))
+eval(expr)
+indir(chain, [abc])
+builtin([builtin], [builtin], [builtin], [len], [abcd])
@@ -0,0 +1,9 @@
+define(`mylen', defn(`len'))dnl
+mylen(`hello')
+pushdef(`mydefine', defn(`define'))dnl
+mydefine(`x', `X')x
+define(`a', `A $1')define(`b', `B')dnl
+defn(`a', `b', `undefined')
+[defn(`len')]
+defn(`a', `len')
+undefine(`len')mylen(`abc')
@@ -0,0 +1,4 @@
+define(`x', `X')dnl
+1 defn(`')
+2 defn(`', `x', `')
+3 x
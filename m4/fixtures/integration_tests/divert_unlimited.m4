@@ -0,0 +1,21 @@
+divert(100)dnl
+hundred divnum
+divert(10)dnl
+ten divnum
+divert(-5)dnl
+discarded divnum
+divert(-1)dnl
+also discarded
+divert(2)dnl
+two divnum
+divert(abc)dnl
+still two divnum
+divert()dnl
+main divnum
+divert( 3)dnl
+three divnum
+divert(` 4')dnl
+four divnum
+divert(`')dnl
+divert(0, 1)dnl
+done
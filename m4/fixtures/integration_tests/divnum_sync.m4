@@ -0,0 +1,13 @@
+divnum
+divert(-1)dnl
+define(`discarded', divnum)dnl
+divert(-7)dnl
+define(`discarded_more', divnum)dnl
+divert(4)dnl
+define(`four', divnum)dnl
+divert(abc)dnl
+define(`still_four', divnum)dnl
+divert(0)dnl
+discarded discarded_more four still_four divnum
+divnum(`x')
+undivert
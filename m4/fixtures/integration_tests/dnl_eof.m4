@@ -0,0 +1,3 @@
+one dnl discarded
+two
+three dnl
\ No newline at end of file
@@ -0,0 +1 @@
+define(`x', `X')dumpdef(`', `x')
@@ -0,0 +1,2 @@
+define(`b', `B')define(`a', `A')define(`mylen', defn(`len'))dnl
+dumpdef(`mylen', `b', `a', `c')dnl
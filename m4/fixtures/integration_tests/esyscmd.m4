@@ -0,0 +1,6 @@
+define(`hello', `HELLO')dnl
+[esyscmd(`echo hello')]
+[esyscmd(`printf "no newline"')]
+esyscmd(`echo captured; exit 4')sysval
+esyscmd(`echo dnl')dnl
+after
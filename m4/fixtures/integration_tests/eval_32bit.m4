@@ -0,0 +1,7 @@
+eval(2147483647+1)
+eval(-2147483648-1)
+eval(1<<33)
+eval(65536*65536)
+eval(2**31)
+eval(0xffffffff)
+eval(-2147483648/-1)
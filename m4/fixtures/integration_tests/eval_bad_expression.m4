@@ -0,0 +1,6 @@
+dnl Invalid expressions are diagnosed, and expand to nothing.
+eval(`1 +')
+eval(`(1 + 2')
+eval(`1 2')
+eval(`1 +* 2')
+eval(`-(2 ** 3) + ((4))')
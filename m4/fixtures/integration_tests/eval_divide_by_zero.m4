@@ -0,0 +1,6 @@
+before
+eval(`1 / 0')
+eval(`10 % (2 - 2)')
+eval(`(4 / 0) + 1')
+eval(`4 / 2')
+after
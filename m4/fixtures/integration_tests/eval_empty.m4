@@ -0,0 +1,5 @@
+eval()
+eval( )
+eval(`',2,3)
+m4wrap()dnl
+after
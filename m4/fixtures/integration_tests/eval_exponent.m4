@@ -0,0 +1,9 @@
+eval(`2 ** 10')
+eval(`2 ** 3 ** 2')
+eval(`-2 ** 2')
+eval(`2 * 3 ** 2')
+eval(`(-3) ** 3')
+eval(`7 ** 0')
+eval(`0 ** 0')
+eval(`2 ** -1')
+eval(`1 + (2 ** -1)')
@@ -0,0 +1,5 @@
+eval(1,10,99999999999)
+eval(-2147483648,1)
+eval(5,1,8)
+eval(-3,1)
+eval(7,10,2147483648)
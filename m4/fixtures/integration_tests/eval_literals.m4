@@ -0,0 +1,9 @@
+eval(`0x1f')
+eval(`0XFF + 1')
+eval(`017')
+eval(`0')
+eval(`0b1010')
+eval(`0r36:zZ')
+eval(`0r2:111')
+eval(`0r1:1111')
+eval(`-0x10')
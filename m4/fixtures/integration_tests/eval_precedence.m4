@@ -0,0 +1,23 @@
+# shifts bind more tightly than relational operators
+eval(`1 < 2 << 1')
+eval(`1 + 1 << 2')
+eval(`8 >> 1 >> 1')
+# consecutive unary operators
+eval(`- - 3')
+eval(`!!5')
+eval(`~ -1')
+eval(`- ~ ! 0')
+eval(`+-+5')
+eval(`2 * -3 + 1')
+# left associativity
+eval(`10 - 4 - 3')
+eval(`100 / 10 / 5')
+eval(`7 % 4 * 3')
+# relational, equality, bitwise and logical operators
+eval(`1 < 2 == 1')
+eval(`2 == 2 != 0')
+eval(`6 & 3 ^ 1 | 8')
+eval(`1 | 2 ^ 3 & 1')
+eval(`1 || 0 && 0')
+eval(`(1 || 0) && 0')
+eval(`-(2 + 3) * 4')
@@ -0,0 +1,13 @@
+eval(`255', `16')
+eval(`255', `2')
+eval(`35', `36')
+eval(`-255', `16', `6')
+eval(`7', `', `3')
+eval(`5', `1')
+eval(`-3', `1', `5')
+eval(`0', `10', `4')
+eval(`123', `10', `2')
+eval(`1', `37')
+eval(`1', `0')
+eval(`1', `10', `-1')
+eval(`1', `ten')
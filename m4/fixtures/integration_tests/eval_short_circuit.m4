@@ -0,0 +1,10 @@
+eval(`0 && 1/0')
+eval(`1 || 1/0')
+eval(`2 || 1/0 && 1%0')
+eval(`0 && (1/0 || 1)')
+eval(`0 || 1 && 1')
+eval(`1 && 0 || 3')
+eval(`0 && 2 ** -1')
+eval(`1 && 1/0')
+eval(`0 || 1%0')
+eval(`0 && (1')
@@ -0,0 +1,20 @@
+define(`foo', `The brown fox jumped over the lazy dog')dnl
+1 format(`The string "%s" uses %d characters', foo, len(foo))
+2 format(`%*.*d', `-1', `-1', `1')
+3 format(`%.0f', `56789.9876')
+4 len(format(`%-*X', `300', `1'))
+5 format(`%010F', `infinity')
+6 format(`%g', `0xa.P+1')
+7 format(`[%5d|%-5d|%05d|%+d|% d|%.3d]', `42', `42', `42', `42', `42', `7')
+8 format(`[%o|%#o|%x|%#x|%X|%#X|%u]', `8', `8', `255', `255', `255', `255', `-1')
+9 format(`[%c%c%c|%5s|%-5s|%.2s]', `65', `66', `67', `ab', `ab', `abcdef')
+10 format(`[%e|%E|%.2e|%#.0e]', `1234.5678', `0.000123', `-1.5', `3')
+11 format(`[%f|%.2f|%#.0f|%+f]', `3.14159', `2.675', `3', `-0')
+12 format(`[%g|%g|%g|%g|%#g|%G]', `100000', `1000000', `0.0001', `0.00001', `1.5', `1e-10')
+13 format(`[%d|%s|%f]')
+14 format(`[%ld|%hd|%jd|%lld]', `1', `2', `3', `4')
+15 format(`100%%')
+16 format(`[%d|%d|%d]', ` 12abc', `abc', `')
+17 format(`%p', `0')
+18 format(`trailing %')
+19 format(`%d', `2147483648')
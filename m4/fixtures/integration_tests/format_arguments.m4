@@ -0,0 +1,8 @@
+format(`[%*d]', 99999999999, 1)
+format(`[%.*f|%s]', 2147483648, 1, next)
+format(`[%*d|%s]', -2147483648, 1, next)
+format(`[%d|%c|%x]', abc, 65z, 0x10)
+format(`[%f|%e|%g]', 1.5x, ` 2', `')
+format(`[%d|%d]', 2147483648, -2147483649)
+format(`[%d %d]', 1)
+format(`[%s|%*s]')
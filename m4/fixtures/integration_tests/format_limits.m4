@@ -0,0 +1,6 @@
+format(`[%.99999999999f]',1)
+format(`[%2147483648d|%s]',1,next)
+format(`[%99999999999d]',1)
+len(format(`%.70000f',1))
+len(format(`%.70000e',1))
+format(`%.70000g',1.5)
@@ -0,0 +1,9 @@
+define(`empty')dnl
+ifdef(`empty', `defined', `undefined')
+ifdef(`missing', `defined', `undefined')
+ifdef(`missing', `defined')
+ifdef(`define', `builtin')
+[ifdef]
+ifdef
+ifdef(`empty')
+ifdef(`empty', `a', `b', `c')
@@ -0,0 +1,11 @@
+ifelse(`a single argument is a comment')dnl
+ifelse(`a', `a', `equal')
+ifelse(`a', `b', `equal')
+ifelse(`a', `b', `equal', `not equal')
+ifelse(`a', `b', `1', `c', `c', `2', `3')
+ifelse(`a', `b', `1', `c', `d', `2', `e', `e', `3', `none')
+ifelse(`a', `b', `1', `c', `d', `2', `e', `f', `3', `none')
+ifelse(`a', `b', `1', `c', `d', `2')
+ifelse(`a', `b')
+ifelse(`a', `b', `1', `c', `2')
+ifelse(`a', `b', `1', `c', `d', `2', `e', `3')
@@ -0,0 +1,3 @@
+included at __file__:__line__
+
+__line__
@@ -0,0 +1,6 @@
+before
+include(`fixtures/integration_tests/DOESNOTEXIST.m4')
+sinclude(`fixtures/integration_tests/DOESNOTEXIST.m4')
+include(`fixtures/integration_tests/include')
+sinclude(`fixtures/integration_tests/include')
+after
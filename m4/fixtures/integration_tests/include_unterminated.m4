@@ -0,0 +1,6 @@
+include(`fixtures/integration_tests/include/unterminated_quote.inc')continued' after
+include(`fixtures/integration_tests/include/unterminated_comment.inc')continued
+after
+define(`show', `[$1]')dnl
+show(include(`fixtures/integration_tests/include/unterminated_comment.inc') continued, x
+)
@@ -0,0 +1,8 @@
+1 incr(`41') decr(`43')
+2 incr(` 5')
+3 incr(`')
+4 incr(`abc') decr(`1x')
+5 incr(`2147483647') decr(`-2147483648')
+6 incr(`4294967296')
+7 incr(`1', `2')
+8 incr(`+7') decr(`-7')
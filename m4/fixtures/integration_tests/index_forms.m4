@@ -0,0 +1,8 @@
+index(`gnu', `')
+index(`', `')
+index(`', `x')
+index(`abc', `abcd')
+index(`aab', `ab')
+index()
+index
+index(`abc', `b', `extra')
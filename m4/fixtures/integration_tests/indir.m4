@@ -0,0 +1,13 @@
+define(`a b', `[$1|$2]')dnl
+a b(1, 2)
+indir(`a b', `x', `y')
+indir(`a b')
+define(`$$internal$macro', `Internal macro (name `$0')')dnl
+indir(`$$internal$macro')
+indir(`len', `abcd')
+indir(`define', `z', `Z')z
+indir(`undefined', `x')
+ifdef(`a b', `defined', `undefined')
+defn(`a b')
+undefine(`a b')dnl
+ifdef(`a b', `defined', `undefined')
@@ -0,0 +1,5 @@
+len()
+len(`')
+len(`abc')
+len(`é')
+len(`abc', `de')
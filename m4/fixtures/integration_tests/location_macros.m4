@@ -0,0 +1,14 @@
+__file__:__line__
+__program__
+define(`where', `__file__:__line__')dnl
+where
+define(`multi', `first
+__line__')dnl
+multi
+include(`fixtures/integration_tests/include/location.m4')dnl
+__file__:__line__
+m4wrap(`wrapped at __file__:__line__
+')dnl
+ifdef(`__line__', `defined')
+m4wrap(`second at __line__
+')dnl
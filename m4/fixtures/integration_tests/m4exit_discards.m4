@@ -0,0 +1,5 @@
+m4wrap(`This text is lost due to `m4exit'.')
+divert(`1') So is this.
+divert
+before
+m4exit And this is never read.
@@ -0,0 +1,2 @@
+m4exit(`x')
+Should not display
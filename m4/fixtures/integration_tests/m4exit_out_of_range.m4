@@ -0,0 +1,2 @@
+m4exit(`300')
+Should not display
@@ -0,0 +1,9 @@
+define(`foo', `FOO')dnl
+m4wrap(`first foo
+')dnl
+m4wrap(`second m4wrap(`third round foo
+')
+')dnl
+divert(1)diverted
+divert(0)dnl
+main
@@ -0,0 +1,4 @@
+define(`foo', `X')define(`_x1', `Y')define(`1x', `Z')define(`a.b', `W')define(`a', `A')dnl
+foo foobar barfoo 1foo foo1 foo_ _foo foo.bar
+_x1 1x a.b 1_x1 _x1_
+indir(`1x') indir(`a.b')
@@ -1 +1 @@
-maketemp(/tmp/m4-XXXXXX)
\ No newline at end of file
+define(`name', maketemp(/tmp/m4-XXXXXX))syscmd(`rm -f 'name)name
\ No newline at end of file
@@ -1 +1 @@
-mkstemp(`/tmp/m4-XXXXXX')
\ No newline at end of file
+define(`name', mkstemp(`/tmp/m4-XXXXXX'))syscmd(`rm -f 'name)name
\ No newline at end of file
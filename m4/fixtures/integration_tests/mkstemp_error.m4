@@ -0,0 +1,3 @@
+before
+mkstemp(`/nonexistent/m4-XXXXXX')
+after
@@ -0,0 +1,14 @@
+1 patsubst(`GNUs not Unix', `^', `OBS: ')
+2 patsubst(`GNUs not Unix', `\<', `OBS: ')
+3 patsubst(`GNUs not Unix', `\w*', `(\&)')
+4 patsubst(`GNUs not Unix', `\w+', `(\0)')
+5 patsubst(`GNUs not Unix', `[A-Z][a-z]+')
+6 patsubst(`abc', `', `-')
+7 patsubst(`John Smith', `\(\w+\) \(\w+\)', `\2, \1')
+8 patsubst(`aaa', `a', `\&\&')
+9 patsubst(`abc', `b', `[\3]')
+10 patsubst(`a.b.c', `\.', `\\')
+11 patsubst(`abc')
+12 patsubst(`abc', `[')
+define(`X', `expanded')dnl
+13 patsubst(`x marks', `x', `X')
@@ -0,0 +1,8 @@
+define(`foo', `FOO')dnl
+xfoo 1foo _foo foo_x x1foo foo
+define(`p', `q$1')dnl
+p(x)foo p(x) foo
+define(`w', `xfoo foo')dnl
+w
+changeword(`[_a-zA-Z0-9]+')dnl
+xfoo 1foo foo
@@ -0,0 +1,10 @@
+define(`long', `This expansion is longer than sixty-four characters so it is kept whole: $1 short(`$1') line __line__.')dnl
+define(`short', `[$1]')dnl
+debugmode(`e')traceon(`long', `short')dnl
+long(`a')
+long(long(`b'))
+define(`nested', `long(`$1')long(`$1')')dnl
+nested(`c')
+ifelse(long(`d'), `', `empty', `not empty')
+traceoff(`long', `short')dnl
+substr(long(`e'), 60)
@@ -0,0 +1,12 @@
+define(`a', `1')pushdef(`a', `2')pushdef(`a', `3')dnl
+a
+define(`a', `4')dnl
+a
+popdef(`a')a
+popdef(`a')a
+popdef(`a')a
+pushdef(`b', `x')pushdef(`b', `y')pushdef(`c', `z')dnl
+b c
+popdef(`b', `c')b c
+pushdef(`b', `w')undefine(`b')b
+define(`empty')[empty]
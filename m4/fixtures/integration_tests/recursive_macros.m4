@@ -0,0 +1,15 @@
+define(`forloop', `pushdef(`$1', `$2')_forloop($@)popdef(`$1')')dnl
+define(`_forloop', `$4`'ifelse($1, `$3', `', `define(`$1', incr($1))$0($@)')')dnl
+forloop(`i', `1', `5', `i ')
+define(`countdown', `ifelse(`$1', `0', `liftoff', `$1 $0(decr($1))')')dnl
+countdown(5)
+define(`fact', `ifelse(`$1', `0', `1', `eval($1 * $0(decr($1)))')')dnl
+fact(10)
+define(`self', ``$0 is' `$0'')dnl
+self
+define(`loop', `ifelse(`$1', `0', `done', `$0(decr($1))')')dnl
+loop(20000)
+define(`redefine', `define(`redefine', `second')first')dnl
+redefine redefine
+define(`deep', `ifelse(`$1', `0', `bottom', `[deep(decr($1))]')')dnl
+deep(3)
@@ -0,0 +1,7 @@
+patsubst(`abc', `\(')
+regexp(`abc', `\)')
+regexp(`abc', `[z-a]')
+patsubst(`abc', `[[:foo:]]')
+regexp(`abc', `[b')
+regexp(`abc', `x\')
+regexp(`abc', `\(b\)', `<\1>')
@@ -0,0 +1,10 @@
+1 regexp(`GNUs not Unix', `\<[a-z]\w+')
+2 regexp(`GNUs not Unix', `\<Q\w*')
+3 regexp(`GNUs not Unix', `\w\(\w+\)$', `*** \& *** \1 ***')
+4 regexp(`GNUs not Unix', `\<Q\w*', `*** \& *** \1 ***')
+5 regexp(`abc', `b\|c', `[\&]')
+6 regexp(`abc', `\(x\)?b', `[\1]')
+7 regexp(`abc', `', `empty')
+8 regexp(`abc')
+9 regexp(`abc', `(')
+10 regexp(`abc', `c', `\2')
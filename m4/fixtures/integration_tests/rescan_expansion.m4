@@ -0,0 +1,11 @@
+define(`x', `le')dnl
+x`'n(abc) xn(abc)
+define(`mk', `define(`$1', `made `$1'')')dnl
+mk(`foo')foo
+define(`q', `changequote([, ])')dnl
+q[quoted `text']
+changequote`'dnl
+define(`call', `len(')dnl
+call`'abcd)
+define(`lq', `changequote(<, >)<')dnl
+lq`'still quoted mk>
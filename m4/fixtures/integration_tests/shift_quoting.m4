@@ -0,0 +1,14 @@
+define(`x', `X')dnl
+define(`show', `$#:[$*]:[$@]')dnl
+shift(`a', `x', `b,c')
+shift(`a', ``x'')
+show(`x', `b,c')
+define(`quoted', `show($@)')dnl
+quoted(`x', `b,c')
+define(`unquoted', `show($*)')dnl
+unquoted(`x', `b,c')
+changequote([, ])dnl
+shift([a], [x], [b,c])
+changequote`'dnl
+define(`last', `ifelse(`$#', `1', `$1', `last(shift($@))')')dnl
+last(`first', `x', `one,two')
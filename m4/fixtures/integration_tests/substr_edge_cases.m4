@@ -0,0 +1,13 @@
+1 substr(`hello', -1)
+2 substr(`hello', -1, 3)
+3 substr(`hello', 1, -1)
+4 substr(`hello', 10, 2)
+5 substr(`hello', 3, 100)
+6 substr(`hello')
+7 substr(`hello', `')
+8 substr(`hello', `1x')
+9 substr(`hello', 1, `two')
+10 substr(`hello', ` 2', 2)
+11 substr(`hello', 1, 2, 3)
+12 substr(`', 0, 1)
+13 substr(`hé!', 1, 2)
@@ -0,0 +1,5 @@
+before sysval
+syscmd(`exit 3')after exit sysval
+syscmd(`kill -9 $$')after kill sysval
+syscmd(`true')after true sysval
+sysval(`x')
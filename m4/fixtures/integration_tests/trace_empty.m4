@@ -0,0 +1,9 @@
+define(`x', `X')dnl
+traceon(`')dnl
+1 x
+traceon(`', `x')dnl
+2 x
+traceoff(`')dnl
+3 x
+traceoff(`x', `')dnl
+4 x
@@ -0,0 +1,11 @@
+1 translit(`hello world', `a-z', `A-Z')
+2 translit(`hello', `a-y', `b-z')
+3 translit(`abcdef', `a-f', `f-a')
+4 translit(`a-b', `-', `_')
+5 translit(`a-b', `b-', `B+')
+6 translit(`hello', `lol', `123')
+7 translit(`abc', `ab', `bc')
+8 translit(`hello world', `a-m')
+9 translit(`0123456789', `0-9', `a-c')
+10 translit(`hello')
+11 translit(`hello', `l', `L', `extra')
@@ -0,0 +1,8 @@
+define(`hello', `HELLO')dnl
+divert(1)dnl
+diverted hello
+divert(0)dnl
+undivert(`fixtures/integration_tests/args/undivert_file.txt')dnl
+undivert(`fixtures/integration_tests/DOESNOTEXIST')dnl
+undivert(`1')dnl
+end
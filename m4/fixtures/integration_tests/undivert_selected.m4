@@ -0,0 +1,16 @@
+divert(3)three
+divert(1)one
+divert(12)twelve
+divert(2)two
+divert(0)dnl
+undivert(2, 12)dnl
+selected
+undivert(0, -1, `', 7)dnl
+divert(5)five
+undivert(5)dnl
+undivert(3)dnl
+divert(0)dnl
+undivert(5)dnl
+rest
+undivert
+end
@@ -0,0 +1,7 @@
+define(`foo', `[$1]')dnl
+foo (a)
+foo(a)
+foo	(b)
+foo
+(c)
+define(`bar', `<$#>')bar  (x, y) bar(x, y)
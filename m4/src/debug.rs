@@ -0,0 +1,197 @@
+//! Debugging output, controlled using [`DebugFlags`] (the `-d` option or the `debugmode`
+//! builtin).
+
+use std::{ffi::OsStr, io::Write, os::unix::ffi::OsStrExt, path::Path};
+
+use crate::state::State;
+
+/// Which debugging information is written, specified using the GNU m4 flag letters. See
+/// [`DebugFlags::parse`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugFlags(u16);
+
+impl DebugFlags {
+    /// `a`: Show the actual arguments in each macro call trace.
+    pub const ARGS: Self = Self(1 << 0);
+    /// `c`: Show an additional trace line when a macro is recognized (before collecting its
+    /// arguments) and when it is called.
+    pub const CALL: Self = Self(1 << 1);
+    /// `e`: Show the expansion of each macro call trace.
+    pub const EXPANSION: Self = Self(1 << 2);
+    /// `f`: Include the current input file name in trace and debug output.
+    pub const FILE: Self = Self(1 << 3);
+    /// `i`: Print a message each time the current input file is changed.
+    pub const INPUT: Self = Self(1 << 4);
+    /// `l`: Include the current input line number in trace and debug output.
+    pub const LINE: Self = Self(1 << 5);
+    /// `p`: Print a message when a file is found using the include path search.
+    pub const PATH: Self = Self(1 << 6);
+    /// `q`: Quote the arguments and expansion in trace output using the current quotes.
+    pub const QUOTE: Self = Self(1 << 7);
+    /// `t`: Trace all macro calls, not just those enabled using `traceon` or `-t`.
+    pub const TRACE_ALL: Self = Self(1 << 8);
+    /// `x`: Add a unique identifier to each macro call trace.
+    pub const CALL_ID: Self = Self(1 << 9);
+    /// `V`: All of the above.
+    pub const ALL: Self = Self((1 << 10) - 1);
+    /// The flags used when `-d` is specified without any flags.
+    pub const DEFAULT: Self = Self(Self::ARGS.0 | Self::EXPANSION.0 | Self::QUOTE.0);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// These flags without any of the flags in `other`.
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Parse the flag letters `acefilpqtxV`, an empty string gives [`DebugFlags::DEFAULT`]. On
+    /// failure returns the unrecognized character.
+    pub fn parse(input: &[u8]) -> std::result::Result<Self, u8> {
+        if input.is_empty() {
+            return Ok(Self::DEFAULT);
+        }
+        input.iter().try_fold(Self::empty(), |flags, c| {
+            let flag = match c {
+                b'a' => Self::ARGS,
+                b'c' => Self::CALL,
+                b'e' => Self::EXPANSION,
+                b'f' => Self::FILE,
+                b'i' => Self::INPUT,
+                b'l' => Self::LINE,
+                b'p' => Self::PATH,
+                b'q' => Self::QUOTE,
+                b't' => Self::TRACE_ALL,
+                b'x' => Self::CALL_ID,
+                b'V' => Self::ALL,
+                _ => return Err(*c),
+            };
+            Ok(flags | flag)
+        })
+    }
+}
+
+impl std::ops::BitOr for DebugFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Parses the value of the `-d` option using [`DebugFlags::parse`].
+#[derive(Clone)]
+pub struct DebugFlagsParser;
+
+impl clap::builder::TypedValueParser for DebugFlagsParser {
+    type Value = DebugFlags;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> std::result::Result<Self::Value, clap::Error> {
+        DebugFlags::parse(value.as_encoded_bytes()).map_err(|_| {
+            let mut e = clap::Error::new(clap::error::ErrorKind::ValueValidation).with_cmd(cmd);
+            if let Some(arg) = arg {
+                e.insert(
+                    clap::error::ContextKind::InvalidArg,
+                    clap::error::ContextValue::String(arg.to_string()),
+                );
+            }
+            e.insert(
+                clap::error::ContextKind::InvalidValue,
+                clap::error::ContextValue::String(value.to_string_lossy().to_string()),
+            );
+            e
+        })
+    }
+}
+
+/// Where the debug output (traces, `dumpdef` and `m4debug:` messages) is written, see
+/// [`crate::Args::debugfile`] and [`crate::macros::DebugfileMacro`].
+#[derive(Debug, Default)]
+pub enum DebugFile {
+    /// Standard error.
+    #[default]
+    Stderr,
+    /// Debug output is discarded, selected using an empty file name.
+    Discard,
+    File(std::fs::File),
+}
+
+impl DebugFile {
+    /// Open the file at `path` for appending, an empty `path` discards the debug output.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        if path.as_os_str().is_empty() {
+            return Ok(Self::Discard);
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Self::File)
+    }
+
+    /// Open the file named by the raw bytes of `path`, see [`DebugFile::open`].
+    pub fn open_bytes(path: &[u8]) -> std::io::Result<Self> {
+        Self::open(Path::new(OsStr::from_bytes(path)))
+    }
+}
+
+/// Write `buf` to the current [`State::debug_file`].
+pub(crate) fn write_debug(
+    state: &State,
+    stderr: &mut dyn Write,
+    buf: &[u8],
+) -> std::io::Result<()> {
+    match &state.debug_file {
+        DebugFile::Stderr => stderr.write_all(buf),
+        DebugFile::Discard => Ok(()),
+        DebugFile::File(file) => {
+            let mut file: &std::fs::File = file;
+            file.write_all(buf)
+        }
+    }
+}
+
+/// Write the location prefix used by trace and debug output, `{prefix}:file:line:` depending on
+/// [`DebugFlags::FILE`] and [`DebugFlags::LINE`].
+pub(crate) fn write_location_prefix(
+    state: &State,
+    prefix: &str,
+    w: &mut dyn Write,
+) -> std::io::Result<()> {
+    w.write_all(prefix.as_bytes())?;
+    w.write_all(b":")?;
+    if state.debug_flags.contains(DebugFlags::FILE) {
+        if let Some(name) = state.input.name() {
+            w.write_all(&name)?;
+            w.write_all(b":")?;
+        }
+    }
+    if state.debug_flags.contains(DebugFlags::LINE) {
+        if let Some(line) = state.input.line_number() {
+            write!(w, "{line}:")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a `m4debug:` message to the debug output.
+pub(crate) fn debug_message(
+    state: &State,
+    stderr: &mut dyn Write,
+    message: impl std::fmt::Display,
+) -> std::io::Result<()> {
+    let mut line = Vec::new();
+    write_location_prefix(state, "m4debug", &mut line)?;
+    writeln!(line, " {message}")?;
+    write_debug(state, stderr, &line)
+}
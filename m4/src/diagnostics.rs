@@ -0,0 +1,106 @@
+//! Warnings and errors in the machine readable format selected using
+//! `--diagnostics-format=json`, see [`crate::DiagnosticsFormat`].
+
+use std::io::Write;
+
+use crate::{error::Location, lexer::MacroName};
+
+/// Whether a [`Diagnostic`] is a warning or an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// A warning or error, written as a single line containing a JSON object by
+/// [`Diagnostic::write_json`].
+pub(crate) struct Diagnostic<'a> {
+    pub severity: Severity,
+    /// The [`crate::warning::WarningCategory::name`] of a warning, or
+    /// [`crate::error::Error::code`] of an error.
+    pub code: &'a str,
+    /// The message without the location or the `Warning:` label.
+    pub message: &'a str,
+    pub location: Option<&'a Location>,
+    /// Where each of the inputs enclosing `location` was included from, innermost first.
+    pub included_from: &'a [Location],
+    /// The macros being expanded, outermost first.
+    pub macros: &'a [MacroName],
+}
+
+impl Diagnostic<'_> {
+    /// Write the object followed by a newline, for example:
+    ///
+    /// ```text
+    /// {"severity":"warning","code":"too-few-args","message":"too few arguments to builtin `substr'","file":"in.m4","line":3,"column":13,"included_from":[],"macros":["substr"]}
+    /// ```
+    ///
+    /// `file`, `line` and `column` are `null` when there is no location, such as for an error in a
+    /// command line option. The location of each includer in `included_from` is an object with the
+    /// same three fields.
+    pub fn write_json(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        let mut line = Vec::new();
+        line.extend(b"{\"severity\":\"");
+        line.extend(self.severity.name().as_bytes());
+        line.extend(b"\",\"code\":");
+        write_string(&mut line, self.code.as_bytes());
+        line.extend(b",\"message\":");
+        write_string(&mut line, self.message.as_bytes());
+        line.push(b',');
+        match self.location {
+            Some(location) => write_location_fields(&mut line, location),
+            None => line.extend(b"\"file\":null,\"line\":null,\"column\":null"),
+        }
+        line.extend(b",\"included_from\":[");
+        for (i, location) in self.included_from.iter().enumerate() {
+            if i > 0 {
+                line.push(b',');
+            }
+            line.push(b'{');
+            write_location_fields(&mut line, location);
+            line.push(b'}');
+        }
+        line.extend(b"],\"macros\":[");
+        for (i, name) in self.macros.iter().enumerate() {
+            if i > 0 {
+                line.push(b',');
+            }
+            write_string(&mut line, name.as_bytes());
+        }
+        line.extend(b"]}\n");
+        w.write_all(&line)
+    }
+}
+
+fn write_location_fields(line: &mut Vec<u8>, location: &Location) {
+    line.extend(b"\"file\":");
+    write_string(line, &location.file);
+    line.extend(format!(",\"line\":{},\"column\":{}", location.line, location.column).as_bytes());
+}
+
+/// Write `s` as a JSON string. A byte which is not part of a valid UTF-8 sequence is replaced with
+/// U+FFFD, because JSON text must be Unicode.
+fn write_string(line: &mut Vec<u8>, s: &[u8]) {
+    line.push(b'"');
+    for c in String::from_utf8_lossy(s).chars() {
+        match c {
+            '"' => line.extend(b"\\\""),
+            '\\' => line.extend(b"\\\\"),
+            '\n' => line.extend(b"\\n"),
+            '\r' => line.extend(b"\\r"),
+            '\t' => line.extend(b"\\t"),
+            c if c < ' ' => line.extend(format!("\\u{:04x}", u32::from(c)).as_bytes()),
+            c => line.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    line.push(b'"');
+}
@@ -0,0 +1,225 @@
+//! The diversion buffers, see [`Diversions`] and [`crate::macros::DivertMacro`].
+
+use std::{
+    collections::BTreeMap,
+    io::{BufWriter, IoSlice, Read, Seek, Write},
+};
+
+/// The diversion buffers by number, like GNU m4 any positive number can be used rather than only
+/// 1 through to 9. Each buffer is owned here (in memory or in a temporary file, see
+/// [`Diversion`]) rather than shared, so that [`crate::output::Output`] only needs to say which
+/// diversion text is written to.
+#[derive(Default)]
+pub(crate) struct Diversions {
+    buffers: BTreeMap<i64, Diversion>,
+}
+
+impl Diversions {
+    /// Append `buf` to diversion `number`.
+    pub fn write_all(&mut self, number: DivertBufferNumber, buf: &[u8]) -> std::io::Result<()> {
+        self.buffers.entry(number.0).or_default().write_all(buf)
+    }
+
+    /// The numbers of the diversions which currently have a buffer, in numerical order.
+    pub fn numbers(&self) -> Vec<DivertBufferNumber> {
+        self.buffers
+            .keys()
+            .copied()
+            .map(DivertBufferNumber)
+            .collect()
+    }
+
+    /// Remove diversion `number`, so that its contents can be written elsewhere (which may be
+    /// another diversion).
+    pub fn remove(&mut self, number: DivertBufferNumber) -> Option<Diversion> {
+        self.buffers.remove(&number.0)
+    }
+
+    /// Remove all of the diversions, writing their contents to `w` in numerical order.
+    /// Consecutive diversions which are in memory are written together using
+    /// [`Write::write_vectored`], so that hundreds of small diversions take few system calls.
+    pub fn write_all_to(&mut self, w: &mut dyn Write) -> std::io::Result<()> {
+        let mut buffers = std::mem::take(&mut self.buffers);
+        let mut pending = Vec::new();
+        for buffer in buffers.values_mut() {
+            match buffer {
+                Diversion::Memory(contents) => {
+                    if !contents.is_empty() {
+                        pending.push(IoSlice::new(contents));
+                    }
+                }
+                Diversion::File(file) => {
+                    write_all_vectored(w, &mut pending)?;
+                    pending.clear();
+                    for_each_file_chunk(file, |chunk| w.write_all(chunk))?;
+                }
+            }
+        }
+        write_all_vectored(w, &mut pending)
+    }
+
+    /// Call `f` with the number and the whole contents of each non-empty diversion, in numerical
+    /// order, leaving them in place.
+    pub fn for_each(
+        &mut self,
+        mut f: impl FnMut(i64, &[u8]) -> crate::Result<()>,
+    ) -> crate::Result<()> {
+        for (number, buffer) in &mut self.buffers {
+            if buffer.is_empty()? {
+                continue;
+            }
+            let mut contents = Vec::new();
+            buffer.for_each_chunk(|chunk| {
+                contents.extend(chunk);
+                Ok(())
+            })?;
+            f(*number, &contents)?;
+        }
+        Ok(())
+    }
+}
+
+/// Once a diversion grows beyond this many bytes it is moved to a temporary file, see
+/// [`Diversion`].
+const DIVERSION_MEMORY_LIMIT: usize = 512 * 1024;
+
+/// The contents of a diversion buffer. Like GNU m4 it is kept in memory until it grows beyond
+/// [`DIVERSION_MEMORY_LIMIT`], then it is moved to a temporary file (which is unlinked straight
+/// away) so that large diversions do not need to be held in memory.
+pub(crate) enum Diversion {
+    Memory(Vec<u8>),
+    File(BufWriter<std::fs::File>),
+}
+
+impl Default for Diversion {
+    fn default() -> Self {
+        Self::Memory(Vec::new())
+    }
+}
+
+impl Diversion {
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if let Self::Memory(contents) = self {
+            if contents.len() + buf.len() <= DIVERSION_MEMORY_LIMIT {
+                contents.extend(buf);
+                return Ok(());
+            }
+            let mut file = BufWriter::new(temporary_file()?);
+            file.write_all(contents)?;
+            *self = Self::File(file);
+        }
+        match self {
+            Self::File(file) => file.write_all(buf),
+            Self::Memory(_) => unreachable!("moved to a file above"),
+        }
+    }
+
+    fn is_empty(&mut self) -> std::io::Result<bool> {
+        match self {
+            Self::Memory(contents) => Ok(contents.is_empty()),
+            Self::File(file) => {
+                Ok(file.get_mut().stream_position()? == 0 && file.buffer().is_empty())
+            }
+        }
+    }
+
+    /// Call `f` with each chunk of the contents, in order.
+    pub fn for_each_chunk(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        match self {
+            Self::Memory(contents) => f(contents),
+            Self::File(file) => for_each_file_chunk(file, f),
+        }
+    }
+}
+
+/// Call `f` with each chunk of the contents of a [`Diversion::File`], in order.
+fn for_each_file_chunk(
+    file: &mut BufWriter<std::fs::File>,
+    mut f: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    file.flush()?;
+    let file = file.get_mut();
+    let end = file.stream_position()?;
+    file.rewind()?;
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        f(&chunk[..n])?;
+    }
+    file.seek(std::io::SeekFrom::Start(end))?;
+    Ok(())
+}
+
+/// Write all of `bufs` to `w`, like the unstable `Write::write_all_vectored`.
+fn write_all_vectored(w: &mut dyn Write, mut bufs: &mut [IoSlice<'_>]) -> std::io::Result<()> {
+    while !bufs.is_empty() {
+        match w.write_vectored(bufs) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+/// Create a temporary file for a [`Diversion`] in `TMPDIR` (or `/tmp`), it is unlinked straight
+/// away so that it is removed once closed.
+fn temporary_file() -> std::io::Result<std::fs::File> {
+    use std::os::{fd::FromRawFd, unix::ffi::OsStrExt};
+
+    let directory = std::env::var_os("TMPDIR")
+        .filter(|directory| !directory.is_empty())
+        .unwrap_or_else(|| "/tmp".into());
+    let mut name = directory.as_bytes().to_vec();
+    if !name.ends_with(b"/") {
+        name.push(b'/');
+    }
+    name.extend(b"m4-XXXXXX\0");
+    if name[..name.len() - 1].contains(&0) {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    }
+    // SAFETY: `name` is a nul terminated string which `mkstemp` modifies in place, without
+    // changing its length.
+    let file_descriptor = unsafe { libc::mkstemp(name.as_mut_ptr().cast()) };
+    if file_descriptor < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: `file_descriptor` was just opened by `mkstemp` and is not used elsewhere.
+    let file = unsafe { std::fs::File::from_raw_fd(file_descriptor) };
+    // SAFETY: `name` is still nul terminated.
+    if unsafe { libc::unlink(name.as_ptr().cast()) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DivertBufferNumber(pub(crate) i64);
+
+impl std::fmt::Display for DivertBufferNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl TryFrom<i64> for DivertBufferNumber {
+    type Error = crate::Error;
+
+    fn try_from(value: i64) -> std::prelude::v1::Result<Self, Self::Error> {
+        if value < 1 {
+            return Err(
+                crate::Error::new(crate::ErrorKind::Parsing).add_context(format!(
+                    "Unexpected buffer number: {value}. Needs to be positive"
+                )),
+            );
+        }
+        Ok(Self(value))
+    }
+}
@@ -1,9 +1,34 @@
 use std::borrow::Cow;
 
+use crate::lexer::MacroName;
+
 pub struct Error {
     pub kind: ErrorKind,
     context: Vec<Cow<'static, str>>,
     source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    /// See [`Error::location`] and [`Error::included_from`], boxed to keep [`Result`] small.
+    location: Option<Box<(Location, Vec<Location>)>>,
+    /// See [`Error::macros`].
+    macros: Vec<MacroName>,
+}
+
+/// A position in an input, see [`Error::location`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The name of the input, as used in diagnostics and `#line` directives (a file name or
+    /// `stdin`).
+    pub file: Vec<u8>,
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column of the last character read on the line, starting at 1. It counts bytes, and is
+    /// 0 when nothing has been read from the line yet.
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", String::from_utf8_lossy(&self.file), self.line)
+    }
 }
 
 impl Error {
@@ -12,7 +37,79 @@ impl Error {
             kind,
             context: Vec::new(),
             source: None,
+            location: None,
+            macros: Vec::new(),
+        }
+    }
+
+    /// Record that the error occurred at `location`, in an input which was included from each of
+    /// `included_from` (innermost first). An error which already has a location keeps it.
+    pub fn with_location(mut self, location: Location, included_from: Vec<Location>) -> Self {
+        if self.location.is_none() {
+            self.location = Some(Box::new((location, included_from)));
+        }
+        self
+    }
+
+    /// Where in the input the error occurred, the position of the last character which was read.
+    /// Errors which are not caused by the input (such as failing to write the output) may still
+    /// have one.
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref().map(|location| &location.0)
+    }
+
+    /// Where each of the inputs enclosing [`Error::location`] was included from, innermost first.
+    /// Text saved by [`crate::macros::M4wrapMacro`] is not considered to be included.
+    pub fn included_from(&self) -> &[Location] {
+        self.location
+            .as_ref()
+            .map_or(&[], |location| location.1.as_slice())
+    }
+
+    /// Record that the error occurred while expanding `macros` (outermost first). An error which
+    /// already has them keeps them.
+    pub fn with_macros(mut self, macros: Vec<MacroName>) -> Self {
+        if self.macros.is_empty() {
+            self.macros = macros;
+        }
+        self
+    }
+
+    /// The macros which were being expanded when the error occurred, outermost first. Except for
+    /// the limits and an unclosed argument list they are only recorded with
+    /// [`crate::DiagnosticsFormat::Json`], because it costs time for every macro call.
+    pub fn macros(&self) -> &[MacroName] {
+        match &self.kind {
+            ErrorKind::NestingLimitExceeded { macros, .. }
+            | ErrorKind::ExpansionLimitExceeded { macros, .. } => macros,
+            _ => &self.macros,
+        }
+    }
+
+    /// A short name for the kind of error, used as the `code` of
+    /// [`crate::DiagnosticsFormat::Json`].
+    pub fn code(&self) -> &'static str {
+        match self.kind {
+            ErrorKind::Evaluation => "evaluation",
+            ErrorKind::Parsing => "parsing",
+            ErrorKind::Io => "io",
+            ErrorKind::NotEnoughArguments => "too-few-args",
+            ErrorKind::UnclosedQuote => "eof-in-quote",
+            ErrorKind::UnclosedParenthesis => "eof-in-argument-list",
+            ErrorKind::NestingLimitExceeded { .. } => "nesting-limit",
+            ErrorKind::ExpansionLimitExceeded { .. } => "expansion-limit",
+            ErrorKind::UnsupportedFrozenFileVersion(_) => "frozen-file-version",
+            ErrorKind::Exit(_) => "exit",
+        }
+    }
+
+    /// The description of the error without its location, followed by the source (if any).
+    pub fn message(&self) -> String {
+        let mut message = Message(self).to_string();
+        if let Some(source) = &self.source {
+            message.push_str(&format!(": {source}"));
         }
+        message
     }
 
     pub fn add_context<C>(mut self, context: C) -> Self
@@ -42,6 +139,9 @@ impl std::fmt::Debug for Error {
                     .collect::<Vec<_>>(),
             )
             .field("source", &self.source)
+            .field("location", &self.location())
+            .field("included_from", &self.included_from())
+            .field("macros", &self.macros())
             .finish()
     }
 }
@@ -51,32 +151,44 @@ pub enum ErrorKind {
     Evaluation,
     Parsing,
     Io,
-    InvalidDivertNumber(i64),
     NotEnoughArguments,
     UnclosedQuote,
+    /// The input ended while collecting the arguments of a macro call.
     UnclosedParenthesis,
+    /// The number of nested macro expansions exceeded [`crate::Args::nesting_limit`].
+    NestingLimitExceeded {
+        limit: usize,
+        /// The macros being expanded, outermost first.
+        macros: Vec<MacroName>,
+    },
+    /// The total size of the macro expansions exceeded [`crate::Args::expansion_limit`].
+    ExpansionLimitExceeded {
+        limit: usize,
+        /// The macros being expanded, outermost first.
+        macros: Vec<MacroName>,
+    },
+    /// A frozen file (see [`crate::Args::reload_state`]) has a format version which is not
+    /// supported, exits with [`EXIT_MISMATCH`].
+    UnsupportedFrozenFileVersion(i64),
     /// NOTE: this isn't always an "error", if the code is 0, it indicates an intentional,
     /// successful, early program exit, just hijacking the [`Result`] semantics to help enable this
     /// in a purely functional manner.
     Exit(i32),
 }
 
+/// Like GNU m4 an error with a [`Error::location`] is formatted as `m4:file:line: message`, which
+/// editors recognize. It is followed by a line of the same form for each file it was included
+/// from.
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for c in self.context.iter().rev() {
-            write!(f, "{c}: ")?;
+        if let Some(location) = self.location() {
+            write!(f, "m4:{location}: ")?;
         }
+        Message(self).fmt(f)?;
 
-        match self.kind {
-            ErrorKind::Evaluation => write!(f, "Error evaluating input"),
-            ErrorKind::Parsing => write!(f, "Error parsing input"),
-            ErrorKind::Io => write!(f, "Error processing io"),
-            ErrorKind::InvalidDivertNumber(i) => write!(f, "Invalid divert number: {i}"),
-            ErrorKind::NotEnoughArguments => write!(f, "The macro doesn't have enough arguments"),
-            ErrorKind::Exit(code) => write!(f, "Program requested an exit with code {code}"),
-            ErrorKind::UnclosedQuote => write!(f, "Unclosed quote"),
-            ErrorKind::UnclosedParenthesis => write!(f, "Unclosed parenthesis"),
-        }?;
+        for location in self.included_from() {
+            write!(f, "\nm4:{location}: included from here")?;
+        }
 
         if f.alternate() {
             if let Some(source) = &self.source {
@@ -89,6 +201,68 @@ impl std::fmt::Display for Error {
     }
 }
 
+/// The context and the kind of an [`Error`], see [`Error::message`].
+struct Message<'a>(&'a Error);
+
+impl std::fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let error = self.0;
+        for c in error.context.iter().rev() {
+            write!(f, "{c}: ")?;
+        }
+
+        match error.kind {
+            ErrorKind::Evaluation => write!(f, "Error evaluating input"),
+            ErrorKind::Parsing => write!(f, "Error parsing input"),
+            ErrorKind::Io => write!(f, "Error processing io"),
+            ErrorKind::NotEnoughArguments => write!(f, "The macro doesn't have enough arguments"),
+            ErrorKind::UnsupportedFrozenFileVersion(version) => write!(
+                f,
+                "Frozen file version {version} greater than max supported of 2"
+            ),
+            ErrorKind::Exit(code) => write!(f, "Program requested an exit with code {code}"),
+            ErrorKind::UnclosedQuote => write!(f, "ERROR: end of file in string"),
+            ErrorKind::UnclosedParenthesis => write!(f, "ERROR: end of file in argument list"),
+            ErrorKind::NestingLimitExceeded { limit, ref macros } => {
+                write!(
+                    f,
+                    "Recursion limit of {limit} exceeded, use -L<N> to change it, while expanding: "
+                )?;
+                write_macro_chain(f, macros)
+            }
+            ErrorKind::ExpansionLimitExceeded { limit, ref macros } => {
+                write!(
+                    f,
+                    "Expansion limit of {limit} bytes exceeded, use --expansion-limit=<N> to change \
+                    it, while expanding: "
+                )?;
+                write_macro_chain(f, macros)
+            }
+        }
+    }
+}
+
+/// Write the chain of macro names separated by ` -> `, with consecutive repetitions of the same
+/// macro collapsed so that deep recursion remains readable.
+fn write_macro_chain(f: &mut std::fmt::Formatter<'_>, macros: &[MacroName]) -> std::fmt::Result {
+    let mut i = 0;
+    while i < macros.len() {
+        let repeats = macros[i..]
+            .iter()
+            .take_while(|name| **name == macros[i])
+            .count();
+        if i > 0 {
+            write!(f, " -> ")?;
+        }
+        write!(f, "{}", macros[i])?;
+        if repeats > 1 {
+            write!(f, " (x{repeats})")?;
+        }
+        i += repeats;
+    }
+    Ok(())
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self.source {
@@ -122,8 +296,21 @@ impl From<nom::Err<nom::error::Error<&[u8]>>> for Error {
     }
 }
 
+/// The description of `error` as used in diagnostics, like `strerror` without the
+/// ` (os error N)` suffix added by [`std::io::Error`].
+pub(crate) fn io_error_description(error: &std::io::Error) -> String {
+    let description = error.to_string();
+    match description.rfind(" (os error ") {
+        Some(end) => description[..end].to_owned(),
+        None => description,
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The exit status when a frozen file has an unsupported version, the same as GNU m4.
+pub const EXIT_MISMATCH: i32 = 63;
+
 pub trait GetExitCode {
     fn get_exit_code(&self) -> i32;
 }
@@ -132,6 +319,7 @@ impl GetExitCode for Error {
     fn get_exit_code(&self) -> i32 {
         match self.kind {
             ErrorKind::Exit(code) => code,
+            ErrorKind::UnsupportedFrozenFileVersion(_) => EXIT_MISMATCH,
             _ => 1,
         }
     }
@@ -0,0 +1,487 @@
+//! Frozen state files, see [`crate::Args::freeze_state`].
+//!
+//! A frozen file is a sequence of directives, each a single letter followed by comma separated
+//! decimal lengths on the same line, followed by the strings of those lengths (each string
+//! terminated by a `<newline>`). The format is version 2 of the one used by GNU m4, so frozen files
+//! are interchangeable with GNU m4 and `autom4te` caches. In version 2 the strings may contain
+//! backslash escape sequences, see [`FrozenReader::string`].
+//!
+//! * `V2` - Format version, must be the first directive.
+//! * `Q len1,len2` - Quote delimiters, if they have been changed from the default.
+//! * `C len1,len2` - Comment delimiters, if they have been changed from the default.
+//! * `T len1,len2[,len3]` - Define the macro `str1` with the text `str2`, pushing on top of any
+//!   existing definition. The optional `str3` is the GNU m4 module name, which is ignored.
+//! * `F len1,len2[,len3]` - Define the macro `str1` as the builtin named `str2`.
+//! * `t len` - Trace the macro `str`.
+//! * `D number,len` - Select the diversion `number` and append `str` to it. The final (possibly
+//!   empty) `D` directive restores the current diversion.
+//!
+//! Lines starting with `#` are comments.
+//!
+//! Reloading a frozen file (see [`crate::Args::reload_state`]) only involves reading the strings
+//! back out by their lengths, no macro processing is performed, which is much faster than
+//! processing the original input again.
+
+use std::{borrow::Cow, io::Write, path::Path, rc::Rc};
+
+use crate::{
+    error::{Result, ResultExt},
+    lexer::{
+        MacroName, DEFAULT_COMMENT_CLOSE_TAG, DEFAULT_COMMENT_OPEN_TAG, DEFAULT_QUOTE_CLOSE_TAG,
+        DEFAULT_QUOTE_OPEN_TAG,
+    },
+    macros::{BuiltinMacro, MacroDefinition, MacroDefinitionImplementation},
+    state::State,
+    warning::WarningCategory,
+    Error, ErrorKind,
+};
+
+/// Write `s` using the escape sequences understood by [`FrozenReader::string`]. The lengths in
+/// the directives refer to the unescaped string.
+fn write_escaped(w: &mut dyn Write, s: &[u8]) -> std::io::Result<()> {
+    let mut start = 0;
+    for (i, c) in s.iter().enumerate() {
+        let escaped: &[u8] = match c {
+            b'\\' => b"\\\\",
+            0x07 => b"\\a",
+            0x08 => b"\\b",
+            0x0c => b"\\f",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0x0b => b"\\v",
+            b' '..=b'~' => continue,
+            _ => {
+                w.write_all(&s[start..i])?;
+                write!(w, "\\{c:03o}")?;
+                start = i + 1;
+                continue;
+            }
+        };
+        w.write_all(&s[start..i])?;
+        w.write_all(escaped)?;
+        start = i + 1;
+    }
+    w.write_all(&s[start..])
+}
+
+/// Write a directive with the single string `s`.
+fn write_directive_1(w: &mut dyn Write, directive: u8, s: &[u8]) -> std::io::Result<()> {
+    writeln!(w, "{}{}", directive as char, s.len())?;
+    write_escaped(w, s)?;
+    w.write_all(b"\n")
+}
+
+/// Write a directive with the two strings `s1` and `s2`.
+fn write_directive_2(
+    w: &mut dyn Write,
+    directive: u8,
+    s1: &[u8],
+    s2: &[u8],
+) -> std::io::Result<()> {
+    writeln!(w, "{}{},{}", directive as char, s1.len(), s2.len())?;
+    write_escaped(w, s1)?;
+    write_escaped(w, s2)?;
+    w.write_all(b"\n")
+}
+
+/// Write the state to `w` in the frozen file format. Diversions are written by number, these
+/// should not have been undiverted.
+pub(crate) fn freeze(state: &State, w: &mut dyn Write) -> Result<()> {
+    writeln!(
+        w,
+        "# This is a frozen state file generated by {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    w.write_all(b"V2\n")?;
+
+    let config = &state.parse_config;
+    if config.quote_open_tag != DEFAULT_QUOTE_OPEN_TAG
+        || config.quote_close_tag != DEFAULT_QUOTE_CLOSE_TAG
+    {
+        write_directive_2(w, b'Q', &config.quote_open_tag, &config.quote_close_tag)?;
+    }
+    if !config.comment_enabled {
+        write_directive_2(w, b'C', &[], &[])?;
+    } else if config.comment_open_tag != DEFAULT_COMMENT_OPEN_TAG
+        || config.comment_close_tag != DEFAULT_COMMENT_CLOSE_TAG
+    {
+        write_directive_2(w, b'C', &config.comment_open_tag, &config.comment_close_tag)?;
+    }
+
+    for name in state.trace.traced_names() {
+        write_directive_1(w, b't', name.as_bytes())?;
+    }
+
+    // Sorted by name so that the file is the same on every run, like `dumpdef`.
+    let mut names: Vec<&MacroName> = state.macro_definitions.keys().collect();
+    names.sort();
+    for name in names {
+        let definitions = &state.macro_definitions[name];
+        // Oldest definition first, so that reloading pushes them back in the same order.
+        for definition in definitions {
+            match &definition.implementation {
+                MacroDefinitionImplementation::UserDefined(m) => {
+                    write_directive_2(w, b'T', name.as_bytes(), &m.definition)?
+                }
+                implementation => {
+                    let builtin = implementation
+                        .builtin()
+                        .expect("implementation is not user defined");
+                    write_directive_2(w, b'F', name.as_bytes(), builtin.as_ref())?
+                }
+            }
+        }
+    }
+
+    let current = state.output.output.divert_number();
+    let mut last_written = 0;
+    state.output.output.for_each_diversion(|number, contents| {
+        writeln!(w, "D{number},{}", contents.len())?;
+        write_escaped(w, contents)?;
+        w.write_all(b"\n")?;
+        last_written = number;
+        Ok(())
+    })?;
+    // Reloading a `D` directive also selects that diversion, so restore the current one.
+    if current != last_written {
+        write!(w, "D{current},0\n\n")?;
+    }
+    w.write_all(b"# End of frozen state file\n")?;
+
+    Ok(())
+}
+
+/// Create (or truncate) the file at `path` and [`freeze`] the state into it.
+pub(crate) fn freeze_to_file(state: &State, path: &Path) -> Result<()> {
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(path)
+            .map_err(crate::Error::from)
+            .add_context(|| format!("Error creating frozen state file {path:?}"))?,
+    );
+    freeze(state, &mut file)
+        .and_then(|_| file.flush().map_err(crate::Error::from))
+        .add_context(|| format!("Error writing frozen state file {path:?}"))
+}
+
+/// Reads the directives of a frozen file.
+struct FrozenReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    line: usize,
+    /// The version from the `V` directive, strings only contain escape sequences from version 2
+    /// onwards.
+    version: u32,
+}
+
+impl<'a> FrozenReader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            position: 0,
+            line: 1,
+            version: 0,
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> Error {
+        Error::new(ErrorKind::Parsing).add_context(format!("line {}: {message}", self.line))
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let c = *self.input.get(self.position)?;
+        self.position += 1;
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn expect_byte(&mut self) -> Result<u8> {
+        self.next_byte()
+            .ok_or_else(|| self.error("Unexpected end of file"))
+    }
+
+    fn expect_newline(&mut self) -> Result<()> {
+        match self.next_byte() {
+            Some(b'\n') => Ok(()),
+            _ => Err(self.error("Expected a newline")),
+        }
+    }
+
+    /// Read the comma separated decimal numbers following a directive, up to and including the
+    /// end of the line.
+    fn numbers(&mut self) -> Result<Vec<i64>> {
+        let start = self.position;
+        let end = self.input[start..]
+            .iter()
+            .position(|c| *c == b'\n')
+            .map(|i| start + i)
+            .ok_or_else(|| self.error("Unexpected end of file"))?;
+        let numbers = self.input[start..end]
+            .split(|c| *c == b',')
+            .map(|number| {
+                std::str::from_utf8(number)
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| {
+                        self.error(format!(
+                            "Expected a number, found {:?}",
+                            String::from_utf8_lossy(number)
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<i64>>>()?;
+        self.position = end;
+        self.expect_newline()?;
+        Ok(numbers)
+    }
+
+    /// Read the string lengths following a directive, there must be between `min` and `max` of
+    /// them.
+    fn lengths(&mut self, min: usize, max: usize) -> Result<Vec<usize>> {
+        let numbers = self.numbers()?;
+        if numbers.len() < min || numbers.len() > max {
+            return Err(self.error(format!(
+                "Expected {min} to {max} numbers, found {}",
+                numbers.len()
+            )));
+        }
+        self.to_lengths(&numbers)
+    }
+
+    fn to_lengths(&self, numbers: &[i64]) -> Result<Vec<usize>> {
+        numbers
+            .iter()
+            .map(|n| usize::try_from(*n).map_err(|_| self.error(format!("Invalid length {n}"))))
+            .collect()
+    }
+
+    /// Read a string which is `len` bytes long once any escape sequences (`\\`, `\a`, `\b`,
+    /// `\f`, `\n`, `\r`, `\t`, `\v`, `\xHH` and `\OOO`) have been decoded. A `\` followed by a
+    /// `<newline>` is ignored.
+    fn string(&mut self, len: usize) -> Result<Cow<'a, [u8]>> {
+        if self.version < 2 {
+            let input: &'a [u8] = self.input;
+            let s = input
+                .get(self.position..self.position.saturating_add(len))
+                .ok_or_else(|| self.error("Unexpected end of file"))?;
+            self.position += len;
+            self.line += s.iter().filter(|c| **c == b'\n').count();
+            return Ok(Cow::Borrowed(s));
+        }
+
+        let mut s = Vec::with_capacity(len);
+        while s.len() < len {
+            let c = self.expect_byte()?;
+            if c != b'\\' {
+                s.push(c);
+                continue;
+            }
+            let c = self.expect_byte()?;
+            match c {
+                b'a' => s.push(0x07),
+                b'b' => s.push(0x08),
+                b'f' => s.push(0x0c),
+                b'n' => s.push(b'\n'),
+                b'r' => s.push(b'\r'),
+                b't' => s.push(b'\t'),
+                b'v' => s.push(0x0b),
+                b'\n' => {}
+                b'x' => s.push(self.escaped_number(16, 2)?),
+                b'0'..=b'7' => {
+                    self.position -= 1;
+                    s.push(self.escaped_number(8, 3)?)
+                }
+                b'\\' | b'"' | b'\'' | b'?' => s.push(c),
+                _ => {
+                    return Err(
+                        self.error(format!("Invalid escape sequence \\{}", c.escape_ascii()))
+                    )
+                }
+            }
+        }
+        Ok(Cow::Owned(s))
+    }
+
+    /// Read up to `max_digits` digits of a number in `radix`, for an escape sequence.
+    fn escaped_number(&mut self, radix: u32, max_digits: usize) -> Result<u8> {
+        let mut value: u32 = 0;
+        let mut digits = 0;
+        while digits < max_digits {
+            let Some(digit) = self
+                .input
+                .get(self.position)
+                .and_then(|c| (*c as char).to_digit(radix))
+            else {
+                break;
+            };
+            value = value * radix + digit;
+            self.position += 1;
+            digits += 1;
+        }
+        if digits == 0 {
+            return Err(self.error("Invalid escape sequence, expected a digit"));
+        }
+        u8::try_from(value).map_err(|_| self.error(format!("Escaped value {value} is too large")))
+    }
+
+    /// Read the lengths and then the two strings of a directive, up to and including the
+    /// terminating newline. Directives which accept an optional third string (the module name for
+    /// `T` and `F`) have it read and discarded.
+    fn strings_2(&mut self, max: usize) -> Result<[Cow<'a, [u8]>; 2]> {
+        let lengths = self.lengths(2, max)?;
+        let s1 = self.string(lengths[0])?;
+        let s2 = self.string(lengths[1])?;
+        if let Some(len) = lengths.get(2) {
+            self.string(*len)?;
+        }
+        self.expect_newline()?;
+        Ok([s1, s2])
+    }
+
+    /// Read the length and then the string of a directive, up to and including the terminating
+    /// newline.
+    fn string_1(&mut self) -> Result<Cow<'a, [u8]>> {
+        let lengths = self.lengths(1, 1)?;
+        let s = self.string(lengths[0])?;
+        self.expect_newline()?;
+        Ok(s)
+    }
+}
+
+/// Restore the state saved by [`freeze`] from `input`. The frozen file contains every macro
+/// definition (including the builtins), so any existing definitions are removed first.
+///
+/// Both version 1 and version 2 of the GNU m4 format are accepted. The `R` (regular expression
+/// syntax), `S` (syntax table), `M` (module) and `d` (debug mode) directives that GNU m4 may
+/// produce are read but have no effect.
+pub(crate) fn reload(mut state: State, input: &[u8], stderr: &mut dyn Write) -> Result<State> {
+    state.macro_definitions.clear();
+    let mut reader = FrozenReader::new(input);
+
+    while let Some(directive) = reader.next_byte() {
+        match directive {
+            b'\n' => continue,
+            b'#' => {
+                while !matches!(reader.next_byte(), Some(b'\n') | None) {}
+                continue;
+            }
+            b'V' => {
+                let version = reader.numbers()?;
+                match version[..] {
+                    [version @ (1 | 2)] => reader.version = version as u32,
+                    [version] => {
+                        return Err(Error::new(ErrorKind::UnsupportedFrozenFileVersion(version)))
+                    }
+                    _ => return Err(reader.error("Expected a single version number")),
+                }
+                continue;
+            }
+            _ if reader.version == 0 => {
+                return Err(reader.error("Expected a V directive before any other directives"))
+            }
+            _ => {}
+        }
+
+        match directive {
+            b'Q' => {
+                // An empty open quote disables quoting, the same as `changequote`.
+                let [open, close] = reader.strings_2(2)?;
+                state.parse_config.quote_close_tag = if !open.is_empty() && close.is_empty() {
+                    DEFAULT_QUOTE_CLOSE_TAG.to_vec()
+                } else {
+                    close.into_owned()
+                };
+                state.parse_config.quote_open_tag = open.into_owned();
+            }
+            b'C' => {
+                let [open, close] = reader.strings_2(2)?;
+                state.parse_config.comment_enabled = !open.is_empty();
+                if !open.is_empty() {
+                    state.parse_config.comment_open_tag = open.into_owned();
+                    state.parse_config.comment_close_tag = close.into_owned();
+                }
+            }
+            b'T' => {
+                let [name, text] = reader.strings_2(3)?;
+                let name = MacroName(name.into());
+                let definition = Rc::new(MacroDefinition::new_user_defined(
+                    name.clone(),
+                    text.into_owned(),
+                ));
+                state
+                    .macro_definitions
+                    .entry(name)
+                    .or_default()
+                    .push(definition);
+            }
+            b'F' => {
+                let [name, builtin_name] = reader.strings_2(3)?;
+                let Some(builtin) = BuiltinMacro::from_name(&builtin_name) else {
+                    state.warning(
+                        stderr,
+                        WarningCategory::Other,
+                        format!(
+                            "`{}' from frozen file not found in builtin table!",
+                            String::from_utf8_lossy(&builtin_name)
+                        ),
+                    )?;
+                    continue;
+                };
+                let name = MacroName(name.into());
+                let definition = Rc::new(MacroDefinition::new_builtin(name.clone(), builtin));
+                state
+                    .macro_definitions
+                    .entry(name)
+                    .or_default()
+                    .push(definition);
+            }
+            b't' => {
+                let name = reader.string_1()?;
+                state.trace.enable(MacroName(name.into()));
+            }
+            b'd' | b'M' | b'R' => {
+                reader.string_1()?;
+            }
+            b'S' => {
+                // The syntax category precedes the length.
+                reader.expect_byte()?;
+                reader.string_1()?;
+            }
+            b'D' => {
+                let numbers = reader.numbers()?;
+                let [number, len] = numbers[..] else {
+                    return Err(reader.error("Expected a diversion number and a length"));
+                };
+                let len = reader.to_lengths(&[len])?[0];
+                let text = reader.string(len)?;
+                reader.expect_newline()?;
+                state.output.output.divert(number)?;
+                state.output.output.write_all(&text)?;
+            }
+            other => {
+                return Err(reader.error(format!(
+                    "Unknown frozen file directive {}",
+                    other.escape_ascii()
+                )))
+            }
+        }
+    }
+
+    if reader.version == 0 {
+        return Err(reader.error("Expected a V directive"));
+    }
+
+    Ok(state)
+}
+
+/// Read the frozen file at `path` and [`reload`] the state from it.
+pub(crate) fn reload_from_file(state: State, path: &Path, stderr: &mut dyn Write) -> Result<State> {
+    let input = std::fs::read(path)
+        .map_err(Error::from)
+        .add_context(|| format!("Error opening frozen state file {path:?}"))?;
+    reload(state, &input, stderr)
+        .add_context(|| format!("Error reloading frozen state file {path:?}"))
+}
@@ -0,0 +1,282 @@
+//! Regular expressions as used by GNU m4, which uses the GNU Emacs syntax. These are translated
+//! into the syntax of the [`regex`] crate, see [`compile`].
+//!
+//! The differences from the [`regex`] syntax are:
+//!
+//! * `\(`, `\)` and `\|` are grouping and alternation, while `(`, `)` and `|` match themselves.
+//! * `{` and `}` match themselves, there are no interval expressions.
+//! * `^` is only an anchor at the start of the expression or of a group or alternative, and `$`
+//!   only at the end of one. `*`, `+` and `?` match themselves at the start.
+//! * `` \` `` and `\'` match the start and the end of the input, `\<` and `\>` word boundaries.
+//! * `\1` to `\9` back references, which are not supported.
+
+use regex::bytes::Regex;
+
+use crate::error::{Error, ErrorKind};
+
+type Result<T> = std::result::Result<T, InvalidRegex>;
+
+/// A regular expression which can not be compiled. It is displayed like GNU m4 with the pattern
+/// as written (not as translated) and the message of the GNU regex library, such as
+/// ``bad regular expression `\(': Unmatched ( or \(``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InvalidRegex {
+    pattern: Vec<u8>,
+    message: &'static str,
+}
+
+impl std::fmt::Display for InvalidRegex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "bad regular expression `{}': {}",
+            String::from_utf8_lossy(&self.pattern),
+            self.message
+        )
+    }
+}
+
+impl From<InvalidRegex> for Error {
+    fn from(error: InvalidRegex) -> Self {
+        Error::new(ErrorKind::Parsing).add_context(error.to_string())
+    }
+}
+
+/// Compile the GNU m4 regular expression `pattern`.
+pub(crate) fn compile(pattern: &[u8]) -> Result<Regex> {
+    compile_with(pattern, |translated| format!("(?-u){translated}"))
+}
+
+/// Compile the GNU m4 regular expression `pattern`, so that it only matches the whole input
+/// rather than anywhere within it.
+pub(crate) fn compile_whole(pattern: &[u8]) -> Result<Regex> {
+    compile_with(pattern, |translated| format!(r"(?-u)\A(?:{translated})\z"))
+}
+
+/// The bytes which can start a (non-empty) match of `pattern` at the start of the input, like the
+/// "fastmap" used by GNU m4.
+pub(crate) fn start_bytes(pattern: &[u8]) -> Result<Box<[bool; 256]>> {
+    use regex_automata::{
+        dfa::{dense, Automaton},
+        util::syntax,
+        Anchored, Input,
+    };
+
+    let translated = translate(pattern).map_err(|message| invalid(pattern, message))?;
+    let dfa = dense::Builder::new()
+        .syntax(syntax::Config::new().utf8(false))
+        .build(&format!("(?-u){translated}"))
+        .map_err(|_| invalid(pattern, TOO_BIG))?;
+    let start = dfa
+        .start_state_forward(&Input::new(&[]).anchored(Anchored::Yes))
+        .map_err(|_| invalid(pattern, BAD_PATTERN))?;
+
+    // Matches are reported one byte late, so a match state reached from the state after `c`
+    // (or the end of input state) is a match which includes `c`.
+    let can_match = |after_start: _| {
+        let mut seen = std::collections::HashSet::new();
+        let mut pending = vec![after_start];
+        while let Some(state) = pending.pop() {
+            if dfa.is_dead_state(state) || !seen.insert(state) {
+                continue;
+            }
+            if dfa.is_match_state(dfa.next_eoi_state(state)) {
+                return true;
+            }
+            for c in 0..=u8::MAX {
+                let next = dfa.next_state(state, c);
+                if dfa.is_match_state(next) {
+                    return true;
+                }
+                pending.push(next);
+            }
+        }
+        false
+    };
+
+    let mut start_bytes = Box::new([false; 256]);
+    for (c, is_start) in (0..=u8::MAX).zip(start_bytes.iter_mut()) {
+        *is_start = can_match(dfa.next_state(start, c));
+    }
+    Ok(start_bytes)
+}
+
+fn compile_with(pattern: &[u8], wrap: impl FnOnce(&str) -> String) -> Result<Regex> {
+    let translated = translate(pattern).map_err(|message| invalid(pattern, message))?;
+    Regex::new(&wrap(&translated)).map_err(|error| {
+        invalid(
+            pattern,
+            match error {
+                regex::Error::CompiledTooBig(_) => TOO_BIG,
+                _ => BAD_PATTERN,
+            },
+        )
+    })
+}
+
+fn invalid(pattern: &[u8], message: &'static str) -> InvalidRegex {
+    InvalidRegex {
+        pattern: pattern.to_vec(),
+        message,
+    }
+}
+
+// The messages of the GNU regex library.
+const BAD_PATTERN: &str = "Invalid regular expression";
+const TOO_BIG: &str = "Regular expression too big";
+const TRAILING_BACKSLASH: &str = "Trailing backslash";
+const UNMATCHED_BRACKET: &str = "Unmatched [, [^, [:, [., or [=";
+const UNMATCHED_OPEN: &str = "Unmatched ( or \\(";
+const UNMATCHED_CLOSE: &str = "Unmatched ) or \\)";
+const INVALID_RANGE_END: &str = "Invalid range end";
+const INVALID_CLASS: &str = "Invalid character class name";
+
+/// The character classes which can be used in a bracket expression, such as `[[:alpha:]]`.
+const CHARACTER_CLASSES: &[&[u8]] = &[
+    b"alnum", b"alpha", b"blank", b"cntrl", b"digit", b"graph", b"lower", b"print", b"punct",
+    b"space", b"upper", b"xdigit",
+];
+
+/// Translate `pattern` into the [`regex`] syntax, it needs to be compiled with the `u` flag
+/// disabled in order to match bytes rather than unicode characters.
+fn translate(pattern: &[u8]) -> std::result::Result<String, &'static str> {
+    let mut out = String::new();
+    // The number of groups which have not been closed yet.
+    let mut depth = 0usize;
+    // Whether the previous token started an expression, group or alternative.
+    let mut at_start = true;
+    let mut i = 0;
+    while i < pattern.len() {
+        let c = pattern[i];
+        i += 1;
+        let was_start = std::mem::replace(&mut at_start, false);
+        match c {
+            b'\\' => {
+                let Some(&e) = pattern.get(i) else {
+                    return Err(TRAILING_BACKSLASH);
+                };
+                i += 1;
+                match e {
+                    b'(' => {
+                        out.push('(');
+                        depth += 1;
+                        at_start = true;
+                    }
+                    b'|' => {
+                        out.push('|');
+                        at_start = true;
+                    }
+                    b')' => {
+                        depth = depth.checked_sub(1).ok_or(UNMATCHED_CLOSE)?;
+                        out.push(')');
+                    }
+                    b'`' => out.push_str(r"\A"),
+                    b'\'' => out.push_str(r"\z"),
+                    b'<' => out.push_str(r"\b{start}"),
+                    b'>' => out.push_str(r"\b{end}"),
+                    b'b' => out.push_str(r"\b"),
+                    b'B' | b'w' | b'W' => {
+                        out.push('\\');
+                        out.push(char::from(e));
+                    }
+                    b'1'..=b'9' => return Err("back references are not supported"),
+                    _ => push_literal(&mut out, e),
+                }
+            }
+            b'^' if was_start => {
+                out.push('^');
+                at_start = true;
+            }
+            b'$' if is_end(&pattern[i..]) => out.push('$'),
+            b'*' | b'+' | b'?' if !was_start => out.push(char::from(c)),
+            b'.' => out.push('.'),
+            b'[' => i += translate_bracket(&pattern[i..], &mut out)?,
+            _ => push_literal(&mut out, c),
+        }
+    }
+    if depth > 0 {
+        return Err(UNMATCHED_OPEN);
+    }
+    Ok(out)
+}
+
+/// Whether `rest` (the pattern after a `$`) ends the expression, a group or an alternative.
+fn is_end(rest: &[u8]) -> bool {
+    rest.is_empty() || rest.starts_with(b"\\)") || rest.starts_with(b"\\|")
+}
+
+/// Translate the bracket expression starting just after the `[`, returning the number of bytes of
+/// `rest` that were consumed (including the closing `]`).
+fn translate_bracket(rest: &[u8], out: &mut String) -> std::result::Result<usize, &'static str> {
+    out.push('[');
+    let mut i = 0;
+    if rest.first() == Some(&b'^') {
+        out.push('^');
+        i += 1;
+    }
+    // A `]` immediately after the opening `[` or `[^` is a member of the set.
+    let first = i;
+    // The previous member, which can start a range.
+    let mut previous: Option<u8> = None;
+    loop {
+        let Some(&c) = rest.get(i) else {
+            return Err(UNMATCHED_BRACKET);
+        };
+        if c == b']' && i > first {
+            out.push(']');
+            return Ok(i + 1);
+        }
+        if c == b'[' && rest.get(i + 1) == Some(&b':') {
+            if let Some(end) = rest[i + 2..].windows(2).position(|w| w == b":]") {
+                if !CHARACTER_CLASSES.contains(&&rest[i + 2..i + 2 + end]) {
+                    return Err(INVALID_CLASS);
+                }
+                let class = &rest[i..i + 2 + end + 2];
+                out.push_str(&String::from_utf8_lossy(class));
+                i += class.len();
+                previous = None;
+                continue;
+            }
+        }
+        match c {
+            // A range, or itself at the start or the end, the same as in the `regex` syntax.
+            b'-' => {
+                if let (Some(start), Some(&end)) = (previous, rest.get(i + 1)) {
+                    if end != b']' && end < start {
+                        return Err(INVALID_RANGE_END);
+                    }
+                }
+                out.push('-');
+                i += 1;
+                previous = None;
+                continue;
+            }
+            // Not special in GNU m4, but these need escaping in the `regex` syntax.
+            b'\\' | b'[' | b']' | b'&' | b'~' | b'^' => {
+                out.push('\\');
+                out.push(char::from(c));
+            }
+            _ => push_byte(out, c),
+        }
+        previous = Some(c);
+        i += 1;
+    }
+}
+
+/// Push `c` so that it matches itself.
+fn push_literal(out: &mut String, c: u8) {
+    if c.is_ascii() {
+        out.push_str(&regex::escape(&char::from(c).to_string()));
+    } else {
+        push_byte(out, c);
+    }
+}
+
+/// Push `c`, escaping it if it is not ASCII so that it matches the single byte.
+fn push_byte(out: &mut String, c: u8) {
+    if c.is_ascii() {
+        out.push(char::from(c));
+    } else {
+        out.push_str(&format!(r"\x{c:02X}"));
+    }
+}
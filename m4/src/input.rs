@@ -1,107 +1,132 @@
 use std::{
+    borrow::Cow,
     cell::RefCell,
-    io::{Read, Write},
+    io::{BufRead, Read},
     path::PathBuf,
     rc::Rc,
 };
 
-use crate::EOF;
+use crate::{error::Location, pushback::Pushback, MemoryMap, MEMORY_MAP_THRESHOLD};
+
+/// Quoted strings and comments are written to the output in pieces of about this many bytes (and
+/// plain text straight from the input, see [`InputState::read_plain`]), so that their size is not
+/// limited by the memory available. Only the arguments of a macro call (and macro names) need to
+/// be held whole, because they are collected before the macro is called.
+pub const TOKEN_CHUNK_SIZE: usize = 64 * 1024;
 
 #[derive(Default)]
 pub struct InputState {
     pub line_synchronization: bool,
+    /// See [`crate::Args::crlf`].
+    pub crlf: bool,
     pub input: Vec<Input>,
+    /// Incremented every time an input is pushed or popped, used to detect when the output needs
+    /// a full `#line NUM "FILE"` directive, see [`crate::output::Output`].
+    pub changes: usize,
+    /// The total number of bytes pushed back using [`InputState::pushback_string`], which is the
+    /// total size of the macro expansions. See [`crate::Args::expansion_limit`].
+    pub expanded: usize,
 }
 
 impl InputState {
     pub fn new(line_synchronization: bool) -> Self {
         Self {
             line_synchronization,
+            crlf: false,
             input: Vec::new(),
+            changes: 0,
+            expanded: 0,
         }
     }
 
-    pub fn input_push(
-        &mut self,
-        mut input: Input,
-        syncline_output: &mut dyn Write,
-    ) -> std::io::Result<()> {
-        if self.line_synchronization {
-            input.emit_syncline(syncline_output, false)?;
-        }
-
+    pub fn input_push(&mut self, input: Input) {
+        self.changes += 1;
         self.input.push(input);
-
-        Ok(())
     }
 
     pub fn input_pop(&mut self) -> Option<Input> {
+        self.changes += 1;
         self.input.pop()
     }
 
-    /// Get the next character to be parsed. First it tries to get one from the pushback buffer,
-    /// otherwise it gets one from the input file.
-    pub fn get_next_character(&mut self) -> crate::error::Result<u8> {
+    /// The next character to be parsed, from the pushback buffer if it is not empty and otherwise
+    /// from the current input, or `None` at the end of it. Any byte (including `NUL`) is a
+    /// character.
+    pub fn get_next_character(&mut self) -> crate::error::Result<Option<u8>> {
         let input = self.input.last_mut().unwrap();
-        if let Some(c) = input.pushback_buffer.pop() {
-            return Ok(c);
+        if let Some(c) = input.pushback.pop() {
+            return Ok(Some(c));
         }
         Ok(input.get_next_character()?)
     }
 
     pub fn pushback_character(&mut self, c: u8) {
-        self.input.last_mut().unwrap().pushback_buffer.push(c);
+        self.input.last_mut().unwrap().pushback.push(c);
+    }
+
+    /// Pass the characters to be parsed next to `write` (without copying them), for as long as
+    /// they are plain (they are not the start of a token which needs to be parsed). This avoids
+    /// parsing plain text one character at a time, which is where m4 spends most of its time.
+    /// Stops at the end of the pushback buffer, or of the data currently buffered from the input
+    /// file.
+    ///
+    /// `plain_len` is called with the characters available next, in the order they are read, and
+    /// returns how many of them at the start are plain, it is called again with the following
+    /// characters only if it accepted all of them. It must not accept `<newline>`, so that the
+    /// line number only needs updating for the first character.
+    pub fn read_plain(
+        &mut self,
+        plain_len: impl FnMut(&[u8]) -> usize,
+        write: impl FnMut(&[u8]) -> crate::error::Result<()>,
+    ) -> crate::error::Result<()> {
+        let input = self.input.last_mut().unwrap();
+        if !input.pushback.is_empty() {
+            return input.pushback.read_plain(plain_len, write);
+        }
+        input.read_plain(plain_len, write)
     }
 
-    pub fn pushback_string(&mut self, s: &[u8]) {
-        let pushback_buffer = &mut self.input.last_mut().unwrap().pushback_buffer;
-        for c in s.iter().rev() {
-            pushback_buffer.push(*c);
+    /// Push back `s` to be read next. Borrowed text is copied, but owned text (such as an argument
+    /// of the macro call which expands to it) is pushed back without being copied, see
+    /// [`Pushback::push_owned`].
+    pub fn pushback_string<'a>(&mut self, s: impl Into<Cow<'a, [u8]>>) {
+        let s = s.into();
+        self.expanded += s.len();
+        let pushback = &mut self.input.last_mut().unwrap().pushback;
+        match s {
+            Cow::Borrowed(s) => pushback.push_slice(s),
+            Cow::Owned(s) => pushback.push_owned(s),
         }
     }
 
     /// Fetch new characters attempting to match them all to token. If any character doesn't match
     /// the token, then all the characters previously fetched are placed onto the pushback buffer.
     ///
-    /// **NOTE:** Will panic if token is not length of at least 1.
+    /// An empty token never matches, such as the open quote when quoting has been disabled.
     ///
     /// * `c` - First character of input which is already available.
     /// * `token` - Token to match against.
-    pub fn look_ahead(&mut self, mut c: u8, token: &[u8]) -> crate::error::Result<bool> {
-        if c == EOF || c != token[0] {
+    pub fn look_ahead(&mut self, c: u8, token: &[u8]) -> crate::error::Result<bool> {
+        if token.first() != Some(&c) {
             return Ok(false);
         }
 
-        let mut i = 1;
-        while i < token.len() {
-            c = self.get_next_character()?;
-            if c == EOF || c != token[i] {
-                loop {
-                    self.pushback_character(token[i]);
-                    if i == 0 {
-                        break;
-                    }
-                    i -= 1;
+        for i in 1..token.len() {
+            let next = self.get_next_character()?;
+            if next != Some(token[i]) {
+                // Put back what was read after `c`, which the caller still has.
+                if let Some(next) = next {
+                    self.pushback_character(next);
+                }
+                for c in token[1..i].iter().rev() {
+                    self.pushback_character(*c);
                 }
                 return Ok(false);
             }
-
-            i += 1;
         }
 
         Ok(true)
     }
-
-    fn emit_syncline(
-        &mut self,
-        output: &mut dyn Write,
-        check_line_numbers: bool,
-    ) -> std::io::Result<()> {
-        self.input
-            .last_mut()
-            .expect("at least one input")
-            .emit_syncline(output, check_line_numbers)
-    }
 }
 
 #[derive(Clone, Default)]
@@ -112,26 +137,20 @@ impl InputStateRef {
         Self(Rc::new(RefCell::new(input_state)))
     }
 
-    pub fn with<F, T>(&self, f: F) -> T
-    where
-        F: Fn(&InputState) -> T,
-    {
-        f(&self.0.borrow())
-    }
-
     pub fn input_pop(&self) -> Option<Input> {
         self.0.borrow_mut().input_pop()
     }
 
-    pub fn input_push(&self, input: Input, syncline_output: &mut dyn Write) -> std::io::Result<()> {
-        self.0.borrow_mut().input_push(input, syncline_output)
+    pub fn input_push(&self, input: Input) {
+        self.0.borrow_mut().input_push(input)
     }
 
     pub fn input_len(&self) -> usize {
         self.0.borrow().input.len()
     }
 
-    pub fn get_next_character(&self) -> crate::error::Result<u8> {
+    /// See [`InputState::get_next_character`].
+    pub fn get_next_character(&self) -> crate::error::Result<Option<u8>> {
         self.0.borrow_mut().get_next_character()
     }
 
@@ -139,105 +158,375 @@ impl InputStateRef {
         self.0.borrow_mut().pushback_character(c)
     }
 
-    pub fn pushback_string(&self, s: &[u8]) {
+    /// See [`InputState::pushback_string`].
+    pub fn pushback_string<'a>(&self, s: impl Into<Cow<'a, [u8]>>) {
         self.0.borrow_mut().pushback_string(s)
     }
 
+    /// See [`InputState::read_plain`].
+    pub fn read_plain(
+        &self,
+        plain_len: impl FnMut(&[u8]) -> usize,
+        write: impl FnMut(&[u8]) -> crate::error::Result<()>,
+    ) -> crate::error::Result<()> {
+        self.0.borrow_mut().read_plain(plain_len, write)
+    }
+
     pub fn look_ahead(&self, c: u8, token: &[u8]) -> crate::error::Result<bool> {
         self.0.borrow_mut().look_ahead(c, token)
     }
 
-    pub fn emit_syncline(
-        &mut self,
-        output: &mut dyn Write,
-        check_line_numbers: bool,
-    ) -> std::io::Result<()> {
+    pub fn sync_lines(&self) -> bool {
+        self.0.borrow().line_synchronization
+    }
+
+    /// See [`InputState::crlf`].
+    pub fn crlf(&self) -> bool {
+        self.0.borrow().crlf
+    }
+
+    pub fn set_crlf(&self, enabled: bool) {
+        self.0.borrow_mut().crlf = enabled;
+    }
+
+    /// See [`InputState::changes`].
+    pub fn changes(&self) -> usize {
+        self.0.borrow().changes
+    }
+
+    /// See [`InputState::expanded`].
+    pub fn expanded(&self) -> usize {
+        self.0.borrow().expanded
+    }
+
+    /// The line number in the current input, or `None` if there is no input.
+    pub fn line_number(&self) -> Option<usize> {
+        self.0.borrow().input.last().map(|input| input.line_number)
+    }
+
+    /// Record the current position in the pushback buffer, see
+    /// [`InputStateRef::pushed_back_since`].
+    pub fn pushback_mark(&self) -> PushbackMark {
+        let input = self.0.borrow();
+        PushbackMark {
+            input_len: input.input.len(),
+            pushback_len: input
+                .input
+                .last()
+                .map(|input| input.pushback.len())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The text that has been pushed back onto the current input since `mark` was recorded, in
+    /// the order that it will be read. Empty if the current input has changed or characters
+    /// pushed back before `mark` have been read since.
+    pub fn pushed_back_since(&self, mark: PushbackMark) -> Vec<u8> {
+        let input = self.0.borrow();
+        if input.input.len() != mark.input_len {
+            return Vec::new();
+        }
+        input
+            .input
+            .last()
+            .and_then(|input| {
+                let pushed_back = input.pushback.len().checked_sub(mark.pushback_len)?;
+                Some(input.pushback.prefix(pushed_back))
+            })
+            .unwrap_or_default()
+    }
+
+    /// The location of the character most recently read from the current input, or `None` if
+    /// there is no input.
+    pub fn location(&self) -> Option<Location> {
+        self.0.borrow().input.last().map(Input::location)
+    }
+
+    /// The location in each of the inputs (files or standard input) below the current one, where
+    /// it was left for the next, innermost first. See [`crate::error::Error::included_from`].
+    pub fn included_from(&self) -> Vec<Location> {
+        let state = self.0.borrow();
+        let Some((_current, below)) = state.input.split_last() else {
+            return Vec::new();
+        };
+        below
+            .iter()
+            .rev()
+            .filter(|input| !matches!(input.input, InputRead::Wrapped { .. }))
+            .map(Input::location)
+            .collect()
+    }
+
+    /// Whether the current input is text saved by [`crate::macros::M4wrapMacro`], see
+    /// [`Input::wrapped`].
+    pub fn is_wrapped(&self) -> bool {
         self.0
-            .borrow_mut()
-            .emit_syncline(output, check_line_numbers)
+            .borrow()
+            .input
+            .last()
+            .is_some_and(|input| matches!(input.input, InputRead::Wrapped { .. }))
     }
 
-    pub fn sync_lines(&self) -> bool {
-        self.0.borrow().line_synchronization
+    /// The name of the current input, or `None` if there is no input.
+    pub fn name(&self) -> Option<Vec<u8>> {
+        self.0
+            .borrow()
+            .input
+            .last()
+            .map(|input| input.name().to_vec())
     }
 }
 
+/// See [`InputStateRef::pushback_mark`].
+#[derive(Clone, Copy)]
+pub struct PushbackMark {
+    input_len: usize,
+    pushback_len: usize,
+}
+
 pub struct Input {
     pub input: InputRead,
-    pub pushback_buffer: Vec<u8>,
+    /// Text to be read before any more of [`Input::input`], see [`Pushback`].
+    pub pushback: Pushback,
+    /// The line containing the character most recently read from [`Input::input`]. A `<newline>`
+    /// belongs to the line that it terminates.
     pub line_number: usize,
-    pub syncline_line_number: usize,
+    /// The column of the character most recently read from [`Input::input`], starting at 1, see
+    /// [`Location::column`].
+    pub column: usize,
+    /// The last character read was a `<newline>`, so the line number will be incremented when the
+    /// next character is read.
+    advance_line: bool,
 }
 
 impl Input {
     pub fn new(input: InputRead) -> Self {
         Self {
             input,
-            pushback_buffer: Vec::new(),
+            pushback: Pushback::default(),
             line_number: 1,
-            syncline_line_number: 0,
+            column: 0,
+            advance_line: false,
         }
     }
 
-    fn get_next_character(&mut self) -> std::io::Result<u8> {
+    /// The text saved by [`crate::macros::M4wrapMacro`] at line `line_number` of the input named
+    /// `name`, which is the location reported while it is being read.
+    pub fn wrapped(text: Vec<u8>, name: Vec<u8>, line_number: usize) -> Self {
+        Self {
+            input: InputRead::Wrapped { name },
+            pushback: Pushback::from(text),
+            line_number,
+            column: 0,
+            advance_line: false,
+        }
+    }
+
+    /// The name of this input, as used for `#line` directives.
+    pub fn name(&self) -> &[u8] {
+        match &self.input {
+            InputRead::File { path, .. } | InputRead::Mapped { path, .. } => {
+                path.as_os_str().as_encoded_bytes()
+            }
+            InputRead::Stdin(_) => b"stdin",
+            InputRead::Wrapped { name } => name,
+        }
+    }
+
+    /// See [`InputStateRef::location`].
+    fn location(&self) -> Location {
+        Location {
+            file: self.name().to_vec(),
+            line: self.line_number,
+            column: self.column,
+        }
+    }
+
+    /// See [`InputState::read_plain`], only files are read from in bulk.
+    fn read_plain(
+        &mut self,
+        mut plain_len: impl FnMut(&[u8]) -> usize,
+        mut write: impl FnMut(&[u8]) -> crate::error::Result<()>,
+    ) -> crate::error::Result<()> {
+        let n = match &mut self.input {
+            InputRead::File { file, .. } => {
+                let available = file.fill_buf()?;
+                let n = plain_len(available);
+                write(&available[..n])?;
+                file.consume(n);
+                n
+            }
+            InputRead::Mapped { map, position, .. } => {
+                let available = &map[*position..];
+                let n = plain_len(available);
+                write(&available[..n])?;
+                *position += n;
+                n
+            }
+            InputRead::Stdin(_) | InputRead::Wrapped { .. } => 0,
+        };
+        if n == 0 {
+            return Ok(());
+        }
+        if self.advance_line {
+            self.line_number += 1;
+            self.column = 0;
+            self.advance_line = false;
+        }
+        self.column += n;
+        Ok(())
+    }
+
+    fn get_next_character(&mut self) -> std::io::Result<Option<u8>> {
         let mut buf: [u8; 1] = [0; 1];
         let n = match &mut self.input {
             InputRead::File { file, .. } => file.read(&mut buf),
+            InputRead::Mapped { map, position, .. } => match map.get(*position) {
+                Some(c) => {
+                    buf[0] = *c;
+                    *position += 1;
+                    Ok(1)
+                }
+                None => Ok(0),
+            },
             InputRead::Stdin(s) => s.read(&mut buf),
+            InputRead::Wrapped { .. } => Ok(0),
         }?;
 
         if n == 0 {
-            return Ok(EOF);
+            return Ok(None);
         }
 
         let c = buf[0];
 
-        if c == b'\n' {
+        if self.advance_line {
             self.line_number += 1;
+            self.column = 0;
+            self.advance_line = false;
+        }
+        self.column += 1;
+        if c == b'\n' {
+            self.advance_line = true;
         }
 
-        Ok(c)
+        Ok(Some(c))
     }
+}
 
-    fn emit_syncline(
-        &mut self,
-        output: &mut dyn Write,
-        check_line_numbers: bool,
-    ) -> std::io::Result<()> {
-        let name = match &self.input {
-            InputRead::File { path, .. } => path.as_os_str().as_encoded_bytes(),
-            InputRead::Stdin(_) => b"stdin",
-        };
+#[derive(Debug)]
+pub enum InputRead {
+    /// A file which is read through a fixed-size buffer, so the memory used does not depend on the
+    /// size of the file. A token which is split across the end of the buffer is simply continued
+    /// from the next one, see [`TOKEN_CHUNK_SIZE`].
+    File {
+        file: std::io::BufReader<std::fs::File>,
+        path: PathBuf,
+    },
+    /// A regular file which is mapped into memory, see [`crate::MemoryMap`]. It is read directly from
+    /// the mapping, starting at `position`.
+    Mapped {
+        map: MappedFile,
+        position: usize,
+        path: PathBuf,
+    },
+    Stdin(std::io::Stdin),
+    /// The text saved by [`crate::macros::M4wrapMacro`], which is read from the
+    /// [`Input::pushback`]. `name` is the name of the input that `m4wrap` was called from,
+    /// see [`Input::wrapped`].
+    Wrapped {
+        name: Vec<u8>,
+    },
+}
 
-        log::debug!(
-            "Input::emit_syncline(): {} {check_line_numbers}",
-            String::from_utf8_lossy(name)
-        );
-        if check_line_numbers {
-            log::debug!(
-                "Input::emit_syncline(): syncline_line_number:{},line_number:{}",
-                self.syncline_line_number,
-                self.line_number
-            );
-            self.syncline_line_number += 1;
-            if self.syncline_line_number == self.line_number {
-                return Ok(());
-            }
+impl InputRead {
+    /// Read the already opened `file` at `path`, mapping it into memory if `memory_map` allows it
+    /// and it is a regular file. If it can not be mapped then it is read through a buffer
+    /// instead.
+    pub fn open(file: std::fs::File, path: PathBuf, memory_map: MemoryMap) -> Self {
+        let len = file
+            .metadata()
+            .ok()
+            .filter(|metadata| metadata.file_type().is_file())
+            .map(|metadata| metadata.len());
+        let map = match (memory_map, len) {
+            (MemoryMap::Always, Some(len)) => Some(len),
+            (MemoryMap::Auto, Some(len)) if len >= MEMORY_MAP_THRESHOLD => Some(len),
+            _ => None,
         }
+        .and_then(|len| MappedFile::new(&file, usize::try_from(len).ok()?).ok());
+        match map {
+            Some(map) => InputRead::Mapped {
+                map,
+                position: 0,
+                path,
+            },
+            None => InputRead::File {
+                file: std::io::BufReader::new(file),
+                path,
+            },
+        }
+    }
+}
 
-        output.write_all(b"#line ")?;
-        write!(output, "{}", self.line_number)?;
-        output.write_all(b" \"")?;
-        output.write_all(name)?;
-        output.write_all(b"\"\n")?;
+/// A read only (private) mapping of the whole of a file, which is unmapped when dropped.
+pub struct MappedFile {
+    ptr: std::ptr::NonNull<u8>,
+    len: usize,
+}
 
-        self.syncline_line_number = self.line_number;
-        Ok(())
+impl MappedFile {
+    /// Map the first `len` bytes of `file`, which must not be empty.
+    fn new(file: &std::fs::File, len: usize) -> std::io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        if len == 0 {
+            return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+        }
+        // SAFETY: A new mapping is created at an address chosen by the kernel, so no existing
+        // memory is affected. The file descriptor is only used for the duration of the call.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        // The file is read through from start to end.
+        // SAFETY: `ptr` and `len` are the mapping which was just created.
+        unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+        Ok(Self {
+            ptr: std::ptr::NonNull::new(ptr.cast()).expect("mmap succeeded"),
+            len,
+        })
     }
 }
 
-#[derive(Debug)]
-pub enum InputRead {
-    File { file: std::fs::File, path: PathBuf },
-    Stdin(std::io::Stdin),
+impl std::ops::Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: The mapping is readable for `len` bytes until it is dropped.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // SAFETY: The mapping is no longer borrowed, and is not used again.
+        unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+impl std::fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedFile")
+            .field("len", &self.len)
+            .finish()
+    }
 }
@@ -15,7 +15,7 @@
 //!
 //! TODO: should quotes support alphanumeric characters? Seems like GNU m4 at least doesn't.
 //!
-use std::ffi::OsStr;
+use std::{ffi::OsStr, sync::Arc};
 
 use nom::IResult;
 
@@ -31,6 +31,10 @@ pub struct MacroParseConfig {
 
 /// Configuration for parsing, affects what are considered macros, quotes or comments. Also keeps a
 /// record of the recusion limit for processing a [`Symbol`].
+///
+/// It is consulted for each token as the input is read rather than when text is buffered, so a
+/// change (such as by `changequote`) applies straight away, including to text which has already
+/// been pushed back but not yet read.
 #[derive(Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub(crate) struct ParseConfig {
@@ -40,6 +44,9 @@ pub(crate) struct ParseConfig {
     pub comment_open_tag: Vec<u8>,
     pub comment_close_tag: Vec<u8>,
     pub comment_enabled: bool,
+    /// What is considered a macro name, `None` uses the default `[_a-zA-Z][_a-zA-Z0-9]*`. See
+    /// [`crate::macros::ChangewordMacro`].
+    pub word_regex: Option<WordRegex>,
 }
 
 pub const DEFAULT_QUOTE_OPEN_TAG: &[u8] = b"`";
@@ -55,14 +62,88 @@ impl Default for ParseConfig {
             comment_open_tag: DEFAULT_COMMENT_OPEN_TAG.to_vec(),
             comment_close_tag: DEFAULT_COMMENT_CLOSE_TAG.to_vec(),
             comment_enabled: true,
+            word_regex: None,
         }
     }
 }
 
-// TODO(performance): small vec optimization could be possible
-/// The name of a macro.
-#[derive(PartialEq, Clone, Hash, Eq)]
-pub struct MacroName(pub Vec<u8>);
+impl ParseConfig {
+    /// Whether `c` can start a macro name.
+    pub fn is_word_start(&self, c: u8) -> bool {
+        match &self.word_regex {
+            Some(word_regex) => word_regex.start[usize::from(c)],
+            None => c == b'_' || is_alpha(c),
+        }
+    }
+
+    /// Interpret `name` (for example a macro argument to `define`) as a macro name. Any non-empty
+    /// name is accepted, like GNU m4, a name which can not be recognized in the input can still be
+    /// called using [`crate::macros::IndirMacro`].
+    pub fn macro_name(&self, name: &[u8]) -> crate::error::Result<MacroName> {
+        if name.is_empty() {
+            return MacroName::try_from_slice(name);
+        }
+        Ok(MacroName(name.into()))
+    }
+}
+
+/// A regular expression (in the GNU m4 syntax) defining what is considered a macro name, set
+/// using `changeword` or `-W`.
+///
+/// A word starts with a byte which can start a match of the expression, and is extended one byte
+/// at a time for as long as the whole word is still matched. If the expression contains a
+/// group, then the macro name is the part of the word matched by the first group.
+#[derive(Clone, Debug)]
+pub(crate) struct WordRegex {
+    regex: regex::bytes::Regex,
+    /// The bytes which can start a word.
+    start: Box<[bool; 256]>,
+}
+
+/// The default [`WordRegex`], which is equivalent to not having one.
+pub const DEFAULT_WORD_REGEX: &[u8] = b"[_a-zA-Z][_a-zA-Z0-9]*";
+
+impl WordRegex {
+    /// Compile `pattern`, returns `None` if it is empty or [`DEFAULT_WORD_REGEX`] in which case
+    /// the default macro name syntax is used.
+    pub fn new(pattern: &[u8]) -> Result<Option<Self>, crate::gnu_regex::InvalidRegex> {
+        if pattern.is_empty() || pattern == DEFAULT_WORD_REGEX {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            regex: crate::gnu_regex::compile_whole(pattern)?,
+            start: crate::gnu_regex::start_bytes(pattern)?,
+        }))
+    }
+
+    /// Whether the whole of `word` is matched, so that a word can be extended to it.
+    pub fn is_match(&self, word: &[u8]) -> bool {
+        self.regex.is_match(word)
+    }
+
+    /// The macro name for `word`, the part matched by the first group if there is one.
+    pub fn name<'w>(&self, word: &'w [u8]) -> &'w [u8] {
+        self.regex
+            .captures(word)
+            .and_then(|captures| captures.get(1))
+            .map_or(word, |name| name.as_bytes())
+    }
+}
+
+#[cfg(test)]
+impl PartialEq for WordRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+    }
+}
+
+/// The name of a macro. The bytes are shared, so that the same name used as a key in
+/// [`crate::state::State::macro_definitions`], in the definition itself, in stack frames and in the
+/// traced names is only allocated once, and cloning it is cheap.
+///
+/// It can be borrowed as `[u8]`, so that the lexer can look up a word without allocating a name.
+#[derive(PartialEq, Clone, Hash, Eq, PartialOrd, Ord)]
+pub struct MacroName(pub Arc<[u8]>);
 
 impl std::fmt::Debug for MacroName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -78,7 +159,18 @@ impl std::fmt::Display for MacroName {
     }
 }
 
+impl std::borrow::Borrow<[u8]> for MacroName {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl MacroName {
+    /// The bytes of the name.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
     /// Macro names shall consist of letters, digits, and underscores, where the first character is
     /// not a digit. Tokens not of this form shall not be treated as macros.
     /// `[_a-zA-Z][_a-zA-Z0-9]*`
@@ -101,10 +193,7 @@ impl MacroName {
             String::from_utf8_lossy(input)
         );
         let (remaining, rest) = nom::bytes::complete::take_while(is_word_char_end)(remaining)?;
-        Ok((
-            remaining,
-            Self(input[..(start.len() + rest.len())].to_vec()),
-        ))
+        Ok((remaining, Self(input[..(start.len() + rest.len())].into())))
     }
 
     pub fn parse_cmd(input: &OsStr) -> std::result::Result<Self, clap::Error> {
@@ -119,7 +208,7 @@ impl MacroName {
         })
     }
 
-    /// Parse macro name from a complete slice, not including the EOF byte.
+    /// Parse macro name from a complete slice.
     /// Mostly used for testing, use [`MacroName::parse`] instead for parsing.
     pub fn try_from_slice(input: &[u8]) -> crate::error::Result<Self> {
         let (_remaining, name) = nom::combinator::all_consuming(Self::parse)(input)
@@ -128,6 +217,32 @@ impl MacroName {
     }
 }
 
+/// Parses a command line option value as a [`MacroName`], such as the value of `-t`.
+#[derive(Clone)]
+pub struct MacroNameParser;
+
+impl clap::builder::TypedValueParser for MacroNameParser {
+    type Value = MacroName;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> std::result::Result<Self::Value, clap::Error> {
+        MacroName::parse_cmd(value).map_err(|mut e| {
+            e = e.with_cmd(cmd);
+            if let Some(arg) = arg {
+                e.insert(
+                    clap::error::ContextKind::InvalidArg,
+                    clap::error::ContextValue::String(arg.to_string()),
+                );
+            }
+            e
+        })
+    }
+}
+
 fn is_word_char_end(c: u8) -> bool {
     // TODO(safety): check safety!
     (unsafe { libc::isalnum(c.into()) } != 0) || c == b'_'
@@ -1,19 +1,40 @@
-use error::{Error, ErrorKind, Result};
+use debug::DebugFile;
+pub use debug::DebugFlags;
+use error::{Error, ErrorKind, Result, ResultExt};
 use input::{Input, InputRead};
 use lexer::MacroName;
 use macros::MacroDefinition;
 use state::State;
-use std::{cell::RefCell, ffi::OsStr, io::Write, path::PathBuf, rc::Rc};
+use std::{
+    cell::RefCell,
+    ffi::{OsStr, OsString},
+    io::{IsTerminal, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
+mod debug;
+mod diagnostics;
+mod diversions;
 pub mod error;
+mod freeze;
+mod gnu_regex;
+mod hash;
 mod input;
 mod lexer;
 mod macros;
 mod main_loop;
 mod output;
-mod precedence;
+mod profile;
+mod pushback;
 mod state;
-pub const EOF: u8 = b'\0';
+pub mod warning;
+/// See [`Args::nesting_limit`].
+pub const DEFAULT_NESTING_LIMIT: usize = 1024;
+/// The regular expression used by [`Args::warn_macro_sequence`] when none is specified, matching
+/// `${...}` and multi-digit argument references such as `$10`.
+pub const DEFAULT_MACRO_SEQUENCE: &[u8] = br"\$\({[^}]*}\|[0-9][0-9]+\)";
 
 #[derive(Debug, Clone)]
 pub struct ArgumentDefine {
@@ -52,7 +73,8 @@ impl ArgumentDefine {
 }
 
 /// Define a symbol name to have some value [`DefineDirective::Define`] or NULL
-/// [`DefineDirective::Undefine`].
+/// [`DefineDirective::Undefine`], or trace it [`DefineDirective::Trace`]. These are applied in the
+/// order that they appear on the command line, before any input is processed.
 #[derive(Debug, Clone)]
 pub enum DefineDirective {
     /// `name[=val]`
@@ -61,28 +83,326 @@ pub enum DefineDirective {
     Define(ArgumentDefine),
     // Undefine `name`.
     Undefine(MacroName),
+    /// Trace `name`, see [`macros::trace::TraceonMacro`]. Tracing is by name, so `name` does not
+    /// need to be defined yet, calls are traced once it is.
+    Trace(MacroName),
 }
 
-#[derive(Debug, Clone, Default)]
+/// How warnings affect processing, see [`Args::fatal_warnings`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FatalWarnings {
+    /// Warnings are printed, processing continues and the exit status is unaffected.
+    #[default]
+    Disabled,
+    /// `-E`: Warnings are treated as errors, processing continues but the exit status will be
+    /// non-zero.
+    ExitStatus,
+    /// `-E -E`: Processing halts with a non-zero exit status at the first warning or error.
+    Halt,
+}
+
+impl FatalWarnings {
+    /// From the number of times `-E` was specified.
+    pub fn from_count(count: u8) -> Self {
+        match count {
+            0 => Self::Disabled,
+            1 => Self::ExitStatus,
+            _ => Self::Halt,
+        }
+    }
+}
+
+/// An option from System V m4 (or older versions of GNU m4) which is accepted so that existing
+/// scripts continue to work, but has no effect other than a warning. See
+/// [`Args::obsolete_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObsoleteOption {
+    /// `-B`, `-H`, `-S` or `-T`, which configured buffer and table sizes in System V m4.
+    SystemV(char),
+    /// `-N` or `--diversions`, the number of diversions is no longer limited.
+    Diversions,
+}
+
+impl std::fmt::Display for ObsoleteOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SystemV(option) => {
+                write!(f, "`m4 -{option}' may be removed in a future release")
+            }
+            Self::Diversions => write!(f, "`m4 -N' is deprecated"),
+        }
+    }
+}
+
+/// Which warnings are printed, see [`Args::verbosity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// All warnings are printed.
+    #[default]
+    Normal,
+    /// `-Q`: Warnings whose effect can be retrieved otherwise (such as missing or excess arguments
+    /// to a builtin) are suppressed, and do not count towards [`Args::fatal_warnings`].
+    Quiet,
+}
+
+/// How warnings and errors are written to standard error, see [`Args::diagnostics_format`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticsFormat {
+    /// Lines of text, like `m4:file:line: message` (depending on [`Args::mode`]).
+    #[default]
+    Text,
+    /// One JSON object per line, with the `severity` (`warning` or `error`), the `code` (the
+    /// [`warning::WarningCategory::name`] of a warning or [`Error::code`] of an error), the
+    /// `message`, the `file`, `line` and `column`, where the input was `included_from` and the
+    /// `macros` being expanded, so that editors and CI tools can parse them reliably. Debug and
+    /// trace output is unaffected.
+    Json,
+}
+
+impl DiagnosticsFormat {
+    /// Parse the value of `--diagnostics-format`: `text` or `json`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Which dialect of m4 is implemented, see [`Args::mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// `-g`: GNU extensions are enabled, such as the additional builtins (e.g. `debugfile` and
+    /// `__file__`) and warnings which include the location where they occurred.
+    #[default]
+    Gnu,
+    /// `-G`: All GNU extensions are disabled, only the traditional (POSIX) behaviour is available.
+    Traditional,
+}
+
+impl Mode {
+    /// The default mode for a program invoked as `program_name` (`argv[0]`): [`Mode::Gnu`] when
+    /// it is named `gm4`, otherwise [`Mode::Traditional`] as required by POSIX.
+    pub fn from_program_name(program_name: &OsStr) -> Self {
+        match Path::new(program_name).file_name() {
+            Some(name) if name == "gm4" => Mode::Gnu,
+            _ => Mode::Traditional,
+        }
+    }
+}
+
+/// Whether input files are mapped into memory rather than read through a buffer, see
+/// [`Args::memory_map`]. Mapping a large file avoids a read system call (and a copy) for
+/// each buffer full. Only regular files can be mapped, anything else (such as a pipe) is always
+/// read through a buffer.
+///
+/// A file which is truncated by another process while it is mapped causes m4 to be killed by
+/// `SIGBUS` when it reads past the new end, so files which may be modified while m4 runs should
+/// not be mapped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryMap {
+    /// Map files which are at least [`MEMORY_MAP_THRESHOLD`] bytes, smaller ones are cheaper to
+    /// read.
+    #[default]
+    Auto,
+    /// Map all (non-empty regular) files.
+    Always,
+    /// Never map files.
+    Never,
+}
+
+impl MemoryMap {
+    /// Parse the value of `--mmap`: `auto`, `always` or `never`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+
+/// The size in bytes from which files are mapped into memory by [`MemoryMap::Auto`].
+pub const MEMORY_MAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// How the output is written to the `stdout` passed to [`run`], see [`Args::output_buffering`].
+/// Diagnostics are written to `stderr` straight away, after flushing the buffered output so that
+/// they stay in sequence with the output which preceded them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputBuffering {
+    /// Every write is passed on (and flushed) straight away.
+    #[default]
+    Unbuffered,
+    /// Complete lines are passed on, a partial line is held until it is completed.
+    Line,
+    /// Passed on once at least this many bytes are buffered.
+    Block(usize),
+}
+
+/// See [`OutputBuffering::Block`], the size used when standard output is not a terminal.
+pub const DEFAULT_OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+impl OutputBuffering {
+    /// Parse the value of `--output-buffering`: `none`, `line`, `block` (using
+    /// [`DEFAULT_OUTPUT_BUFFER_SIZE`]) or a block size in bytes.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" | "0" => Some(Self::Unbuffered),
+            "line" => Some(Self::Line),
+            "block" => Some(Self::Block(DEFAULT_OUTPUT_BUFFER_SIZE)),
+            _ => value.parse().ok().map(Self::Block),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Args {
     /// Enable line synchronization output for the c99 preprocessor phase (that is, #line
     /// directives).
     pub line_synchronization: bool,
+    /// The input lines are terminated by `\r\n` (as on Windows) rather than `\n`, specified
+    /// using `--crlf`. Reading the input needs no special handling, as the `\r` comes just before
+    /// the `\n`: `dnl` discards both, a comment ending at the newline includes both, and lines are
+    /// counted by the `\n`. But the line synchronization directives (see
+    /// [`Args::line_synchronization`]) are terminated by `\r\n` as well, so that the line endings
+    /// of the output stay consistent.
+    pub crlf: bool,
     /// See [`DefineDirective`].
     pub define_directives: Vec<DefineDirective>,
-    /// Whether to read input from a file.
+    /// Directories to search (in order) for files included with `include` or `sinclude`, when they
+    /// are not found relative to the current working directory. Directories listed in the
+    /// `M4PATH` environment variable are searched afterwards.
+    pub include: Vec<PathBuf>,
+    /// Once all the input has been processed, save the state (macro definitions, quote and comment
+    /// delimiters, traced macros and diversions) to this file instead of outputting the
+    /// diversions.
+    pub freeze_state: Option<PathBuf>,
+    /// Before processing any of the [`DefineDirective`]s or input, restore the state from this
+    /// file, saved previously using [`Args::freeze_state`].
+    pub reload_state: Option<PathBuf>,
+    /// The maximum number of nested macro expansions, exceeding it is an error. `0` means there is
+    /// no limit.
+    pub nesting_limit: usize,
+    /// The maximum total number of bytes produced by macro expansions, exceeding it is an error
+    /// which stops a runaway recursive macro before it uses all of the memory or disk. It is
+    /// checked after each macro has been expanded. `0` (the default) means there is no limit.
+    /// Specified using `--expansion-limit`.
+    pub expansion_limit: usize,
+    /// Specified using `-E` (possibly multiple times), see [`FatalWarnings`].
+    pub fatal_warnings: FatalWarnings,
+    /// Specified using `-Q`, see [`Verbosity`].
+    pub verbosity: Verbosity,
+    /// The action for each category of warnings, specified using `--warning` (possibly multiple
+    /// times, applied in order), see [`warning::WarningControls`]. It takes precedence over
+    /// [`Args::fatal_warnings`] and [`Args::verbosity`] for the categories it specifies.
+    pub warnings: warning::WarningControls,
+    /// Specified using `--diagnostics-format`, see [`DiagnosticsFormat`].
+    pub diagnostics_format: DiagnosticsFormat,
+    /// Specified using `-d`, see [`DebugFlags`].
+    pub debug_flags: DebugFlags,
+    /// Specified using `-g` or `-G`, whichever appears last, otherwise it depends on the program
+    /// name (see [`Mode::from_program_name`]) unless an option which needs the GNU extensions (such
+    /// as `--debugfile`) is used.
+    pub mode: Mode,
+    /// Flush the output after every expansion and ignore interrupts (`SIGINT`), so that m4 can be
+    /// used interactively or in a pipeline.
+    pub interactive: bool,
+    /// Specified using `--output-buffering`, see [`OutputBuffering`]. Otherwise the output is
+    /// unbuffered for [`Args::interactive`], line buffered when standard output is a terminal and
+    /// block buffered when it is not.
+    pub output_buffering: OutputBuffering,
+    /// Specified using `--mmap`, see [`MemoryMap`].
+    pub memory_map: MemoryMap,
+    /// Rename all the builtin macros to have the `m4_` prefix (e.g. `m4_define`), so that the
+    /// unprefixed names are ordinary text.
+    pub prefix_builtins: bool,
+    /// Disable the builtins which could be abused by untrusted input: `syscmd`, `maketemp`,
+    /// `mkstemp` and `debugfile` with a file name. Calling them is an error instead.
+    pub safer: bool,
+    /// Make `len`, `index`, `substr` and `translit` operate on UTF-8 characters (Unicode scalar
+    /// values) rather than bytes, specified using `--utf8`. A byte which is not part of a valid
+    /// UTF-8 sequence is still a character by itself, so any input can be processed.
+    pub utf8: bool,
+    /// Record the number of calls, the time spent and the number of bytes produced by each macro,
+    /// and write a report to standard error at exit (including `m4exit`) with the slowest macros
+    /// first. Specified using `--profile`.
+    pub profile: bool,
+    /// Write the debug and trace output to this file (appending to it) instead of standard error.
+    /// An empty path discards the debug output. Specified using `--debugfile` or the BSD
+    /// compatible `-o`.
+    pub debugfile: Option<PathBuf>,
+    /// What is considered a macro name, a regular expression (GNU m4 syntax) specified using `-W`,
+    /// see [`macros::ChangewordMacro`].
+    pub word_regexp: Option<Vec<u8>>,
+    /// Warn when a macro definition contains a match of this (GNU m4 syntax) regular expression,
+    /// specified using `--warn-macro-sequence[=regex]`, see [`DEFAULT_MACRO_SEQUENCE`].
+    pub warn_macro_sequence: Option<Vec<u8>>,
+    /// Obsolete options that were specified, in order. A warning is printed for each of them.
+    pub obsolete_options: Vec<ObsoleteOption>,
+    /// The name the program was invoked as (`argv[0]`), which is the expansion of
+    /// `__program__`.
+    pub program_name: OsString,
+    /// Input files to read, in order. A file named `-` reads from standard input, and if no files
+    /// are specified then standard input is read.
     pub files: Vec<PathBuf>,
 }
 
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            line_synchronization: false,
+            crlf: false,
+            define_directives: Vec::new(),
+            include: Vec::new(),
+            freeze_state: None,
+            reload_state: None,
+            nesting_limit: DEFAULT_NESTING_LIMIT,
+            expansion_limit: 0,
+            fatal_warnings: FatalWarnings::default(),
+            verbosity: Verbosity::default(),
+            warnings: warning::WarningControls::default(),
+            diagnostics_format: DiagnosticsFormat::default(),
+            debug_flags: DebugFlags::default(),
+            mode: Mode::default(),
+            interactive: false,
+            output_buffering: OutputBuffering::default(),
+            memory_map: MemoryMap::default(),
+            prefix_builtins: false,
+            safer: false,
+            utf8: false,
+            profile: false,
+            debugfile: None,
+            word_regexp: None,
+            warn_macro_sequence: None,
+            obsolete_options: Vec::new(),
+            program_name: OsString::from("m4"),
+            files: Vec::new(),
+        }
+    }
+}
+
 impl Args {
+    /// A builder for [`Args`] which is validated, see [`ArgsBuilder::build`].
+    pub fn builder() -> ArgsBuilder {
+        ArgsBuilder::default()
+    }
+
     pub fn parse() -> Self {
-        let matches = clap::command!()
+        let mut command = clap::command!()
             .arg(
                 clap::Arg::new("line_synchronization")
                     .short('s')
                     .help("Output line synchronization directives, suitable for cpp")
                     .action(clap::ArgAction::SetTrue),
             )
+            .arg(
+                clap::Arg::new("crlf")
+                    .long("crlf")
+                    .help("Input lines end with \\r\\n, also used for line synchronization")
+                    .action(clap::ArgAction::SetTrue),
+            )
             .arg(
                 clap::Arg::new("define")
                     .short('D')
@@ -98,10 +418,364 @@ impl Args {
                     .num_args(1)
                     .action(clap::ArgAction::Append),
             )
-            .arg(clap::Arg::new("file").action(clap::ArgAction::Append))
-            .get_matches();
+            .arg(
+                clap::Arg::new("trace")
+                    .short('t')
+                    .long("trace")
+                    .value_name("name")
+                    .help("Trace the macro name, even if it is not yet defined")
+                    .num_args(1)
+                    .value_parser(lexer::MacroNameParser)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                clap::Arg::new("include")
+                    .short('I')
+                    .long("include")
+                    .value_name("directory")
+                    .help("Append directory to the include path")
+                    .num_args(1)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                clap::Arg::new("freeze_state")
+                    .short('F')
+                    .long("freeze-state")
+                    .value_name("file")
+                    .help("Produce a frozen state on file at the end")
+                    .num_args(1),
+            )
+            .arg(
+                clap::Arg::new("reload_state")
+                    .short('R')
+                    .long("reload-state")
+                    .value_name("file")
+                    .help("Reload a frozen state from file at the start")
+                    .num_args(1),
+            )
+            .arg(
+                clap::Arg::new("nesting_limit")
+                    .short('L')
+                    .long("nesting-limit")
+                    .value_name("number")
+                    .help("Change nesting limit, 0 for unlimited")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(usize)),
+            )
+            .arg(
+                clap::Arg::new("expansion_limit")
+                    .long("expansion-limit")
+                    .value_name("bytes")
+                    .help("Limit the total size of macro expansions, 0 for unlimited")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(usize)),
+            )
+            .arg(
+                clap::Arg::new("fatal_warnings")
+                    .short('E')
+                    .long("fatal-warnings")
+                    .help("Once: warnings become errors, twice: stop execution at first error")
+                    .action(clap::ArgAction::Count),
+            )
+            .arg(
+                clap::Arg::new("warning")
+                    .long("warning")
+                    .value_name("control")
+                    .help("Control a category of warnings: CATEGORY, no-CATEGORY or error=CATEGORY")
+                    .action(clap::ArgAction::Append)
+                    .value_parser(|value: &str| {
+                        warning::WarningControls::default()
+                            .apply(value)
+                            .map(|()| value.to_owned())
+                            .map_err(|category| format!("unknown warning category {category:?}"))
+                    }),
+            )
+            .arg(
+                clap::Arg::new("diagnostics_format")
+                    .long("diagnostics-format")
+                    .value_name("format")
+                    .help("Write warnings and errors as text or json")
+                    .num_args(1)
+                    .value_parser(["text", "json"]),
+            )
+            .arg(
+                clap::Arg::new("quiet")
+                    .short('Q')
+                    .long("quiet")
+                    .visible_alias("silent")
+                    .help("Suppress some warnings for builtins")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("debug")
+                    .short('d')
+                    .long("debug")
+                    .value_name("flags")
+                    .help("Set debug level (no flags implies `aeq')")
+                    .num_args(1)
+                    .value_parser(debug::DebugFlagsParser),
+            )
+            .arg(
+                clap::Arg::new("debugfile")
+                    .long("debugfile")
+                    .value_name("file")
+                    .help("Redirect debug and trace output to file (discard if empty)")
+                    .num_args(0..=1)
+                    .require_equals(true)
+                    .default_missing_value(DEBUGFILE_STDERR),
+            )
+            .arg(
+                clap::Arg::new("trace_file")
+                    .short('o')
+                    .value_name("file")
+                    .help("Redirect trace output to file (BSD compatible, same as --debugfile)")
+                    .num_args(1),
+            )
+            .arg(
+                clap::Arg::new("word_regexp")
+                    .short('W')
+                    .long("word-regexp")
+                    .value_name("regex")
+                    .help("Use regex for macro name syntax")
+                    .num_args(1),
+            )
+            .arg(
+                clap::Arg::new("warn_macro_sequence")
+                    .long("warn-macro-sequence")
+                    .value_name("regex")
+                    .help("Warn if a macro definition matches regex (default $({...}|[0-9][0-9]+))")
+                    .num_args(0..=1)
+                    .require_equals(true)
+                    .default_missing_value(OsStr::from_bytes(DEFAULT_MACRO_SEQUENCE)),
+            )
+            .arg(
+                clap::Arg::new("gnu")
+                    .short('g')
+                    .long("gnu")
+                    .help("Enable the GNU extensions (the default when invoked as gm4)")
+                    .action(clap::ArgAction::SetTrue)
+                    .overrides_with("traditional"),
+            )
+            .arg(
+                clap::Arg::new("traditional")
+                    .short('G')
+                    .long("traditional")
+                    .help("Suppress all GNU extensions")
+                    .action(clap::ArgAction::SetTrue)
+                    .overrides_with("gnu")
+                    .conflicts_with_all(GNU_OPTIONS),
+            )
+            .arg(
+                clap::Arg::new("interactive")
+                    .short('e')
+                    .short_alias('i')
+                    .long("interactive")
+                    .help("Unbuffer output, ignore interrupts")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("output_buffering")
+                    .long("output-buffering")
+                    .value_name("mode")
+                    .help("Buffer the output: none, line, block or a block size in bytes")
+                    .num_args(1)
+                    .value_parser(|value: &str| {
+                        OutputBuffering::parse(value)
+                            .ok_or("expected none, line, block or a size in bytes")
+                    }),
+            )
+            .arg(
+                clap::Arg::new("memory_map")
+                    .long("mmap")
+                    .value_name("when")
+                    .help("Map input files into memory: auto (large files), always or never")
+                    .num_args(1)
+                    .value_parser(["auto", "always", "never"]),
+            )
+            .arg(
+                clap::Arg::new("prefix_builtins")
+                    .short('P')
+                    .long("prefix-builtins")
+                    .help("Force a `m4_' prefix to all builtins")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("safer")
+                    .long("safer")
+                    .help("Disable potentially unsafe builtins")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("utf8")
+                    .long("utf8")
+                    .help("String builtins count UTF-8 characters rather than bytes")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("profile")
+                    .long("profile")
+                    .help("Report the calls, time and output of each macro at exit")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .args(OBSOLETE_SYSTEM_V_OPTIONS.iter().map(|(option, id)| {
+                clap::Arg::new(*id)
+                    .short(*option)
+                    .num_args(1)
+                    .hide(true)
+                    .action(clap::ArgAction::Append)
+            }))
+            .arg(
+                clap::Arg::new("diversions")
+                    .short('N')
+                    .long("diversions")
+                    .num_args(1)
+                    .hide(true)
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(clap::Arg::new("file").action(clap::ArgAction::Append));
+        let matches = command
+            .try_get_matches_from_mut(attach_optional_values(std::env::args_os()))
+            .unwrap_or_else(|error| error.exit());
+
+        let program_name = std::env::args_os().next();
 
         let line_synchronization = matches.get_flag("line_synchronization");
+        let crlf = matches.get_flag("crlf");
+
+        let include = matches
+            .get_raw("include")
+            .unwrap_or_default()
+            .map(PathBuf::from)
+            .collect();
+
+        let freeze_state = matches
+            .get_raw("freeze_state")
+            .and_then(|mut values| values.next())
+            .map(PathBuf::from);
+
+        let reload_state = matches
+            .get_raw("reload_state")
+            .and_then(|mut values| values.next())
+            .map(PathBuf::from);
+
+        let nesting_limit = matches
+            .get_one::<usize>("nesting_limit")
+            .copied()
+            .unwrap_or(DEFAULT_NESTING_LIMIT);
+
+        let expansion_limit = matches
+            .get_one::<usize>("expansion_limit")
+            .copied()
+            .unwrap_or_default();
+
+        let fatal_warnings = FatalWarnings::from_count(matches.get_count("fatal_warnings"));
+
+        let mut warnings = warning::WarningControls::default();
+        for value in matches.get_many::<String>("warning").into_iter().flatten() {
+            // Already validated by the value parser.
+            let _ = warnings.apply(value);
+        }
+
+        let diagnostics_format = matches
+            .get_one::<String>("diagnostics_format")
+            .and_then(|value| DiagnosticsFormat::parse(value))
+            .unwrap_or_default();
+
+        let verbosity = if matches.get_flag("quiet") {
+            Verbosity::Quiet
+        } else {
+            Verbosity::Normal
+        };
+
+        let debug_flags = matches
+            .get_one::<DebugFlags>("debug")
+            .copied()
+            .unwrap_or_default();
+
+        // `-g` and `-G` override each other, so only the last one specified is set. Otherwise the
+        // mode depends on the name the program was invoked as, unless one of the GNU options
+        // (which conflict with `-G`) is used.
+        let mode = if matches.get_flag("traditional") {
+            Mode::Traditional
+        } else if matches.get_flag("gnu") || GNU_OPTIONS.iter().any(|id| matches.contains_id(id)) {
+            Mode::Gnu
+        } else {
+            program_name
+                .as_deref()
+                .map(Mode::from_program_name)
+                .unwrap_or_default()
+        };
+
+        let interactive = matches.get_flag("interactive");
+        let output_buffering = matches
+            .get_one::<OutputBuffering>("output_buffering")
+            .copied()
+            .unwrap_or_else(|| {
+                if interactive {
+                    OutputBuffering::Unbuffered
+                } else if std::io::stdout().is_terminal() {
+                    OutputBuffering::Line
+                } else {
+                    OutputBuffering::Block(DEFAULT_OUTPUT_BUFFER_SIZE)
+                }
+            });
+        let memory_map = matches
+            .get_one::<String>("memory_map")
+            .and_then(|value| MemoryMap::parse(value))
+            .unwrap_or_default();
+        let prefix_builtins = matches.get_flag("prefix_builtins");
+        let safer = matches.get_flag("safer");
+        let utf8 = matches.get_flag("utf8");
+        let profile = matches.get_flag("profile");
+
+        // `--debugfile` without a value selects standard error. The BSD `-o` option is equivalent,
+        // if both are specified the last one wins.
+        let debugfile = ["debugfile", "trace_file"]
+            .into_iter()
+            .filter_map(|id| {
+                let index = matches.index_of(id)?;
+                let path = matches
+                    .get_raw(id)
+                    .and_then(|mut values| values.next())
+                    .filter(|path| *path != DEBUGFILE_STDERR)
+                    .map(PathBuf::from);
+                Some((index, path))
+            })
+            .max_by_key(|(index, _)| *index)
+            .and_then(|(_, path)| path);
+
+        let word_regexp = matches
+            .get_raw("word_regexp")
+            .and_then(|mut values| values.next())
+            .map(|regex| regex.as_bytes().to_vec());
+
+        // An empty regular expression disables the warning.
+        let warn_macro_sequence = matches
+            .get_raw("warn_macro_sequence")
+            .and_then(|mut values| values.next())
+            .map(|regex| regex.as_bytes().to_vec())
+            .filter(|regex| !regex.is_empty());
+
+        let mut obsolete_options: Vec<(usize, ObsoleteOption)> = OBSOLETE_SYSTEM_V_OPTIONS
+            .iter()
+            .flat_map(|(option, id)| {
+                matches
+                    .indices_of(id)
+                    .unwrap_or_default()
+                    .map(|index| (index, ObsoleteOption::SystemV(*option)))
+            })
+            .chain(
+                matches
+                    .indices_of("diversions")
+                    .unwrap_or_default()
+                    .map(|index| (index, ObsoleteOption::Diversions)),
+            )
+            .collect();
+        obsolete_options.sort_by_key(|option| option.0);
+        let obsolete_options = obsolete_options
+            .into_iter()
+            .map(|option| option.1)
+            .collect();
 
         let files = matches
             .get_raw("file")
@@ -109,8 +783,8 @@ impl Args {
             .map(PathBuf::from)
             .collect();
 
-        // Order of defines and undefines is important, so we need to do this, otherwise we'd just
-        // use the clap derive macro instead.
+        // Order of defines, undefines and traces is important, so we need to do this, otherwise
+        // we'd just use the clap derive macro instead.
         let mut define_directives = Vec::new();
         let defines = matches.get_raw("define").unwrap_or_default();
         for (value, index) in defines.zip(matches.indices_of("define").unwrap_or_default()) {
@@ -122,14 +796,402 @@ impl Args {
             let value = MacroName::parse_cmd(value).expect("Invalid -U argument undefine");
             define_directives.push((index, DefineDirective::Undefine(value)));
         }
+        let traces = matches.get_many::<MacroName>("trace").unwrap_or_default();
+        for (value, index) in traces.zip(matches.indices_of("trace").unwrap_or_default()) {
+            define_directives.push((index, DefineDirective::Trace(value.clone())));
+        }
         define_directives.sort_by_key(|d| d.0);
         let define_directives = define_directives.into_iter().map(|d| d.1).collect();
 
-        Self {
+        let args = Self {
             line_synchronization,
+            crlf,
             define_directives,
+            include,
+            freeze_state,
+            reload_state,
+            nesting_limit,
+            expansion_limit,
+            fatal_warnings,
+            verbosity,
+            warnings,
+            diagnostics_format,
+            debug_flags,
+            mode,
+            interactive,
+            output_buffering,
+            memory_map,
+            prefix_builtins,
+            safer,
+            utf8,
+            profile,
+            debugfile,
+            word_regexp,
+            warn_macro_sequence,
+            obsolete_options,
+            program_name: program_name.unwrap_or_else(|| OsString::from("m4")),
             files,
+        };
+        if let Err(error) = args.validate() {
+            command
+                .error(clap::error::ErrorKind::ArgumentConflict, error)
+                .exit();
+        }
+        args
+    }
+
+    /// Check for nonsensical combinations of arguments, see [`ArgsError`].
+    pub fn validate(&self) -> std::result::Result<(), ArgsError> {
+        if self.mode == Mode::Traditional {
+            let gnu_options = [
+                ("--debugfile", self.debugfile.is_some()),
+                ("--word-regexp", self.word_regexp.is_some()),
+                ("--warn-macro-sequence", self.warn_macro_sequence.is_some()),
+                ("--freeze-state", self.freeze_state.is_some()),
+                ("--reload-state", self.reload_state.is_some()),
+            ];
+            if let Some((option, _)) = gnu_options.into_iter().find(|(_, used)| *used) {
+                return Err(ArgsError::GnuOptionWithTraditional(option));
+            }
+        }
+        if let (Some(freeze_state), Some(reload_state)) = (&self.freeze_state, &self.reload_state) {
+            if is_same_file(freeze_state, reload_state) {
+                return Err(ArgsError::FreezeAndReloadSameFile(freeze_state.clone()));
+            }
+        }
+        let regexes = [
+            ("--word-regexp", &self.word_regexp),
+            ("--warn-macro-sequence", &self.warn_macro_sequence),
+        ];
+        for (option, regex) in regexes {
+            if let Some(regex) = regex {
+                gnu_regex::compile(regex).map_err(|error| ArgsError::InvalidRegex {
+                    option,
+                    message: error.to_string(),
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A nonsensical combination of [`Args`], see [`Args::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgsError {
+    /// An option which is a GNU extension (such as `--debugfile`) was used with
+    /// [`Mode::Traditional`].
+    GnuOptionWithTraditional(&'static str),
+    /// [`Args::freeze_state`] and [`Args::reload_state`] are the same file.
+    FreezeAndReloadSameFile(PathBuf),
+    /// A negative nesting limit was given to [`ArgsBuilder::nesting_limit`].
+    NegativeNestingLimit(i64),
+    /// [`Args::word_regexp`] or [`Args::warn_macro_sequence`] is not a valid regular expression.
+    InvalidRegex {
+        option: &'static str,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GnuOptionWithTraditional(option) => {
+                write!(f, "{option} cannot be used with --traditional")
+            }
+            Self::FreezeAndReloadSameFile(path) => write!(
+                f,
+                "--freeze-state and --reload-state cannot both use {path:?}"
+            ),
+            Self::NegativeNestingLimit(limit) => {
+                write!(f, "the nesting limit cannot be negative: {limit}")
+            }
+            Self::InvalidRegex { option, message } => write!(f, "{option}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+/// Builds [`Args`], the arguments are checked with [`Args::validate`] by [`ArgsBuilder::build`].
+/// Methods which are not called leave the [`Args::default`] value.
+#[derive(Debug, Clone, Default)]
+pub struct ArgsBuilder {
+    args: Args,
+    nesting_limit: Option<i64>,
+}
+
+impl ArgsBuilder {
+    /// See [`Args::line_synchronization`].
+    pub fn line_synchronization(mut self, enabled: bool) -> Self {
+        self.args.line_synchronization = enabled;
+        self
+    }
+
+    /// See [`Args::crlf`].
+    pub fn crlf(mut self, enabled: bool) -> Self {
+        self.args.crlf = enabled;
+        self
+    }
+
+    /// Add a [`DefineDirective::Define`].
+    pub fn define(mut self, name: MacroName, definition: impl Into<Vec<u8>>) -> Self {
+        self.args
+            .define_directives
+            .push(DefineDirective::Define(ArgumentDefine {
+                name,
+                definition: definition.into(),
+            }));
+        self
+    }
+
+    /// Add a [`DefineDirective::Undefine`].
+    pub fn undefine(mut self, name: MacroName) -> Self {
+        self.args
+            .define_directives
+            .push(DefineDirective::Undefine(name));
+        self
+    }
+
+    /// Add a [`DefineDirective::Trace`].
+    pub fn trace(mut self, name: MacroName) -> Self {
+        self.args
+            .define_directives
+            .push(DefineDirective::Trace(name));
+        self
+    }
+
+    /// Append a directory to [`Args::include`].
+    pub fn include(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.args.include.push(directory.into());
+        self
+    }
+
+    /// See [`Args::freeze_state`].
+    pub fn freeze_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.freeze_state = Some(path.into());
+        self
+    }
+
+    /// See [`Args::reload_state`].
+    pub fn reload_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.reload_state = Some(path.into());
+        self
+    }
+
+    /// See [`Args::nesting_limit`], it is an error for `limit` to be negative.
+    pub fn nesting_limit(mut self, limit: i64) -> Self {
+        self.nesting_limit = Some(limit);
+        self
+    }
+
+    /// See [`Args::expansion_limit`].
+    pub fn expansion_limit(mut self, limit: usize) -> Self {
+        self.args.expansion_limit = limit;
+        self
+    }
+
+    /// See [`Args::fatal_warnings`].
+    pub fn fatal_warnings(mut self, fatal_warnings: FatalWarnings) -> Self {
+        self.args.fatal_warnings = fatal_warnings;
+        self
+    }
+
+    /// See [`Args::verbosity`].
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.args.verbosity = verbosity;
+        self
+    }
+
+    /// See [`Args::warnings`].
+    pub fn warning(
+        mut self,
+        category: warning::WarningCategory,
+        action: warning::WarningAction,
+    ) -> Self {
+        self.args.warnings.set(category, action);
+        self
+    }
+
+    /// See [`Args::diagnostics_format`].
+    pub fn diagnostics_format(mut self, format: DiagnosticsFormat) -> Self {
+        self.args.diagnostics_format = format;
+        self
+    }
+
+    /// See [`Args::debug_flags`].
+    pub fn debug_flags(mut self, debug_flags: DebugFlags) -> Self {
+        self.args.debug_flags = debug_flags;
+        self
+    }
+
+    /// See [`Args::mode`].
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.args.mode = mode;
+        self
+    }
+
+    /// See [`Args::interactive`].
+    pub fn interactive(mut self, enabled: bool) -> Self {
+        self.args.interactive = enabled;
+        self
+    }
+
+    /// See [`Args::output_buffering`].
+    pub fn output_buffering(mut self, output_buffering: OutputBuffering) -> Self {
+        self.args.output_buffering = output_buffering;
+        self
+    }
+
+    /// See [`Args::memory_map`].
+    pub fn memory_map(mut self, memory_map: MemoryMap) -> Self {
+        self.args.memory_map = memory_map;
+        self
+    }
+
+    /// See [`Args::prefix_builtins`].
+    pub fn prefix_builtins(mut self, enabled: bool) -> Self {
+        self.args.prefix_builtins = enabled;
+        self
+    }
+
+    /// See [`Args::safer`].
+    pub fn safer(mut self, enabled: bool) -> Self {
+        self.args.safer = enabled;
+        self
+    }
+
+    /// See [`Args::utf8`].
+    pub fn utf8(mut self, enabled: bool) -> Self {
+        self.args.utf8 = enabled;
+        self
+    }
+
+    /// See [`Args::profile`].
+    pub fn profile(mut self, enabled: bool) -> Self {
+        self.args.profile = enabled;
+        self
+    }
+
+    /// See [`Args::program_name`].
+    pub fn program_name(mut self, program_name: impl Into<OsString>) -> Self {
+        self.args.program_name = program_name.into();
+        self
+    }
+
+    /// See [`Args::debugfile`].
+    pub fn debugfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.debugfile = Some(path.into());
+        self
+    }
+
+    /// See [`Args::word_regexp`].
+    pub fn word_regexp(mut self, regex: impl Into<Vec<u8>>) -> Self {
+        self.args.word_regexp = Some(regex.into());
+        self
+    }
+
+    /// See [`Args::warn_macro_sequence`].
+    pub fn warn_macro_sequence(mut self, regex: impl Into<Vec<u8>>) -> Self {
+        self.args.warn_macro_sequence = Some(regex.into());
+        self
+    }
+
+    /// Append an input file to [`Args::files`].
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.args.files.push(path.into());
+        self
+    }
+
+    /// Validate the arguments using [`Args::validate`].
+    pub fn build(self) -> std::result::Result<Args, ArgsError> {
+        let mut args = self.args;
+        if let Some(limit) = self.nesting_limit {
+            args.nesting_limit =
+                usize::try_from(limit).map_err(|_| ArgsError::NegativeNestingLimit(limit))?;
         }
+        args.validate()?;
+        Ok(args)
+    }
+}
+
+/// The clap ids of the options which need the GNU extensions, and so conflict with `-G`. See
+/// [`ArgsError::GnuOptionWithTraditional`].
+const GNU_OPTIONS: &[&str] = &[
+    "debugfile",
+    "trace_file",
+    "word_regexp",
+    "warn_macro_sequence",
+    "freeze_state",
+    "reload_state",
+];
+
+/// Whether `a` and `b` refer to the same file, either by name or once they are resolved.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// The System V options which are accepted (along with a value) and ignored, see
+/// [`ObsoleteOption::SystemV`]. These are the option and its clap id.
+const OBSOLETE_SYSTEM_V_OPTIONS: &[(char, &str)] = &[
+    ('B', "buffer_size"),
+    ('H', "hash_size"),
+    ('S', "stack_size"),
+    ('T', "token_size"),
+];
+
+/// The value of `--debugfile` when it is specified without one, selecting standard error. A file
+/// name cannot contain NUL, so this is never confused with `--debugfile=file`.
+const DEBUGFILE_STDERR: &str = "\0";
+
+/// Options with an optional value, the value needs to be attached to the option (like
+/// `-daeq` or `--debug=aeq`) in order to be distinguished from the following argument.
+const OPTIONAL_VALUE_OPTIONS: &[&str] = &["-d", "--debug"];
+
+/// Give the [`OPTIONAL_VALUE_OPTIONS`] an explicit empty value when they appear without one, so
+/// that clap does not consume the following argument as their value.
+fn attach_optional_values(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut options_ended = false;
+    args.map(|mut arg| {
+        if arg == "--" {
+            options_ended = true;
+        } else if !options_ended && OPTIONAL_VALUE_OPTIONS.iter().any(|option| arg == *option) {
+            arg.push("=");
+        }
+        arg
+    })
+    .collect()
+}
+
+/// Parse the colon separated list of directories in the `M4PATH` environment variable, these are
+/// searched after the directories specified using [`Args::include`]. An empty entry refers to the
+/// current working directory.
+fn parse_m4path(value: &OsStr) -> Vec<PathBuf> {
+    value
+        .as_encoded_bytes()
+        .split(|b| *b == b':')
+        .map(|directory| {
+            if directory.is_empty() {
+                PathBuf::from(".")
+            } else {
+                // SAFETY: `directory` was split from `value` on an ASCII character.
+                PathBuf::from(unsafe { OsStr::from_encoded_bytes_unchecked(directory) })
+            }
+        })
+        .collect()
+}
+
+/// Ignore `SIGINT` for [`Args::interactive`].
+fn ignore_interrupts() {
+    // SAFETY: According to https://man7.org/linux/man-pages/man2/signal.2.html setting the
+    // disposition to `SIG_IGN` is safe, no handler is installed.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
     }
 }
 
@@ -138,12 +1200,25 @@ pub fn run<STDOUT: Write + 'static, STDERR: Write>(
     mut stderr: STDERR,
     args: Args,
 ) -> crate::error::Result<()> {
+    let diagnostics_format = args.diagnostics_format;
     match run_impl(stdout, &mut stderr, args) {
         Ok(_) => Ok(()),
         Err(error) => match error.kind {
             ErrorKind::Exit(_) => Err(error),
             _ => {
-                if let Err(error) = stderr.write_all(format!("{error:#}").as_bytes()) {
+                let result = match diagnostics_format {
+                    DiagnosticsFormat::Text => writeln!(stderr, "{error:#}"),
+                    DiagnosticsFormat::Json => diagnostics::Diagnostic {
+                        severity: diagnostics::Severity::Error,
+                        code: error.code(),
+                        message: &error.message(),
+                        location: error.location(),
+                        included_from: error.included_from(),
+                        macros: error.macros(),
+                    }
+                    .write_json(&mut stderr),
+                };
+                if let Err(error) = result {
                     return Err(error.into());
                 }
                 Err(error)
@@ -154,31 +1229,77 @@ pub fn run<STDOUT: Write + 'static, STDERR: Write>(
 
 pub fn run_impl<STDOUT: Write + 'static, STDERR: Write>(
     stdout: STDOUT,
-    mut stderr: STDERR,
+    stderr: STDERR,
     args: Args,
 ) -> crate::error::Result<()> {
-    let stdout = Rc::new(RefCell::new(stdout));
-    let mut state = State::try_new(stdout.clone(), Vec::new(), args.line_synchronization)?;
-    if args.files.is_empty() {
-        state.input.input_push(
-            Input::new(InputRead::Stdin(std::io::stdin())),
-            &mut *stdout.borrow_mut(),
-        )?;
-    } else {
-        for file_path in args.files {
-            state.input.input_push(
-                Input::new(InputRead::File {
-                    file: std::fs::File::open(&file_path)?,
-                    path: file_path,
-                }),
-                &mut *stdout.borrow_mut(),
-            )?;
+    let stdout = Rc::new(RefCell::new(output::BufferedStdout::new(
+        stdout,
+        args.output_buffering,
+    )));
+    let mut stderr = output::OrderedStderr::new(stderr, stdout.clone());
+    for option in &args.obsolete_options {
+        match args.diagnostics_format {
+            DiagnosticsFormat::Text => writeln!(stderr, "m4: warning: {option}")?,
+            DiagnosticsFormat::Json => diagnostics::Diagnostic {
+                severity: diagnostics::Severity::Warning,
+                code: warning::WarningCategory::Other.name(),
+                message: &option.to_string(),
+                location: None,
+                included_from: &[],
+                macros: &[],
+            }
+            .write_json(&mut stderr)?,
         }
-    };
+    }
+
+    let mut state = State::try_new(stdout.clone(), Vec::new(), args.line_synchronization)?;
+    state.input.set_crlf(args.crlf);
+    state.include_paths = args.include;
+    state.nesting_limit = args.nesting_limit;
+    state.expansion_limit = args.expansion_limit;
+    state.fatal_warnings = args.fatal_warnings;
+    state.verbosity = args.verbosity;
+    state.warnings = args.warnings;
+    state.diagnostics_format = args.diagnostics_format;
+    state.debug_flags = args.debug_flags;
+    state.set_mode(args.mode);
+    if args.prefix_builtins {
+        state.prefix_builtins();
+    }
+    state.interactive = args.interactive;
+    state.memory_map = args.memory_map;
+    state.safer = args.safer;
+    state.utf8 = args.utf8;
+    if args.profile {
+        state.profile = Some(profile::Profile::default());
+    }
+    state.program_name = args.program_name.into_vec();
+    if args.interactive {
+        ignore_interrupts();
+    }
+    if let Some(path) = &args.debugfile {
+        state.debug_file = DebugFile::open(path)
+            .map_err(Error::from)
+            .add_context(|| format!("Cannot set debug file {path:?}"))?;
+    }
+    if let Some(regex) = &args.word_regexp {
+        state.parse_config.word_regex = lexer::WordRegex::new(regex)?;
+    }
+    if let Some(regex) = &args.warn_macro_sequence {
+        state.warn_macro_sequence = Some(gnu_regex::compile(regex)?);
+    }
+    if let Some(m4path) = std::env::var_os("M4PATH") {
+        state.include_paths.extend(parse_m4path(&m4path));
+    }
+
+    if let Some(path) = &args.reload_state {
+        state = freeze::reload_from_file(state, path, &mut stderr)?;
+    }
 
     for directive in args.define_directives {
         match directive {
             DefineDirective::Define(define) => {
+                state.check_macro_sequence(&mut stderr, &define.name, &define.definition)?;
                 let definition = Rc::new(MacroDefinition::new_user_defined(
                     define.name.clone(),
                     define.definition,
@@ -191,10 +1312,53 @@ pub fn run_impl<STDOUT: Write + 'static, STDERR: Write>(
             DefineDirective::Undefine(name) => {
                 state.macro_definitions.remove(&name);
             }
+            DefineDirective::Trace(name) => state.trace.enable(name),
         }
     }
 
-    main_loop::main_loop(state, &mut stderr)?;
+    // All input operands are processed in order using the same state, so that definitions made in
+    // one file are visible in the following ones. Diversions are only flushed after the last file.
+    let files = if args.files.is_empty() {
+        vec![PathBuf::from("-")]
+    } else {
+        args.files
+    };
+    for file_path in files {
+        // An operand of `-` reads from standard input at that point in the sequence.
+        let input = if file_path.as_os_str() == "-" {
+            Input::new(InputRead::Stdin(std::io::stdin()))
+        } else {
+            // Like GNU m4 a file which can not be opened is reported, and the remaining operands
+            // are still processed.
+            let file = match std::fs::File::open(&file_path) {
+                Ok(file) => file,
+                Err(error) => {
+                    state.error(
+                        &mut stderr,
+                        "io",
+                        format_args!(
+                            "cannot open `{}': {}",
+                            file_path.display(),
+                            error::io_error_description(&error)
+                        ),
+                    )?;
+                    continue;
+                }
+            };
+            Input::new(InputRead::open(file, file_path, args.memory_map))
+        };
+        state.input_push(input, &mut stderr)?;
+        state = main_loop::main_loop(state, &mut stderr)?;
+        state.input_pop(&mut stderr)?;
+    }
+
+    let state = main_loop::finish(state, &mut stderr, args.freeze_state.as_deref())?;
+    stdout.borrow_mut().flush()?;
+    state.write_profile(&mut stderr)?;
+
+    if state.exit_error {
+        return Err(Error::new(ErrorKind::Exit(1)));
+    }
 
     Ok(())
 }
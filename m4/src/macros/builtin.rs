@@ -1,37 +1,98 @@
+use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
+use std::os::fd::FromRawFd;
 use std::os::unix::ffi::{OsStrExt, OsStringExt};
-use std::path::PathBuf;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
 use std::{io::Write, rc::Rc};
 
 use super::eval::parse_integer;
 use super::{MacroDefinitionImplementation, MacroImplementation};
-use nom::error::{ContextError, FromExternalError};
-use nom::IResult;
 
-use crate::error::{Result, ResultExt};
+use crate::debug::{debug_message, write_debug, write_location_prefix, DebugFile, DebugFlags};
+use crate::diversions::DivertBufferNumber;
+use crate::error::{io_error_description, Result};
 use crate::input::{Input, InputRead};
-use crate::lexer::{MacroName, MacroParseConfig, DEFAULT_QUOTE_CLOSE_TAG, DEFAULT_QUOTE_OPEN_TAG};
+use crate::lexer::{
+    is_space, MacroParseConfig, WordRegex, DEFAULT_COMMENT_CLOSE_TAG, DEFAULT_COMMENT_OPEN_TAG,
+    DEFAULT_QUOTE_CLOSE_TAG, DEFAULT_QUOTE_OPEN_TAG,
+};
+use crate::macros::trace::trace_recognized;
 use crate::macros::user_defined::UserDefinedMacro;
-use crate::macros::MacroDefinition;
-use crate::output::DivertBufferNumber;
+use crate::macros::{strip_builtin_tokens, BuiltinMacro, MacroDefinition, BUILTIN_TOKEN_START};
+use crate::main_loop::check_nesting_limit;
 use crate::state::{StackFrame, State};
-use crate::EOF;
+use crate::warning::WarningCategory;
+use crate::Mode;
 
 const AT_LEAST_ONE_MACRO_DEFINITION_EXPECT: &str =
     "There should always be at least one macro definition";
 
+/// Parse `arg` as a decimal integer argument to the builtin `name`, diagnosing it like GNU m4.
+/// An empty argument is treated as 0, leading whitespace is ignored, and `None` is returned for a
+/// non-numeric argument.
+fn numeric_argument(
+    state: &mut State,
+    stderr: &mut dyn Write,
+    name: &str,
+    arg: &[u8],
+) -> Result<Option<i64>> {
+    if arg.is_empty() {
+        state.warning_message(
+            stderr,
+            WarningCategory::Numeric,
+            format_args!("empty string treated as 0 in builtin `{name}'"),
+        )?;
+        return Ok(Some(0));
+    }
+    let trimmed = match arg.iter().position(|c| !is_space(*c)) {
+        Some(start) => &arg[start..],
+        None => &[],
+    };
+    let digits = trimmed.strip_prefix(b"+").unwrap_or(trimmed);
+    match nom::combinator::all_consuming(parse_integer)(digits) {
+        Ok((_, number)) => {
+            if trimmed.len() != arg.len() {
+                state.warning_message(
+                    stderr,
+                    WarningCategory::Numeric,
+                    format_args!("leading whitespace ignored in builtin `{name}'"),
+                )?;
+            }
+            Ok(Some(number))
+        }
+        Err(_) => {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                format_args!("non-numeric argument to builtin `{name}'"),
+            )?;
+            Ok(None)
+        }
+    }
+}
+
 /// The dnl macro shall cause m4 to discard all input characters up to and including the next
 /// `<newline>`.
+///
+/// Like GNU m4, reaching the end of the input instead is diagnosed with a [`State::warning`].
 pub struct DnlMacro;
 
 impl MacroImplementation for DnlMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, _f: StackFrame) -> Result<State> {
-        let mut c: u8;
+    fn evaluate(&self, mut state: State, stderr: &mut dyn Write, _f: StackFrame) -> Result<State> {
         loop {
-            c = state.input.get_next_character()?;
-            if c == b'\n' || c == EOF {
-                break;
+            match state.input.get_next_character()? {
+                Some(b'\n') => break,
+                None => {
+                    state.warning(
+                        stderr,
+                        WarningCategory::Other,
+                        "end of file treated as newline",
+                    )?;
+                    break;
+                }
+                _ => {}
             }
         }
         Ok(state)
@@ -49,13 +110,23 @@ pub struct DefineMacro;
 
 impl DefineMacro {
     fn define(
-        state: State,
-        _stderr: &mut dyn Write,
+        mut state: State,
+        stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<(State, Option<MacroDefinition>)> {
+        let builtin_name = frame.definition.parse_config.name.clone();
         let mut args = frame.args.into_iter();
         let name = if let Some(name_bytes) = args.next() {
-            if let Ok(name) = MacroName::try_from_slice(&name_bytes) {
+            if let Ok(name) = state.parse_config.macro_name(&name_bytes) {
+                // Such a name can never be recognized in the input, but it can still be called
+                // using `indir`.
+                if name_bytes.contains(&b'\0') {
+                    state.warning(
+                        stderr,
+                        WarningCategory::Other,
+                        format_args!("macro name contains NUL in builtin `{builtin_name}'"),
+                    )?;
+                }
                 name
             } else {
                 log::warn!(
@@ -68,16 +139,20 @@ impl DefineMacro {
             log::warn!("No macro name specified, skipping definition");
             return Ok((state, None));
         };
-        let definition = if let Some(definition) = args.next() {
-            definition
-        } else {
-            log::warn!("No macro definition provided, skipping definition");
-            return Ok((state, None));
-        };
+        // Like GNU m4, a missing definition defines the macro to be empty.
+        let mut definition = args.next().unwrap_or_default();
+        if let Some(builtin) = BuiltinMacro::from_token(&definition) {
+            log::debug!("DefineMacro::define() defined macro {name} as a builtin");
+            return Ok((state, Some(MacroDefinition::new_builtin(name, builtin))));
+        }
+        if definition.contains(&BUILTIN_TOKEN_START) {
+            definition = strip_builtin_tokens(&definition).into_owned();
+        }
         log::debug!(
             "DefineMacro::define() defined macro {name}: {:?}",
             String::from_utf8_lossy(&definition)
         );
+        state.check_macro_sequence(stderr, &name, &definition)?;
         let definition = MacroDefinition {
             parse_config: MacroParseConfig { name, min_args: 0 },
             implementation: MacroDefinitionImplementation::UserDefined(UserDefinedMacro {
@@ -135,6 +210,7 @@ impl MacroImplementation for PushdefMacro {
 
 /// The popdef macro shall delete the current definition of its arguments, replacing that
 /// definition with the previous one. If there is no previous definition, the macro is undefined.
+/// Each argument is a macro name, and the definitions are popped in order.
 /// The behavior is unspecified if popdef is not immediately followed by a `<left-parenthesis>`.
 pub struct PopdefMacro;
 
@@ -145,8 +221,8 @@ impl MacroImplementation for PopdefMacro {
         _stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        if let Some(arg) = frame.args.into_iter().next() {
-            if let Ok(name) = MacroName::try_from_slice(&arg) {
+        for arg in frame.args {
+            if let Ok(name) = state.parse_config.macro_name(&arg) {
                 if let Some(n_remaining_definitions) =
                     state.macro_definitions.get_mut(&name).map(|e| {
                         e.pop();
@@ -176,8 +252,8 @@ impl MacroImplementation for UndefineMacro {
         _stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        if let Some(arg) = frame.args.into_iter().next() {
-            if let Ok(name) = MacroName::try_from_slice(&arg) {
+        for arg in frame.args {
+            if let Ok(name) = state.parse_config.macro_name(&arg) {
                 state.macro_definitions.remove(&name);
             }
         }
@@ -185,52 +261,220 @@ impl MacroImplementation for UndefineMacro {
     }
 }
 
+/// GNU extension. `builtin(name, args...)` calls the builtin macro `name` with the remaining
+/// arguments, even if it has been undefined or redefined, or was only ever defined with a prefix
+/// (see [`crate::Args::prefix_builtins`]).
+pub struct BuiltinMacroMacro;
+
+impl BuiltinMacroMacro {
+    /// The frame for calling the builtin named by the first argument of `frame`, or `None` if there
+    /// is nothing to call. See [`MacroDefinitionImplementation`] for how it is called.
+    pub(crate) fn frame(
+        state: &mut State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<Option<StackFrame>> {
+        // The remaining arguments are passed on in the same storage.
+        let mut args = frame.args;
+        if args.is_empty() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `builtin'",
+            )?;
+            return Ok(None);
+        }
+        let name = args.remove(0);
+        let Some(builtin) = BuiltinMacro::from_name(&name) else {
+            state.warning_message(
+                stderr,
+                WarningCategory::UndefinedMacro,
+                format_args!("undefined builtin `{}'", String::from_utf8_lossy(&name)),
+            )?;
+            return Ok(None);
+        };
+        Ok(Some(StackFrame {
+            parenthesis_level: 0,
+            args,
+            definition: Rc::new(MacroDefinition::new_builtin(builtin.name(), builtin)),
+            call_id: frame.call_id,
+        }))
+    }
+}
+
+impl MacroImplementation for BuiltinMacroMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        match Self::frame(&mut state, stderr, frame)? {
+            Some(frame) => frame
+                .definition
+                .clone()
+                .implementation
+                .evaluate(state, stderr, frame),
+            None => Ok(state),
+        }
+    }
+}
+
+/// GNU extension. `indir(name, args...)` calls the macro `name` with the remaining arguments,
+/// which allows calling macros whose names are not valid macro names in the input (for example
+/// `define(`a b', ...)`). The call counts towards [`State::nesting_limit`] and is traced the same
+/// as a direct call.
+pub struct IndirMacro;
+
+impl IndirMacro {
+    /// The frame for calling the macro named by the first argument of `frame`, or `None` if there
+    /// is nothing to call. See [`MacroDefinitionImplementation`] for how it is called.
+    pub(crate) fn frame(
+        state: &mut State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<Option<StackFrame>> {
+        // The remaining arguments are passed on in the same storage.
+        let mut args = frame.args;
+        if args.is_empty() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `indir'",
+            )?;
+            return Ok(None);
+        }
+        let name = args.remove(0);
+        let Some(definition) = state
+            .macro_definitions
+            .get(name.as_slice())
+            .map(|definitions| {
+                definitions
+                    .last()
+                    .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT)
+                    .clone()
+            })
+        else {
+            state.warning_message(
+                stderr,
+                WarningCategory::UndefinedMacro,
+                format_args!("undefined macro `{}'", String::from_utf8_lossy(&name)),
+            )?;
+            return Ok(None);
+        };
+        check_nesting_limit(state, &definition)?;
+        state.macro_calls += 1;
+        let frame = StackFrame {
+            parenthesis_level: 0,
+            args,
+            definition,
+            call_id: state.macro_calls,
+        };
+        trace_recognized(state, &frame, stderr)?;
+        Ok(Some(frame))
+    }
+}
+
+impl MacroImplementation for IndirMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        match Self::frame(&mut state, stderr, frame)? {
+            Some(frame) => frame
+                .definition
+                .clone()
+                .implementation
+                .evaluate(state, stderr, frame),
+            None => Ok(state),
+        }
+    }
+}
+
 /// The defining text of the defn macro shall be the quoted definition (using the current quoting
 /// strings) of its arguments. The behavior is unspecified if defn is not immediately followed by a
 /// `<left-parenthesis>`.
+///
+/// The definition of a builtin macro is its builtin token (see
+/// [`crate::macros::BUILTIN_TOKEN_START`]), so that `define(`mylen', defn(`len'))` defines
+/// `mylen` to be the `len` builtin. Builtin tokens can not be concatenated, so they are omitted
+/// with a warning when there is more than one argument.
 pub struct DefnMacro;
 
 impl MacroImplementation for DefnMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        let first_arg = frame
-            .args
-            .into_iter()
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        if let Some(definitions) = state
-            .macro_definitions
-            .get(&MacroName::try_from_slice(&first_arg)?)
-        {
-            let definition = definitions
-                .last()
-                .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT);
-            if let MacroDefinitionImplementation::UserDefined(definition) =
-                &definition.implementation
-            {
-                state
-                    .input
-                    .pushback_string(&state.parse_config.quote_close_tag);
-                state.input.pushback_string(&definition.definition);
-                state
-                    .input
-                    .pushback_string(&state.parse_config.quote_open_tag);
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let args_len = frame.args.len();
+        let mut expansion = Vec::new();
+        for arg in frame.args {
+            // An argument which is not a macro name (such as an empty one) is not defined either.
+            let Some(definition) = state
+                .parse_config
+                .macro_name(&arg)
+                .ok()
+                .and_then(|name| state.macro_definitions.get(&name))
+                .map(|definitions| {
+                    definitions
+                        .last()
+                        .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT)
+                        .clone()
+                })
+            else {
+                continue;
+            };
+            match &definition.implementation {
+                MacroDefinitionImplementation::UserDefined(definition) => {
+                    expansion.extend(&state.parse_config.quote_open_tag);
+                    expansion.extend(&definition.definition);
+                    expansion.extend(&state.parse_config.quote_close_tag);
+                }
+                implementation => {
+                    let builtin = implementation
+                        .builtin()
+                        .expect("Only user defined macros are not builtins");
+                    if args_len == 1 {
+                        expansion.extend(builtin.token());
+                    } else {
+                        state.warning(
+                            stderr,
+                            WarningCategory::Other,
+                            format!(
+                                "cannot concatenate builtin `{}'",
+                                String::from_utf8_lossy(&arg)
+                            ),
+                        )?;
+                    }
+                }
             }
         }
+        state.input.pushback_string(expansion);
         Ok(state)
     }
 }
 
+/// Write the arguments to standard error separated by spaces, without a trailing newline. In
+/// [`Mode::Gnu`] the message is prefixed with the location, `m4:file:line: `, depending on
+/// [`DebugFlags::FILE`] and [`DebugFlags::LINE`].
 pub struct ErrprintMacro;
 
 impl MacroImplementation for ErrprintMacro {
     fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        let args_len = frame.args.len();
-        for (i, arg) in frame.args.into_iter().enumerate() {
-            stderr.write_all(&arg)?;
-            if i < (args_len - 1) {
-                stderr.write_all(b" ")?;
-            }
+        let mut message = Vec::new();
+        if state.mode == Mode::Gnu
+            && (state.debug_flags.contains(DebugFlags::FILE)
+                || state.debug_flags.contains(DebugFlags::LINE))
+        {
+            write_location_prefix(&state, "m4", &mut message)?;
+            message.push(b' ');
         }
+        message.extend(frame.args.join(&b' '));
+        stderr.write_all(&message)?;
         Ok(state)
     }
 }
@@ -250,29 +494,100 @@ impl IncludeMacro {
         }
     }
 
-    fn include_impl(path: PathBuf, state: State) -> crate::error::Result<State> {
-        let file = std::fs::File::open(&path)
-            .map_err(crate::Error::from)
-            .add_context(|| format!("Error opening file {path:?}"))?;
-        state.input.input_push(
-            Input::new(InputRead::File { file, path }),
-            &mut *state.output.output.stdout().borrow_mut(),
-        )?;
+    /// Search for the file at `path`, first relative to the current working directory, and then
+    /// in each of the [`State::include_paths`] in order. Absolute paths are not searched for. If
+    /// no file is found but `path` exists (such as a directory), then it is returned so that the
+    /// reason it cannot be read is diagnosed.
+    fn resolve_file_path(path: &Path, state: &State) -> Option<PathBuf> {
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        if path.is_absolute() {
+            return path.exists().then(|| path.to_path_buf());
+        }
+
+        state
+            .include_paths
+            .iter()
+            .map(|directory| directory.join(path))
+            .find(|candidate| candidate.is_file())
+            .or_else(|| path.exists().then(|| path.to_path_buf()))
+    }
+
+    /// Include the file at `path`. A file which cannot be found is an error, unless
+    /// `missing_ok` (for [`SincludeMacro`]), while a file which exists but cannot be read is
+    /// always an error.
+    fn include_impl(
+        path: PathBuf,
+        mut state: State,
+        stderr: &mut dyn Write,
+        missing_ok: bool,
+    ) -> crate::error::Result<State> {
+        let Some(resolved) = Self::resolve_file_path(&path, &state) else {
+            if !missing_ok {
+                let error = std::io::Error::from_raw_os_error(libc::ENOENT);
+                Self::cannot_open(&path, &error, &mut state, stderr)?;
+            }
+            return Ok(state);
+        };
+        if resolved != path && state.debug_flags.contains(DebugFlags::PATH) {
+            debug_message(
+                &state,
+                stderr,
+                format_args!(
+                    "path search for `{}' found `{}'",
+                    path.display(),
+                    resolved.display()
+                ),
+            )?;
+        }
+        let file = if resolved.is_dir() {
+            Err(std::io::Error::from_raw_os_error(libc::EISDIR))
+        } else {
+            std::fs::File::open(&resolved)
+        };
+        match file {
+            Ok(file) => state.input_push(
+                Input::new(InputRead::open(file, resolved, state.memory_map)),
+                stderr,
+            )?,
+            Err(error) => Self::cannot_open(&path, &error, &mut state, stderr)?,
+        }
         Ok(state)
     }
+
+    /// Diagnose a file which could not be included, processing continues but the exit status
+    /// will be a failure.
+    fn cannot_open(
+        path: &Path,
+        error: &std::io::Error,
+        state: &mut State,
+        stderr: &mut dyn Write,
+    ) -> crate::error::Result<()> {
+        state.error_message(
+            stderr,
+            "io",
+            format_args!(
+                "cannot open `{}': {}",
+                path.display(),
+                io_error_description(error)
+            ),
+        )?;
+        Ok(())
+    }
 }
 
 impl MacroImplementation for IncludeMacro {
     fn evaluate(
         &self,
         mut state: State,
-        _stderr: &mut dyn Write,
+        stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
         let path;
         (path, state) = Self::get_file_path(frame, state)?;
         if let Some(path) = path {
-            state = Self::include_impl(path, state)?;
+            state = Self::include_impl(path, state, stderr, false)?;
         }
         Ok(state)
     }
@@ -281,27 +596,31 @@ impl MacroImplementation for IncludeMacro {
 /// The sinclude macro shall be equivalent to the [`IncludeMacro`], except that it shall not be an
 /// error if the file is inaccessible. The behavior is unspecified if sinclude is not immediately
 /// followed by a `<left-parenthesis>`.
+///
+/// A file which is found but cannot be read is still diagnosed.
 pub struct SincludeMacro;
 
 impl MacroImplementation for SincludeMacro {
     fn evaluate(
         &self,
         mut state: State,
-        _stderr: &mut dyn Write,
+        stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
         let path;
         (path, state) = IncludeMacro::get_file_path(frame, state)?;
         if let Some(path) = path {
-            if path.is_file() {
-                state = IncludeMacro::include_impl(path, state)?;
-            }
+            state = IncludeMacro::include_impl(path, state, stderr, true)?;
         }
 
         Ok(state)
     }
 }
 
+/// Change the comment delimiters, which can be strings of any length. The close delimiter
+/// defaults to `<newline>` when it is omitted or empty, and an empty open delimiter disables
+/// comments. With no arguments (not even brackets) comments are disabled, except in
+/// [`Mode::Traditional`] where the defaults, `#` and `<newline>`, are restored.
 pub struct ChangecomMacro;
 
 impl MacroImplementation for ChangecomMacro {
@@ -311,44 +630,43 @@ impl MacroImplementation for ChangecomMacro {
         stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        let args_len = frame.args.len();
-
-        if args_len == 0 {
-            state.parse_config.comment_enabled = false;
-            log::trace!("ChangecomMacro::evaluate() reset to default");
-            return Ok(state);
+        if frame.args.len() > 2 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `changecom' ignored",
+            )?;
         }
-
         let mut args = frame.args.into_iter();
-        let open_tag = args.next().expect("1 argument should be present");
-        if !open_tag.is_empty() {
-            log::trace!(
-                "ChangecomMacro::evaluate() comment_open_tag set to {:?}",
-                String::from_utf8_lossy(&open_tag)
-            );
-            state.parse_config.comment_enabled = true;
-            state.parse_config.comment_open_tag = open_tag;
-        }
-
-        if args_len >= 2 {
-            let close_tag = args.next().expect("2 arguments should be present");
-            if !close_tag.is_empty() {
-                log::trace!(
-                    "ChangecomMacro::evaluate() comment_close_tag set to {:?}",
-                    String::from_utf8_lossy(&close_tag)
-                );
-                state.parse_config.comment_close_tag = close_tag;
-            }
-        }
-
-        if args_len > 2 {
-            stderr.write_all(b"Warning: excess arguments to builtin `changecom' ignored")?;
-        }
+        let (open_tag, close_tag) = match (args.next(), args.next()) {
+            (None, _) if state.mode == Mode::Traditional => (
+                DEFAULT_COMMENT_OPEN_TAG.to_vec(),
+                DEFAULT_COMMENT_CLOSE_TAG.to_vec(),
+            ),
+            (None, _) => (Vec::new(), DEFAULT_COMMENT_CLOSE_TAG.to_vec()),
+            (Some(open_tag), close_tag) => (
+                open_tag,
+                close_tag
+                    .filter(|close_tag| !close_tag.is_empty())
+                    .unwrap_or_else(|| DEFAULT_COMMENT_CLOSE_TAG.to_vec()),
+            ),
+        };
+        log::trace!(
+            "ChangecomMacro::evaluate() comment tags set to {:?} and {:?}",
+            String::from_utf8_lossy(&open_tag),
+            String::from_utf8_lossy(&close_tag)
+        );
+        state.parse_config.comment_enabled = !open_tag.is_empty();
+        state.parse_config.comment_open_tag = open_tag;
+        state.parse_config.comment_close_tag = close_tag;
 
         Ok(state)
     }
 }
 
+/// Change the quote delimiters, which can be strings of any length. With no arguments (not even
+/// brackets) the defaults, `` ` `` and `'`, are restored. Like GNU m4, if the close quote is
+/// omitted or empty then it is `'`, while an empty open quote disables quoting entirely.
 pub struct ChangequoteMacro;
 
 impl MacroImplementation for ChangequoteMacro {
@@ -358,29 +676,29 @@ impl MacroImplementation for ChangequoteMacro {
         stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        match frame.args.len() {
-            0 => {
-                DEFAULT_QUOTE_OPEN_TAG.clone_into(&mut state.parse_config.quote_open_tag);
-                DEFAULT_QUOTE_CLOSE_TAG.clone_into(&mut state.parse_config.quote_close_tag);
-            }
-            1 => {}
-            args_len @ 2.. => {
-                if args_len > 2 {
-                    stderr
-                        .write_all(b"Warning: excess arguments to builtin `changequote' ignored")?;
-                }
-                let mut args = frame.args.into_iter();
-                let open_tag = args.next().expect("2 arguments should be present");
-                let close_tag = args.next().expect("2 arguments should be present");
-                // The behavior is unspecified if there is a single argument or either argument is null.
-                if !open_tag.is_empty() && !close_tag.is_empty() {
-                    // It looks like GNU m4 only allows quote strings using non-alphanumeric
-                    // characters. The spec I'm following doesn't mention anything about that.
-                    state.parse_config.quote_open_tag = open_tag;
-                    state.parse_config.quote_close_tag = close_tag;
-                }
-            }
+        if frame.args.len() > 2 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `changequote' ignored",
+            )?;
         }
+        let mut args = frame.args.into_iter();
+        let (open_tag, close_tag) = match (args.next(), args.next()) {
+            (None, _) => (
+                DEFAULT_QUOTE_OPEN_TAG.to_vec(),
+                DEFAULT_QUOTE_CLOSE_TAG.to_vec(),
+            ),
+            (Some(open_tag), None) => (open_tag, DEFAULT_QUOTE_CLOSE_TAG.to_vec()),
+            (Some(open_tag), Some(close_tag)) if !open_tag.is_empty() && close_tag.is_empty() => {
+                (open_tag, DEFAULT_QUOTE_CLOSE_TAG.to_vec())
+            }
+            (Some(open_tag), Some(close_tag)) => (open_tag, close_tag),
+        };
+        // It looks like GNU m4 only allows quote strings using non-alphanumeric
+        // characters. The spec I'm following doesn't mention anything about that.
+        state.parse_config.quote_open_tag = open_tag;
+        state.parse_config.quote_close_tag = close_tag;
 
         Ok(state)
     }
@@ -389,47 +707,69 @@ impl MacroImplementation for ChangequoteMacro {
 /// The defining text of the incr macro shall be its first argument incremented by 1. It shall be
 /// an error to specify an argument containing any non-numeric characters. The behavior is
 /// unspecified if incr is not immediately followed by a `<left-parenthesis>`.
+///
+/// See [`add_one`] for how the argument is interpreted.
 pub struct IncrMacro;
 
 impl MacroImplementation for IncrMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        if let Some(first) = frame.args.into_iter().next() {
-            let (remaining, mut number) = super::eval::padded(super::eval::parse_integer)(&first)?;
-            if !remaining.is_empty() {
-                return Err(
-                    crate::Error::new(crate::ErrorKind::Parsing).add_context(format!(
-                        "Error parsing number from {first:?}, remaining input: {remaining:?}"
-                    )),
-                );
-            }
-            number += 1;
-            state.input.pushback_string(number.to_string().as_bytes());
-        }
-        Ok(state)
+    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+        add_one(state, stderr, frame, "incr", 1)
     }
 }
 
 /// The defining text of the decr macro shall be its first argument decremented by 1. It shall be
 /// an error to specify an argument containing any non-numeric characters. The behavior is
 /// unspecified if decr is not immediately followed by a `<left-parenthesis>`.
+///
+/// See [`add_one`] for how the argument is interpreted.
 pub struct DecrMacro;
 
 impl MacroImplementation for DecrMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        if let Some(first) = frame.args.into_iter().next() {
-            let (remaining, mut number) = super::eval::padded(super::eval::parse_integer)(&first)?;
-            if !remaining.is_empty() {
-                return Err(
-                    crate::Error::new(crate::ErrorKind::Parsing).add_context(format!(
-                        "Error parsing number from {first:?}, remaining input: {remaining:?}"
-                    )),
-                );
-            }
-            number -= 1;
-            state.input.pushback_string(number.to_string().as_bytes());
-        }
-        Ok(state)
+    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+        add_one(state, stderr, frame, "decr", -1)
+    }
+}
+
+/// Implementation of [`IncrMacro`] and [`DecrMacro`], the first argument plus `delta`.
+///
+/// Like GNU m4 the argument is a 32 bit integer (a larger one is diagnosed and truncated) and a
+/// non-numeric argument is diagnosed and expands to nothing, see [`numeric_argument`]. The result
+/// wraps on overflow, which is silent in [`Mode::Gnu`] just like GNU m4, but is diagnosed in
+/// [`Mode::Traditional`].
+fn add_one(
+    mut state: State,
+    stderr: &mut dyn Write,
+    frame: StackFrame,
+    name: &str,
+    delta: i32,
+) -> Result<State> {
+    if frame.args.len() > 1 {
+        state.retrievable_warning(
+            stderr,
+            WarningCategory::ExcessArguments,
+            format_args!("excess arguments to builtin `{name}' ignored"),
+        )?;
+    }
+    // `incr()` has a single empty argument.
+    let first_arg = frame.args.into_iter().next().unwrap_or_default();
+    let Some(number) = numeric_argument(&mut state, stderr, name, &first_arg)? else {
+        return Ok(state);
+    };
+    let mut overflow = i32::try_from(number).is_err();
+    let number = number as i32;
+    let result = number.checked_add(delta).unwrap_or_else(|| {
+        overflow |= state.mode == Mode::Traditional;
+        number.wrapping_add(delta)
+    });
+    if overflow {
+        state.warning_message(
+            stderr,
+            WarningCategory::Numeric,
+            format_args!("numeric overflow detected in builtin `{name}'"),
+        )?;
     }
+    state.input.pushback_string(result.to_string().as_bytes());
+    Ok(state)
 }
 
 /// The ifelse macro takes three or more arguments. If the first two arguments compare as equal
@@ -447,10 +787,23 @@ impl MacroImplementation for DecrMacro {
 pub struct IfelseMacro;
 
 impl MacroImplementation for IfelseMacro {
-    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
         let mut args_len = frame.args.len();
+        // A single argument is commonly used for comments, such as `ifelse(`comment')`.
+        if args_len == 1 {
+            return Ok(state);
+        }
         if args_len < 3 {
-            write!(stderr, "Too few arguments to builtin `ifelse'")?;
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `ifelse'",
+            )?;
             return Ok(state);
         }
 
@@ -464,24 +817,25 @@ impl MacroImplementation for IfelseMacro {
             if arg_0 == arg_1 {
                 log::debug!("IfelseMacro::evaluate() evaluating argument {}", i * 3 + 2);
                 let arg = args.next().expect("at least 3 args");
-                state.input.pushback_string(&arg);
+                state.input.pushback_string(arg);
                 return Ok(state);
             } else {
                 match args_len {
                     0..=2 => panic!("at least 3 args"),
                     3 => return Ok(state),
                     4 | 5 => {
-                        args.next();
-                        log::debug!("IfelseMacro::evaluate() evaluating argument {}", i * 3 + 3);
-                        let arg = args.next().expect("at least 4 args");
-                        state.input.pushback_string(&arg);
+                        // The remaining arguments are one short of another comparison.
                         if args_len == 5 {
-                            write!(
+                            state.retrievable_warning(
                                 stderr,
-                                "Excess argument {:?} to builtin `ifelse' will be ignored",
-                                args.next().expect("5 arguments")
+                                WarningCategory::ExcessArguments,
+                                "excess arguments to builtin `ifelse' ignored",
                             )?;
                         }
+                        args.next();
+                        log::debug!("IfelseMacro::evaluate() evaluating argument {}", i * 3 + 3);
+                        let arg = args.next().expect("at least 4 args");
+                        state.input.pushback_string(arg);
                         return Ok(state);
                     }
                     6.. => {
@@ -504,22 +858,39 @@ impl MacroImplementation for IfelseMacro {
 pub struct IfdefMacro;
 
 impl MacroImplementation for IfdefMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() < 2 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `ifdef'",
+            )?;
+            return Ok(state);
+        }
+        if frame.args.len() > 3 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `ifdef' ignored",
+            )?;
+        }
         let mut args = frame.args.into_iter();
-        let first_arg = args
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        let second_arg = args
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        let name = MacroName::try_from_slice(&first_arg).ok();
+        let first_arg = args.next().expect("at least 2 args");
+        let second_arg = args.next().expect("at least 2 args");
+        // A macro defined with an empty definition is still defined.
+        let name = state.parse_config.macro_name(&first_arg).ok();
         if name
             .map(|name| state.macro_definitions.contains_key(&name))
             .unwrap_or(false)
         {
-            state.input.pushback_string(&second_arg);
+            state.input.pushback_string(second_arg);
         } else if let Some(third_arg) = args.next() {
-            state.input.pushback_string(&third_arg);
+            state.input.pushback_string(third_arg);
         }
         log::debug!("IfdefMacro::evaluate() finished");
         Ok(state)
@@ -533,33 +904,48 @@ pub struct ShiftMacro;
 
 impl MacroImplementation for ShiftMacro {
     fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        if frame.args.len() > 1 {
-            let args = &frame.args[1..];
-            for (i, arg) in args.iter().enumerate().rev() {
-                state.input.pushback_string(arg);
-                if i != 0 {
-                    state.input.pushback_character(b',');
-                }
+        let mut expansion = Vec::new();
+        for (i, arg) in frame.args.iter().skip(1).enumerate() {
+            if i != 0 {
+                expansion.push(b',');
             }
+            expansion.extend(&state.parse_config.quote_open_tag);
+            expansion.extend(arg);
+            expansion.extend(&state.parse_config.quote_close_tag);
         }
+        state.input.pushback_string(expansion);
         Ok(state)
     }
 }
 
 /// The defining text of the len macro shall be the length (as a string) of the first argument. The
 /// behavior is unspecified if len is not immediately followed by a `<left-parenthesis>`.
+///
+/// The length is in bytes, or in characters with [`crate::Args::utf8`].
 pub struct LenMacro;
 
 impl MacroImplementation for LenMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        let first_arg = frame
-            .args
-            .into_iter()
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        state
-            .input
-            .pushback_string(first_arg.len().to_string().as_bytes());
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 1 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `len' ignored",
+            )?;
+        }
+        // `len()` has a single empty argument.
+        let first_arg = frame.args.into_iter().next().unwrap_or_default();
+        let len = if state.utf8 {
+            character_boundaries(&first_arg).len() - 1
+        } else {
+            first_arg.len()
+        };
+        state.input.pushback_string(len.to_string().as_bytes());
         Ok(state)
     }
 }
@@ -568,26 +954,53 @@ impl MacroImplementation for LenMacro {
 /// first argument where a string matching the second argument begins (zero origin), or -1 if the
 /// second argument does not occur. The behavior is unspecified if index is not immediately followed
 /// by a `<left-parenthesis>`.
+///
+/// The position is in bytes, or in characters with [`crate::Args::utf8`].
 pub struct IndexMacro;
 
 impl MacroImplementation for IndexMacro {
-    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 2 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `index' ignored",
+            )?;
+        }
         let mut args = frame.args.into_iter();
-        let first_arg = args
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
+        // `index()` has a single empty argument.
+        let first_arg = args.next().unwrap_or_default();
         let second_arg = match args.next() {
             Some(second_arg) => second_arg,
             None => {
-                stderr.write_all(b"Warning too few arguments for index macro")?;
+                state.retrievable_warning(
+                    stderr,
+                    WarningCategory::TooFewArguments,
+                    "too few arguments to builtin `index'",
+                )?;
                 state.input.pushback_character(b'0');
                 return Ok(state);
             }
         };
 
-        let index = first_arg
-            .windows(second_arg.len())
-            .position(|window| window == second_arg);
+        // An empty string occurs at the start of any string.
+        let index = if second_arg.is_empty() {
+            Some(0)
+        } else if state.utf8 {
+            // Only a match which starts at a character counts.
+            character_boundaries(&first_arg)
+                .iter()
+                .position(|start| first_arg[*start..].starts_with(&second_arg))
+        } else {
+            first_arg
+                .windows(second_arg.len())
+                .position(|window| window == second_arg)
+        };
         match index {
             Some(index) => {
                 state.input.pushback_string(index.to_string().as_bytes());
@@ -608,147 +1021,652 @@ impl MacroImplementation for IndexMacro {
 /// second or third argument anywhere besides the first or last character. The behavior is
 /// unspecified if the same character appears more than once in the second argument. The behavior is
 /// unspecified if translit is not immediately followed by a `<left-parenthesis>`.
+///
+/// The characters are bytes, or UTF-8 characters with [`crate::Args::utf8`] in which case a range
+/// is of Unicode scalar values.
 pub struct TranslitMacro;
 
-// TODO(utf8): support utf8 multibyte characters properly
 impl MacroImplementation for TranslitMacro {
-    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 3 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `translit' ignored",
+            )?;
+        }
         let mut args = frame.args.into_iter();
-        let mut output_buffer = args
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
+        let input = args.next().unwrap_or_default();
 
         log::debug!(
             "TranslitMacro::evaluate() transliterating {:?}",
-            String::from_utf8_lossy(&output_buffer)
+            String::from_utf8_lossy(&input)
         );
 
-        let second_arg = match args.next() {
-            Some(second_arg) => second_arg,
-            None => {
-                stderr.write_all(b"Warning too few arguments for index macro")?;
-                state.input.pushback_character(b'0');
-                return Ok(state);
-            }
+        let Some(second_arg) = args.next() else {
+            // Like GNU m4, `translit(`abc')` is the first argument unchanged.
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `translit'",
+            )?;
+            state.input.pushback_string(input);
+            return Ok(state);
         };
-
         let third_arg = args.next().unwrap_or_default();
-
-        for (i, source_char) in second_arg.into_iter().enumerate() {
-            if let Some(replacement_char) = third_arg.get(i) {
-                for current_char in output_buffer.iter_mut() {
-                    if *current_char == source_char {
-                        *current_char = *replacement_char;
-                    }
-                }
-            } else {
-                output_buffer.retain(|current_char| *current_char != source_char);
+        if state.utf8 {
+            let output = translit_characters(&input, &second_arg, &third_arg);
+            state.input.pushback_string(output);
+            return Ok(state);
+        }
+        let from = expand_ranges(&second_arg);
+        let to = expand_ranges(&third_arg);
+
+        // The replacement for each byte, `None` if it is deleted. Only the first occurrence of a
+        // byte in `from` is used.
+        let mut map: [Option<Option<u8>>; 256] = [None; 256];
+        for (i, source) in from.iter().enumerate() {
+            let entry = &mut map[usize::from(*source)];
+            if entry.is_none() {
+                *entry = Some(to.get(i).copied());
             }
         }
 
-        state.input.pushback_string(&output_buffer);
+        let output: Vec<u8> = input
+            .iter()
+            .filter_map(|c| map[usize::from(*c)].unwrap_or(Some(*c)))
+            .collect();
+        state.input.pushback_string(output);
         Ok(state)
     }
 }
 
-/// The defining text for the substr macro shall be the substring of the first argument beginning at
-/// the zero-offset character position specified by the second argument. The third argument, if
-/// specified, shall be the number of characters to select; if not specified, the characters from
-/// the starting point to the end of the first argument shall become the defining text. It shall not
-/// be an error to specify a starting point beyond the end of the first argument and the defining
-/// text shall be null. It shall be an error to specify an argument containing any non-numeric
-/// characters. The behavior is unspecified if substr is not immediately followed by a
-/// `<left-parenthesis>`.
-pub struct SubstrMacro;
-
-impl MacroImplementation for SubstrMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        let mut args = frame.args.into_iter();
-        let first_arg = args
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-
-        if first_arg.is_empty() {
-            return Ok(state);
+/// [`TranslitMacro`] with [`crate::Args::utf8`], `input` with the characters in `from` replaced by
+/// those in `to`.
+fn translit_characters(input: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let from = expand_character_ranges(&characters(from));
+    let to = expand_character_ranges(&characters(to));
+    let mut map: HashMap<Character, Option<Character>> = HashMap::new();
+    for (i, source) in from.iter().enumerate() {
+        map.entry(*source).or_insert_with(|| to.get(i).copied());
+    }
+    let mut output = Vec::with_capacity(input.len());
+    for c in characters(input) {
+        if let Some(c) = map.get(&c).copied().unwrap_or(Some(c)) {
+            c.encode(&mut output);
         }
+    }
+    output
+}
 
-        let start_index = if let Some(second_arg) = args.next() {
-            let (_, i) = nom::combinator::all_consuming(parse_index)(&second_arg)?;
-            i
-        } else {
-            0
-        };
+/// A character with [`crate::Args::utf8`], see [`characters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Character {
+    Scalar(char),
+    /// A byte which is not part of a valid UTF-8 sequence.
+    Byte(u8),
+}
 
-        if start_index > (first_arg.len() - 1) {
-            return Ok(state);
+impl Character {
+    fn encode(self, output: &mut Vec<u8>) {
+        match self {
+            Character::Scalar(c) => output.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            Character::Byte(b) => output.push(b),
         }
+    }
+}
 
-        let out = if let Some(third_arg) = args.next() {
-            let (_, number_of_chars) = nom::combinator::all_consuming(parse_index)(&third_arg)?;
+/// Split `s` into UTF-8 characters, each byte of an invalid sequence is a [`Character::Byte`].
+fn characters(s: &[u8]) -> Vec<Character> {
+    let mut characters = Vec::with_capacity(s.len());
+    for chunk in s.utf8_chunks() {
+        characters.extend(chunk.valid().chars().map(Character::Scalar));
+        characters.extend(chunk.invalid().iter().copied().map(Character::Byte));
+    }
+    characters
+}
 
-            if number_of_chars > 0 {
-                let end_index = usize::min(start_index + number_of_chars, first_arg.len());
-                &first_arg[start_index..end_index]
-            } else {
-                return Ok(state);
-            }
+/// The byte offset of the start of each of the [`characters`] of `s`, followed by `s.len()`.
+fn character_boundaries(s: &[u8]) -> Vec<usize> {
+    let mut boundaries = Vec::with_capacity(s.len() + 1);
+    let mut offset = 0;
+    for chunk in s.utf8_chunks() {
+        boundaries.extend(chunk.valid().char_indices().map(|(i, _)| offset + i));
+        offset += chunk.valid().len();
+        boundaries.extend(offset..offset + chunk.invalid().len());
+        offset += chunk.invalid().len();
+    }
+    boundaries.push(s.len());
+    boundaries
+}
+
+/// [`expand_ranges`] for [`characters`], a range between two scalar values includes those in
+/// between (other than surrogates) and a range between two invalid bytes the bytes in between. A
+/// `-` between one of each is itself.
+fn expand_character_ranges(s: &[Character]) -> Vec<Character> {
+    let range = |from: Character, to: Character| -> Option<Vec<Character>> {
+        let (from, to, character): (u32, u32, fn(u32) -> Option<Character>) = match (from, to) {
+            (Character::Scalar(from), Character::Scalar(to)) => (from.into(), to.into(), |c| {
+                char::from_u32(c).map(Character::Scalar)
+            }),
+            (Character::Byte(from), Character::Byte(to)) => (from.into(), to.into(), |c| {
+                u8::try_from(c).ok().map(Character::Byte)
+            }),
+            _ => return None,
+        };
+        Some(if from <= to {
+            (from + 1..=to).filter_map(character).collect()
         } else {
-            &first_arg[start_index..]
+            (to..from).rev().filter_map(character).collect()
+        })
+    };
+    let mut expanded = Vec::with_capacity(s.len());
+    let mut previous: Option<Character> = None;
+    let mut i = 0;
+    while i < s.len() {
+        let c = s[i];
+        if let (Character::Scalar('-'), Some(from), Some(&to)) = (c, previous, s.get(i + 1)) {
+            if let Some(range) = range(from, to) {
+                expanded.extend(range);
+                previous = Some(to);
+                i += 2;
+                continue;
+            }
+        }
+        expanded.push(c);
+        previous = Some(c);
+        i += 1;
+    }
+    expanded
+}
+
+/// Expand the ranges such as `a-z` in an argument to [`TranslitMacro`], the same as GNU m4. A
+/// range may be reversed, such as `z-a`, and a `-` at the start or end is itself.
+fn expand_ranges(s: &[u8]) -> Vec<u8> {
+    let mut expanded = Vec::with_capacity(s.len());
+    let mut previous: Option<u8> = None;
+    let mut i = 0;
+    while i < s.len() {
+        match (s[i], previous, s.get(i + 1)) {
+            (b'-', Some(from), Some(&to)) => {
+                if from <= to {
+                    expanded.extend((from..=to).skip(1));
+                } else {
+                    expanded.extend((to..from).rev());
+                }
+                previous = Some(to);
+                i += 2;
+            }
+            (c, _, _) => {
+                expanded.push(c);
+                previous = Some(c);
+                i += 1;
+            }
+        }
+    }
+    expanded
+}
+
+/// The defining text for the substr macro shall be the substring of the first argument beginning at
+/// the zero-offset character position specified by the second argument. The third argument, if
+/// specified, shall be the number of characters to select; if not specified, the characters from
+/// the starting point to the end of the first argument shall become the defining text. It shall not
+/// be an error to specify a starting point beyond the end of the first argument and the defining
+/// text shall be null. It shall be an error to specify an argument containing any non-numeric
+/// characters. The behavior is unspecified if substr is not immediately followed by a
+/// `<left-parenthesis>`.
+///
+/// Positions and lengths are in bytes, or in characters with [`crate::Args::utf8`]. Like GNU m4, a
+/// negative start or length also gives null defining text, as does a non-numeric argument after it
+/// has been diagnosed.
+pub struct SubstrMacro;
+
+impl MacroImplementation for SubstrMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 3 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `substr' ignored",
+            )?;
+        }
+        let mut args = frame.args.into_iter();
+        let first_arg = args.next().unwrap_or_default();
+        let Some(second_arg) = args.next() else {
+            // Like GNU m4, `substr(`abc')` is the whole of the first argument.
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `substr'",
+            )?;
+            state.input.pushback_string(first_arg);
+            return Ok(state);
         };
 
-        state.input.pushback_string(out);
+        let Some(start) = numeric_argument(&mut state, stderr, "substr", &second_arg)? else {
+            return Ok(state);
+        };
+        let boundaries = state.utf8.then(|| character_boundaries(&first_arg));
+        let available = match &boundaries {
+            Some(boundaries) => boundaries.len() - 1,
+            None => first_arg.len(),
+        } as i64;
+        let length = match args.next() {
+            Some(third_arg) => match numeric_argument(&mut state, stderr, "substr", &third_arg)? {
+                Some(length) => length,
+                None => return Ok(state),
+            },
+            None => available,
+        };
+
+        // A negative start or length, or a start beyond the end, selects nothing.
+        if start < 0 || length <= 0 || start >= available {
+            return Ok(state);
+        }
+        let end = start.saturating_add(length).min(available);
+        let offset = |i: i64| match &boundaries {
+            Some(boundaries) => boundaries[i as usize],
+            None => i as usize,
+        };
+        state
+            .input
+            .pushback_string(&first_arg[offset(start)..offset(end)]);
         Ok(state)
     }
 }
 
 /// The dumpdef macro shall write the defined text to standard error for each of the macros
 /// specified as arguments, or, if no arguments are specified, for all macros.
+///
+/// Like GNU m4 each macro is written as `name:<tab>definition` (quoted with [`DebugFlags::QUOTE`])
+/// sorted by name, to the debug output (see [`crate::debug::DebugFile`]). Builtins are shown as
+/// `<builtin>`.
 pub struct DumpdefMacro;
 
 impl MacroImplementation for DumpdefMacro {
-    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        let dumpdef = |stderr: &mut dyn Write, n: &MacroName, d: &MacroDefinition| {
-            write!(stderr, "{n}:\t")?;
-            match &d.implementation {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut definitions: Vec<Rc<MacroDefinition>> = Vec::new();
+        if frame.args.is_empty() {
+            definitions.extend(state.macro_definitions.values().map(|definitions| {
+                definitions
+                    .last()
+                    .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT)
+                    .clone()
+            }));
+        }
+        for arg in frame.args {
+            // An argument which is not a macro name (such as an empty one) is not defined either.
+            let named = state
+                .parse_config
+                .macro_name(&arg)
+                .ok()
+                .and_then(|name| state.macro_definitions.get(&name));
+            match named {
+                Some(named) => definitions.push(
+                    named
+                        .last()
+                        .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT)
+                        .clone(),
+                ),
+                None => state.warning_message(
+                    stderr,
+                    WarningCategory::UndefinedMacro,
+                    format!("undefined macro `{}'", String::from_utf8_lossy(&arg)),
+                )?,
+            }
+        }
+        definitions.sort_by(|a, b| a.parse_config.name.cmp(&b.parse_config.name));
+
+        let mut out: Vec<u8> = Vec::new();
+        for definition in definitions {
+            write!(out, "{}:\t", definition.parse_config.name)?;
+            match &definition.implementation {
                 MacroDefinitionImplementation::UserDefined(user_defined) => {
-                    stderr.write_all(&user_defined.definition)?;
+                    let quote = state.debug_flags.contains(DebugFlags::QUOTE);
+                    if quote {
+                        out.extend(&state.parse_config.quote_open_tag);
+                    }
+                    out.extend(&user_defined.definition);
+                    if quote {
+                        out.extend(&state.parse_config.quote_close_tag);
+                    }
+                }
+                implementation => {
+                    // The name of the builtin itself, which can differ from the macro name, for
+                    // example with `-P`.
+                    let builtin = implementation
+                        .builtin()
+                        .expect("implementation is not user defined");
+                    out.extend(b"<");
+                    out.extend(builtin.as_ref());
+                    out.extend(b">");
                 }
-                _ => {
-                    stderr.write_all(b"<")?;
-                    stderr.write_all(&n.0)?;
-                    stderr.write_all(b">")?;
+            }
+            out.extend(b"\n");
+        }
+
+        write_debug(&state, stderr, &out)?;
+        Ok(state)
+    }
+}
+
+/// GNU extension. Change what is considered a macro name to the words matched by the regular
+/// expression in the first argument, see [`crate::lexer::WordRegex`]. An empty argument restores
+/// the default `[_a-zA-Z][_a-zA-Z0-9]*`.
+pub struct ChangewordMacro;
+
+impl MacroImplementation for ChangewordMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut args = frame.args.into_iter();
+        let pattern = args.next().unwrap_or_default();
+        if args.next().is_some() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `changeword' ignored",
+            )?;
+        }
+        match WordRegex::new(&pattern) {
+            Ok(word_regex) => state.parse_config.word_regex = word_regex,
+            Err(error) => state.warning(stderr, WarningCategory::Regex, error)?,
+        }
+        Ok(state)
+    }
+}
+
+/// GNU extension. Replace each match of the regular expression (see [`crate::gnu_regex`]) in the
+/// second argument found in the first argument with the third argument, the defining text is
+/// rescanned. In the replacement `\&` is the whole match and `\1` to `\9` are the text matched by
+/// the corresponding group, see [`substitute`]. Without a replacement the matches are deleted.
+///
+/// An empty match is replaced too, after which the following character is copied so that
+/// matching continues from the next one, the same as GNU m4.
+pub struct PatsubstMacro;
+
+impl MacroImplementation for PatsubstMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 3 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `patsubst' ignored",
+            )?;
+        }
+        let mut args = frame.args.into_iter();
+        let victim = args.next().unwrap_or_default();
+        let Some(pattern) = args.next() else {
+            // Like GNU m4, `patsubst(`abc')` is the first argument unchanged.
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `patsubst'",
+            )?;
+            state.input.pushback_string(victim);
+            return Ok(state);
+        };
+        let replacement = args.next().unwrap_or_default();
+        let regex = match crate::gnu_regex::compile(&pattern) {
+            Ok(regex) => regex,
+            Err(error) => {
+                state.warning(stderr, WarningCategory::Regex, error)?;
+                return Ok(state);
+            }
+        };
+
+        let mut output = Vec::with_capacity(victim.len());
+        let mut offset = 0;
+        while offset <= victim.len() {
+            let Some(captures) = regex.captures_at(&victim, offset) else {
+                output.extend(&victim[offset..]);
+                break;
+            };
+            let whole = captures.get(0).expect("group 0 is always present");
+            output.extend(&victim[offset..whole.start()]);
+            substitute(
+                &mut state,
+                stderr,
+                "patsubst",
+                &replacement,
+                &captures,
+                &mut output,
+            )?;
+            offset = whole.end();
+            if whole.is_empty() {
+                if let Some(c) = victim.get(offset) {
+                    output.push(*c);
                 }
+                offset += 1;
+            }
+        }
+        state.input.pushback_string(output);
+        Ok(state)
+    }
+}
+
+/// GNU extension. Search the first argument for a match of the regular expression (see
+/// [`crate::gnu_regex`]) in the second argument. Without a third argument the defining text is the
+/// index of the first match, or -1 if there is none. Otherwise it is the third argument with the
+/// references to the first match replaced, the same as [`PatsubstMacro`], or null if there is no
+/// match.
+pub struct RegexpMacro;
+
+impl MacroImplementation for RegexpMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 3 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `regexp' ignored",
+            )?;
+        }
+        let mut args = frame.args.into_iter();
+        let victim = args.next().unwrap_or_default();
+        let Some(pattern) = args.next() else {
+            // Like GNU m4, `regexp(`abc')` is 0.
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `regexp'",
+            )?;
+            state.input.pushback_character(b'0');
+            return Ok(state);
+        };
+        let regex = match crate::gnu_regex::compile(&pattern) {
+            Ok(regex) => regex,
+            Err(error) => {
+                state.warning(stderr, WarningCategory::Regex, error)?;
+                return Ok(state);
             }
-            Result::Ok(())
         };
-        if frame.args.is_empty() {
-            for (name, definitions) in state.macro_definitions.iter() {
-                dumpdef(
-                    stderr,
-                    name,
-                    definitions
-                        .last()
-                        .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT),
-                )?;
-                stderr.write_all(b"\n")?;
+
+        let captures = regex.captures(&victim);
+        match args.next() {
+            None => {
+                let index = captures
+                    .map(|captures| captures.get(0).expect("group 0 is always present").start())
+                    .map_or(-1, |start| start as i64);
+                state.input.pushback_string(index.to_string().as_bytes());
+            }
+            Some(replacement) => {
+                if let Some(captures) = captures {
+                    let mut output = Vec::new();
+                    substitute(
+                        &mut state,
+                        stderr,
+                        "regexp",
+                        &replacement,
+                        &captures,
+                        &mut output,
+                    )?;
+                    state.input.pushback_string(output);
+                }
             }
         }
+        Ok(state)
+    }
+}
 
-        for arg in frame.args.into_iter() {
-            let name = MacroName::try_from_slice(&arg)?;
-            match state.macro_definitions.get(&name) {
-                Some(definition) => dumpdef(
+/// Append `replacement` to `output`, with `\&` (or `\0`) replaced by the whole match in
+/// `captures` and `\1` to `\9` by the text matched by the corresponding group, which is empty if
+/// the group did not participate in the match. Any other character following a `\` is itself.
+/// Used by [`PatsubstMacro`] and [`RegexpMacro`], diagnostics are for the builtin `name`.
+fn substitute(
+    state: &mut State,
+    stderr: &mut dyn Write,
+    name: &str,
+    replacement: &[u8],
+    captures: &regex::bytes::Captures,
+    output: &mut Vec<u8>,
+) -> Result<()> {
+    let mut chars = replacement.iter();
+    while let Some(&c) = chars.next() {
+        if c != b'\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(b'&') | Some(b'0') => {
+                output.extend(captures.get(0).map_or(&[][..], |m| m.as_bytes()))
+            }
+            Some(&digit @ b'1'..=b'9') => {
+                let index = usize::from(digit - b'0');
+                if index >= captures.len() {
+                    state.warning(
+                        stderr,
+                        WarningCategory::Regex,
+                        format_args!("sub-expression {index} not present in builtin `{name}'"),
+                    )?;
+                } else if let Some(group) = captures.get(index) {
+                    output.extend(group.as_bytes());
+                }
+            }
+            Some(&other) => output.push(other),
+            None => {
+                state.warning(
                     stderr,
-                    &name,
-                    definition
-                        .last()
-                        .expect(AT_LEAST_ONE_MACRO_DEFINITION_EXPECT),
-                )?,
-                None => write!(stderr, "undefined macro {name}")?,
+                    WarningCategory::Regex,
+                    format_args!("trailing \\ ignored in replacement in builtin `{name}'"),
+                )?;
             }
-            stderr.write_all(b"\n")?;
         }
+    }
+    Ok(())
+}
 
+/// GNU extension. With no arguments, the debug output is written to standard error. With an empty
+/// argument the debug output is discarded, otherwise it is appended to the named file. See
+/// [`crate::debug::DebugFile`].
+pub struct DebugfileMacro;
+
+impl MacroImplementation for DebugfileMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut args = frame.args.into_iter();
+        let Some(path) = args.next() else {
+            state.debug_file = DebugFile::Stderr;
+            return Ok(state);
+        };
+        if args.next().is_some() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `debugfile' ignored",
+            )?;
+        }
+        match DebugFile::open_bytes(&path) {
+            Ok(debug_file) => state.debug_file = debug_file,
+            Err(error) => state.warning(
+                stderr,
+                WarningCategory::Other,
+                format!(
+                    "cannot set debug file `{}': {error}",
+                    String::from_utf8_lossy(&path)
+                ),
+            )?,
+        }
+        Ok(state)
+    }
+}
+
+/// GNU extension. `debugmode(flags)` changes the current [`DebugFlags`], using the same letters as
+/// the `-d` option (see [`DebugFlags::parse`]). Flags prefixed with `+` are added to the current
+/// flags and flags prefixed with `-` are removed, otherwise they replace them. An empty argument
+/// selects [`DebugFlags::DEFAULT`], and without arguments all the flags are cleared.
+pub struct DebugmodeMacro;
+
+impl MacroImplementation for DebugmodeMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut args = frame.args.into_iter();
+        let Some(arg) = args.next() else {
+            state.debug_flags = DebugFlags::empty();
+            return Ok(state);
+        };
+        if args.next().is_some() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `debugmode' ignored",
+            )?;
+        }
+        let (change, flags) = match arg.split_first() {
+            Some((c @ (b'+' | b'-'), flags)) => (Some(*c), flags),
+            _ => (None, arg.as_slice()),
+        };
+        let Ok(flags) = DebugFlags::parse(flags) else {
+            state.warning_message(
+                stderr,
+                WarningCategory::Other,
+                format_args!(
+                    "Debugmode: bad debug flags: `{}'",
+                    String::from_utf8_lossy(&arg)
+                ),
+            )?;
+            return Ok(state);
+        };
+        state.debug_flags = match change {
+            Some(b'+') => state.debug_flags | flags,
+            Some(_) => state.debug_flags.difference(flags),
+            None => flags,
+        };
         Ok(state)
     }
 }
@@ -763,82 +1681,130 @@ impl MacroImplementation for DumpdefMacro {
 /// a `<left-parenthesis>`.
 pub struct MkstempMacro;
 
-/// The mkstemp() function shall replace the contents of the `template` by a unique filename which
-/// is returned. The string in template should look like a filename with six trailing 'X' s;
-/// mkstemp() replaces each 'X' with a character from the portable filename character set. The
-/// characters are chosen such that the resulting name does not duplicate the name of an existing
-/// file at the time of a call to mkstemp().
-fn mkstemp(mut template: Vec<u8>) -> Result<Vec<u8>> {
-    if template.len() < 6 {
-        return Err(crate::Error::new(crate::ErrorKind::Io).with_source(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Unable to create temporary file, template should be greater than 6 characters long. template: {:?}", String::from_utf8_lossy(&template))
-        )));
-    }
-    if &template[(template.len() - 6)..] != b"XXXXXX" {
-        return Err(crate::Error::new(crate::ErrorKind::Io).with_source(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Unable to create temporary file, template does not finish with six trailing 'X's. template: {:?}", String::from_utf8_lossy(&template))
-        )));
-    }
-    let template_pointer = template.as_mut_ptr();
-    // SAFETY: According to https://man7.org/linux/man-pages/man3/mkstemp.3.html it seems like this is correct.
-    let file_descriptor = unsafe {
-        // Docs: https://pubs.opengroup.org/onlinepubs/009604499/functions/mkstemp.html
-        libc::mkstemp(template_pointer as *mut i8)
-    };
+/// Create a new file from `template` using
+/// [`mkstemp()`](https://pubs.opengroup.org/onlinepubs/9699919799/functions/mkstemp.html), which
+/// opens it with `O_EXCL` and permissions `0600` so that an existing file is never reused. Like
+/// GNU m4, trailing `X`s are added to the template so that there are at least six. A template
+/// without a directory is created in `TMPDIR` when it is set. Returns the name of the file.
+fn mkstemp(template: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut name = Vec::new();
+    if !template.contains(&b'/') {
+        if let Some(directory) = std::env::var_os("TMPDIR").filter(|d| !d.is_empty()) {
+            name.extend(directory.as_bytes());
+            if !name.ends_with(b"/") {
+                name.push(b'/');
+            }
+        }
+    }
+    name.extend(template);
+    let trailing_xs = template
+        .iter()
+        .rev()
+        .take(6)
+        .take_while(|c| **c == b'X')
+        .count();
+    name.resize(name.len() + 6 - trailing_xs, b'X');
+    if name.contains(&0) {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    }
+
+    name.push(0);
+    // SAFETY: `name` is a nul terminated string which `mkstemp` modifies in place, without
+    // changing its length.
+    let file_descriptor = unsafe { libc::mkstemp(name.as_mut_ptr().cast()) };
     if file_descriptor < 0 {
-        let e = errno::errno();
-        return Err(
-            crate::Error::new(crate::ErrorKind::Io).with_source(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "Unable to create temporary file. template: {:?}, Error {}: {}",
-                    String::from_utf8_lossy(&template),
-                    e.0,
-                    e
-                ),
-            )),
-        );
+        return Err(std::io::Error::last_os_error());
     }
-    // SAFETY: According to https://linux.die.net/man/2/close it seems like this is correct.
-    let result = unsafe { libc::close(file_descriptor) };
-    if result < 0 {
-        let e = errno::errno();
-        return Err(
-            crate::Error::new(crate::ErrorKind::Io).with_source(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!(
-                    "Unable to close temporary file. template: {:?}, Error {}: {}",
+    // SAFETY: `file_descriptor` was just opened by `mkstemp` and is not used elsewhere.
+    drop(unsafe { std::fs::File::from_raw_fd(file_descriptor) });
+    name.pop();
+    Ok(name)
+}
+
+/// Shared by [`MkstempMacro`] and [`MaketempMacro`] (`name`), the created file name is quoted so
+/// that it is not rescanned for macros.
+fn mkstemp_macro(
+    name: &str,
+    mut state: State,
+    stderr: &mut dyn Write,
+    frame: StackFrame,
+) -> Result<State> {
+    if frame.args.len() > 1 {
+        state.retrievable_warning(
+            stderr,
+            WarningCategory::ExcessArguments,
+            format!("excess arguments to builtin `{name}' ignored"),
+        )?;
+    }
+    let template = frame.args.into_iter().next().unwrap_or_default();
+    match mkstemp(&template) {
+        Ok(path) => {
+            let mut quoted = state.parse_config.quote_open_tag.clone();
+            quoted.extend(path);
+            quoted.extend(&state.parse_config.quote_close_tag);
+            state.input.pushback_string(quoted);
+        }
+        Err(error) => {
+            state.error_message(
+                stderr,
+                "io",
+                format_args!(
+                    "{name}: cannot create tempfile `{}': {}",
                     String::from_utf8_lossy(&template),
-                    e.0,
-                    e
+                    io_error_description(&error)
                 ),
-            )),
-        );
+            )?;
+        }
     }
-    Ok(template)
+    Ok(state)
 }
 
 impl MacroImplementation for MkstempMacro {
+    fn evaluate(&self, state: State, stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+        mkstemp_macro("mkstemp", state, stderr, frame)
+    }
+}
+
+/// The obsolete predecessor of [`MkstempMacro`]. POSIX specifies that the trailing `X`s of the
+/// template are replaced with the process ID, without creating a file, so the name is
+/// predictable. This is only done in [`Mode::Traditional`] (with a warning), otherwise it is the
+/// same as [`MkstempMacro`].
+pub struct MaketempMacro;
+
+impl MacroImplementation for MaketempMacro {
     fn evaluate(
         &self,
         mut state: State,
         stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        let first_arg = frame
-            .args
-            .into_iter()
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        match mkstemp(first_arg) {
-            Ok(pathname) => state.input.pushback_string(&pathname),
-            Err(error) => {
-                write!(stderr, "Error evaluating `mkstemp` macro: {}", error)?;
-                state.exit_error = true;
-            }
+        if state.mode != Mode::Traditional {
+            return mkstemp_macro("maketemp", state, stderr, frame);
         }
+        state.warning_message(
+            stderr,
+            WarningCategory::Other,
+            "recommend using mkstemp instead",
+        )?;
+        let template = frame.args.into_iter().next().unwrap_or_default();
+        // The first character is kept, even if it is an `X`.
+        let trailing_xs = template
+            .iter()
+            .skip(1)
+            .rev()
+            .take_while(|c| **c == b'X')
+            .count();
+        let (prefix, xs) = template.split_at(template.len() - trailing_xs);
+        let pid = std::process::id().to_string();
+        let mut name = prefix.to_vec();
+        if pid.len() > xs.len() {
+            // Only the last digits of the process ID fit.
+            name.extend(&pid.as_bytes()[pid.len() - xs.len()..]);
+        } else {
+            name.resize(name.len() + xs.len() - pid.len(), b'0');
+            name.extend(pid.as_bytes());
+        }
+        state.input.pushback_string(name);
         Ok(state)
     }
 }
@@ -848,27 +1814,58 @@ impl MacroImplementation for MkstempMacro {
 /// containing any non-numeric characters. If the first argument is zero or no argument is
 /// specified, and an error has previously occurred (for example, a file operand that could not be
 /// opened), it is unspecified whether the exit status is zero or non-zero.
+///
+/// Like GNU m4, a previous error makes the exit status non-zero, and the exit is immediate: text
+/// saved by [`M4wrapMacro`] is not processed, pending diversions are discarded and the state is
+/// not frozen.
 pub struct M4exitMacro;
 
 impl MacroImplementation for M4exitMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut exit_code = 0;
         if let Some(first_arg) = frame.args.into_iter().next() {
-            let (_, exit_code) = nom::combinator::all_consuming(parse_index)(&first_arg)?;
-            let exit_code: i32 = i32::try_from(exit_code).map_err(|e| {
-                crate::Error::new(crate::ErrorKind::Parsing).add_context(e.to_string())
-            })?;
-            if exit_code == 0 && state.exit_error {
-                return Err(crate::Error::new(crate::ErrorKind::Exit(1)));
-            } else {
-                return Err(crate::Error::new(crate::ErrorKind::Exit(exit_code)));
+            // Like GNU m4, an invalid exit status is diagnosed and exits with a failure.
+            match nom::combinator::all_consuming(parse_integer)(&first_arg) {
+                Ok((_, code)) => match u8::try_from(code) {
+                    Ok(code) => exit_code = i32::from(code),
+                    Err(_) => {
+                        state.warning(
+                            stderr,
+                            WarningCategory::Numeric,
+                            format!("exit status out of range: `{code}'"),
+                        )?;
+                        exit_code = 1;
+                    }
+                },
+                Err(_) if first_arg.is_empty() => {
+                    state.warning(
+                        stderr,
+                        WarningCategory::Numeric,
+                        "empty string treated as 0 in builtin `m4exit'",
+                    )?;
+                }
+                Err(_) => {
+                    state.warning(
+                        stderr,
+                        WarningCategory::Numeric,
+                        "non-numeric argument to builtin `m4exit'",
+                    )?;
+                    exit_code = 1;
+                }
             }
         }
 
-        if state.exit_error {
-            Err(crate::Error::new(crate::ErrorKind::Exit(1)))
-        } else {
-            Err(crate::Error::new(crate::ErrorKind::Exit(0)))
+        // A previous error is not hidden by a successful exit.
+        if exit_code == 0 && state.exit_error {
+            exit_code = 1;
         }
+        state.write_profile(stderr)?;
+        Err(crate::Error::new(crate::ErrorKind::Exit(exit_code)))
     }
 }
 
@@ -876,6 +1873,11 @@ impl MacroImplementation for M4exitMacro {
 /// times, the arguments specified shall be processed in the order in which the `m4wrap` macros were
 /// processed. The behavior is unspecified if `m4wrap` is not immediately followed by a
 /// `<left-parenthesis>`.
+///
+/// The saved text is rescanned once all the input has been processed, see
+/// [`crate::main_loop::finish`]. Text saved while doing so is processed in a further round, after
+/// the text from the current round. Its location (see [`FileMacro`] and [`LineMacro`]) is where
+/// `m4wrap` was called.
 pub struct M4wrapMacro;
 
 impl MacroImplementation for M4wrapMacro {
@@ -885,12 +1887,13 @@ impl MacroImplementation for M4wrapMacro {
         _stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        let first_arg = frame
-            .args
-            .into_iter()
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        state.m4wrap.push(first_arg);
+        // `m4wrap()` has no arguments, which is the same as saving empty text.
+        let first_arg = frame.args.into_iter().next().unwrap_or_default();
+        let name = state.input.name().unwrap_or_default();
+        let line_number = state.input.line_number().unwrap_or_default();
+        state
+            .m4wrap
+            .push(Input::wrapped(first_arg, name, line_number));
         Ok(state)
     }
 }
@@ -900,53 +1903,141 @@ impl MacroImplementation for M4wrapMacro {
 /// while setting the defining text. No output redirection shall be performed by the m4 utility. The
 /// exit status value from the command can be retrieved using the sysval macro. The behavior is
 /// unspecified if syscmd is not immediately followed by a `<left-parenthesis>`.
+///
+/// Like GNU m4, the command is run using `sh -c` and its standard output and standard error are
+/// not captured, they are written directly to those of m4 (which are flushed first so that the
+/// output stays in order). This means it bypasses any current diversion and the defining text is
+/// empty.
 pub struct SyscmdMacro;
 
-fn system(command: &[u8]) -> Result<ExitStatus> {
-    let command = OsStr::from_bytes(command);
+/// Run `command` with `sh -c`, after flushing the output so that the command's output comes after
+/// it. A command which cannot be run is diagnosed, and gives the exit status 127 like a shell.
+fn system(
+    command: &[u8],
+    name: &str,
+    state: &mut State,
+    stderr: &mut dyn Write,
+    configure: impl FnOnce(&mut std::process::Command),
+) -> Result<Option<std::process::Output>> {
+    state.output.output.flush_stdout()?;
+    stderr.flush()?;
     // TODO(security): check security of this, for shell injection? It seems to be what the GNU m4
     // does in https://github.com/tar-mirror/gnu-m4/blob/master/src/builtin.c#L953
-    let status = std::process::Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .status()?;
-
-    Ok(status)
+    let mut child = std::process::Command::new("sh");
+    child.arg("-c").arg(OsStr::from_bytes(command));
+    configure(&mut child);
+    match child.output() {
+        Ok(output) => {
+            state.last_syscmd_status = Some(output.status);
+            Ok(Some(output))
+        }
+        Err(error) => {
+            state.error_message(
+                stderr,
+                "io",
+                format_args!(
+                    "{name}: cannot run command `{}': {}",
+                    String::from_utf8_lossy(command),
+                    io_error_description(&error)
+                ),
+            )?;
+            state.last_syscmd_status = Some(ExitStatus::from_raw(127 << 8));
+            Ok(None)
+        }
+    }
 }
 
 impl MacroImplementation for SyscmdMacro {
     fn evaluate(
         &self,
         mut state: State,
-        _stderr: &mut dyn Write,
+        stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        let first_arg = frame
-            .args
-            .into_iter()
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-        let status = system(&first_arg)?;
-        state.last_syscmd_status = Some(status);
+        if frame.args.len() > 1 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `syscmd' ignored",
+            )?;
+        }
+        // `syscmd()` has a single empty argument, which is an empty command.
+        let first_arg = frame.args.into_iter().next().unwrap_or_default();
+        system(&first_arg, "syscmd", &mut state, stderr, |command| {
+            command
+                .stdin(std::process::Stdio::inherit())
+                .stdout(std::process::Stdio::inherit())
+                .stderr(std::process::Stdio::inherit());
+        })?;
+        Ok(state)
+    }
+}
+
+/// A GNU extension, the same as [`SyscmdMacro`] except that the standard output of the command is
+/// captured and is the defining text, which is rescanned. Like GNU m4 it is used as is, a trailing
+/// newline is not removed.
+pub struct EsyscmdMacro;
+
+impl MacroImplementation for EsyscmdMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if frame.args.len() > 1 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `esyscmd' ignored",
+            )?;
+        }
+        let first_arg = frame.args.into_iter().next().unwrap_or_default();
+        let output = system(&first_arg, "esyscmd", &mut state, stderr, |command| {
+            command
+                .stdin(std::process::Stdio::inherit())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::inherit());
+        })?;
+        if let Some(output) = output {
+            state.input.pushback_string(output.stdout);
+        }
         Ok(state)
     }
 }
 
 /// The defining text of the `sysval` macro shall be the exit value of the utility last invoked by the
-/// [`SyscmdMacro`] (as a string).
+/// [`SyscmdMacro`] (as a string), or [`EsyscmdMacro`].
+///
+/// It is 0 before any command has been run. A command terminated by a signal is encoded the same
+/// as GNU m4 does, the signal number shifted into the second byte, in [`Mode::Traditional`] it is
+/// instead 128 plus the signal number like a shell.
 pub struct SysvalMacro;
 
 impl MacroImplementation for SysvalMacro {
-    fn evaluate(&self, state: State, stderr: &mut dyn Write, _frame: StackFrame) -> Result<State> {
-        if let Some(status) = state.last_syscmd_status {
-            match status.code() {
-                Some(code) => state.input.pushback_string(code.to_string().as_bytes()),
-                None => write!(
-                    stderr,
-                    "Last syscmd exited without exit code (process terminated by signal)"
-                )?,
-            }
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if !frame.args.is_empty() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `sysval' ignored",
+            )?;
         }
+        let sysval = match state.last_syscmd_status {
+            None => 0,
+            Some(status) => match (status.code(), status.signal()) {
+                (Some(code), _) => code,
+                (None, Some(signal)) if state.mode == Mode::Traditional => 128 + signal,
+                (None, Some(signal)) => signal << 8,
+                (None, None) => status.into_raw(),
+            },
+        };
+        state.input.pushback_string(sysval.to_string().as_bytes());
         Ok(state)
     }
 }
@@ -958,6 +2049,8 @@ impl MacroImplementation for SysvalMacro {
 /// normal output process. Output diverted to a stream with a negative number shall be discarded.
 /// Behavior is implementation-defined if a stream number larger than 9 is specified. It shall be an
 /// error to specify an argument containing any non-numeric characters.
+///
+/// Like GNU m4, any positive stream number can be used.
 pub struct DivertMacro;
 
 impl MacroImplementation for DivertMacro {
@@ -967,24 +2060,19 @@ impl MacroImplementation for DivertMacro {
         stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        let divert_number = if let Some(first_arg) = frame.args.into_iter().next() {
-            if first_arg.is_empty() {
-                write!(
-                    stderr,
-                    "WARNING: treating empty first argument of `divert` macro as 0"
-                )?;
-                0
-            } else {
-                let (_, divert_number) = nom::combinator::all_consuming(parse_integer)(&first_arg)?;
-                if divert_number > 9 {
-                    return Err(crate::Error::new(crate::ErrorKind::Parsing).add_context(format!(
-                        "Error parsing first argument for `divert` macro with value {divert_number}, should be between 0 and 9"
-                    )));
-                }
-                divert_number
-            }
-        } else {
-            0
+        if frame.args.len() > 1 {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `divert' ignored",
+            )?;
+        }
+        let divert_number = match frame.args.into_iter().next() {
+            Some(first_arg) => match numeric_argument(&mut state, stderr, "divert", &first_arg)? {
+                Some(divert_number) => divert_number,
+                None => return Ok(state),
+            },
+            None => 0,
         };
         state.output.output.divert(divert_number)?;
         Ok(state)
@@ -992,10 +2080,25 @@ impl MacroImplementation for DivertMacro {
 }
 
 /// The defining text of the divnum macro shall be the number of the current output stream as a string.
+///
+/// This is the number most recently passed to [`DivertMacro`], including a negative number for
+/// output which is being discarded.
 pub struct DivnumMacro;
 
 impl MacroImplementation for DivnumMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, _frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        if !frame.args.is_empty() {
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::ExcessArguments,
+                "excess arguments to builtin `divnum' ignored",
+            )?;
+        }
         state
             .input
             .pushback_string(state.output.output.divert_number().to_string().as_bytes());
@@ -1007,26 +2110,15 @@ impl MacroImplementation for DivnumMacro {
 /// arguments, or all temporary buffers if no arguments are specified. Buffers can be undiverted
 /// into other temporary buffers. Undiverting shall discard the contents of the temporary buffer.
 /// The behavior is unspecified if an argument contains any non-numeric characters.
+///
+/// When undiverting within a diversion, the text is appended to the current diversion. The current
+/// diversion itself is silently left alone.
+///
+/// Like GNU m4, a non-numeric argument is the name of a file (searched for the same as with
+/// [`IncludeMacro`]) whose contents are copied to the output without being scanned for macros.
+/// In [`Mode::Traditional`] it is diagnosed instead.
 pub struct UndivertMacro;
 
-// TODO(performance): rewrite to be more performant and remove the need to parse as `str`.
-fn parse_index(input: &[u8]) -> IResult<&[u8], usize> {
-    log::trace!("parse_index() {}", String::from_utf8_lossy(input));
-    let (remaining, found) = nom::bytes::complete::take_while(|c: u8| c.is_ascii_digit())(input)?;
-    let s = std::str::from_utf8(found).expect("Should be valid utf8 betweeen b'0' and b'9'");
-    let i: usize = s.parse().map_err(|e| {
-        let e = nom::error::Error::from_external_error(found, nom::error::ErrorKind::Digit, e);
-        nom::Err::Error(nom::error::Error::add_context(
-            found,
-            "Error parsing user defined macro argument as an index",
-            e,
-        ))
-    })?;
-    log::trace!("parse_index() successfully parsed: {i}");
-
-    Ok((remaining, i))
-}
-
 impl MacroImplementation for UndivertMacro {
     fn evaluate(
         &self,
@@ -1034,41 +2126,119 @@ impl MacroImplementation for UndivertMacro {
         stderr: &mut dyn Write,
         frame: StackFrame,
     ) -> Result<State> {
-        let undivert_buffers: Vec<DivertBufferNumber> = if frame.args.is_empty() {
-            (1..=9)
-                .map(DivertBufferNumber::try_from)
-                .collect::<Result<Vec<_>>>()?
-        } else {
-            let mut undivert_buffers: Vec<DivertBufferNumber> = Vec::new();
-            for arg in frame.args.into_iter() {
-                let (_, buffer_number) = nom::combinator::all_consuming(parse_index)(&arg)?;
-                match DivertBufferNumber::try_from(buffer_number) {
-                    Ok(n) => undivert_buffers.push(n),
-                    Err(error) => write!(stderr, "WARNING: {error}")?,
-                }
+        if frame.args.is_empty() {
+            state.output.output.undivert_all()?;
+            return Ok(state);
+        }
+        for arg in frame.args.into_iter() {
+            // Like GNU m4, an empty argument is diversion 0.
+            let buffer_number = if arg.is_empty() {
+                0
+            } else if let Ok((_, buffer_number)) =
+                nom::combinator::all_consuming(parse_integer)(&arg)
+            {
+                buffer_number
+            } else {
+                state = Self::undivert_file(&arg, state, stderr)?;
+                continue;
+            };
+            // Undiverting the standard output, a discarded diversion, or the current diversion
+            // does nothing.
+            if let Ok(buffer_number) = DivertBufferNumber::try_from(buffer_number) {
+                state.output.output.undivert(buffer_number)?;
             }
+        }
+        Ok(state)
+    }
+}
 
-            undivert_buffers
+impl UndivertMacro {
+    fn undivert_file(arg: &[u8], mut state: State, stderr: &mut dyn Write) -> Result<State> {
+        if state.mode == Mode::Traditional {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                "non-numeric argument to builtin `undivert'",
+            )?;
+            return Ok(state);
+        }
+        let path = PathBuf::from(OsString::from_vec(arg.to_vec()));
+        let contents = match IncludeMacro::resolve_file_path(&path, &state) {
+            Some(resolved) if resolved.is_dir() => {
+                Err(std::io::Error::from_raw_os_error(libc::EISDIR))
+            }
+            Some(resolved) => std::fs::read(resolved),
+            None => Err(std::io::Error::from_raw_os_error(libc::ENOENT)),
         };
-
-        for buffer_number in undivert_buffers {
-            state.output.output.undivert(buffer_number)?;
+        match contents {
+            Ok(contents) => state.output.output.undivert_file(&contents)?,
+            Err(error) => {
+                state.error_message(
+                    stderr,
+                    "io",
+                    format_args!(
+                        "cannot undivert `{}': {}",
+                        path.display(),
+                        io_error_description(&error)
+                    ),
+                )?;
+            }
         }
         Ok(state)
     }
 }
 
+/// GNU extension. `__file__` expands to the quoted name of the current input file, `stdin` for
+/// the standard input. While reading text saved by [`M4wrapMacro`] it is the input that `m4wrap`
+/// was called from.
 pub struct FileMacro;
 
 impl MacroImplementation for FileMacro {
     fn evaluate(&self, state: State, _stderr: &mut dyn Write, _frame: StackFrame) -> Result<State> {
-        let name = state.input.with(|input_state| {
-            match &input_state.input.last().expect("At least one input").input {
-                InputRead::File { path, .. } => path.as_os_str().as_encoded_bytes().to_vec(),
-                InputRead::Stdin(_) => b"stdin".to_vec(),
-            }
-        });
-        state.input.pushback_string(&name);
+        let name = state.input.name().unwrap_or_default();
+        let mut expansion = state.parse_config.quote_open_tag.clone();
+        expansion.extend(&name);
+        expansion.extend(&state.parse_config.quote_close_tag);
+        state.input.pushback_string(expansion);
+        Ok(state)
+    }
+}
+
+/// The platform macros `__gnu__` and `__unix__` (only defined in [`Mode::Gnu`]) and `unix` (only
+/// defined in [`Mode::Traditional`]) expand to nothing, they can be tested using `ifdef` in order
+/// to detect the implementation. Only POSIX systems are supported, so `__windows__` is never
+/// defined. See [`BuiltinMacro::is_defined_in`].
+pub struct PlatformMacro;
+
+impl MacroImplementation for PlatformMacro {
+    fn evaluate(&self, state: State, _stderr: &mut dyn Write, _frame: StackFrame) -> Result<State> {
+        Ok(state)
+    }
+}
+
+/// GNU extension. `__line__` expands to the current line number of the current input file. The
+/// expansion of a macro is read from the line on which the macro was called, and text saved by
+/// [`M4wrapMacro`] from the line on which `m4wrap` was called.
+pub struct LineMacro;
+
+impl MacroImplementation for LineMacro {
+    fn evaluate(&self, state: State, _stderr: &mut dyn Write, _frame: StackFrame) -> Result<State> {
+        let line = state.input.line_number().unwrap_or_default();
+        state.input.pushback_string(line.to_string().as_bytes());
+        Ok(state)
+    }
+}
+
+/// GNU extension. `__program__` expands to the quoted name that m4 was invoked as, see
+/// [`crate::Args::program_name`].
+pub struct ProgramMacro;
+
+impl MacroImplementation for ProgramMacro {
+    fn evaluate(&self, state: State, _stderr: &mut dyn Write, _frame: StackFrame) -> Result<State> {
+        let mut expansion = state.parse_config.quote_open_tag.clone();
+        expansion.extend(&state.program_name);
+        expansion.extend(&state.parse_config.quote_close_tag);
+        state.input.pushback_string(expansion);
         Ok(state)
     }
 }
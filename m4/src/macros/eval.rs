@@ -1,41 +1,161 @@
 use std::io::Write;
 
-use nom::{
-    branch::alt,
-    bytes::complete::{tag, take_while},
-    combinator::fail,
-    error::FromExternalError,
-    sequence::{delimited, tuple},
-    IResult,
-};
+use nom::{bytes::complete::take_while, error::FromExternalError, sequence::tuple, IResult};
 
 use crate::{
     lexer::is_whitespace,
-    precedence::{self, binary_op, unary_op, Assoc, Operation},
     state::{StackFrame, State},
-    Result,
+    warning::WarningCategory,
+    Mode, Result,
 };
 
 use super::MacroImplementation;
 
+/// `eval(expression, [radix], [width])` evaluates an integer expression with 32 bit signed
+/// arithmetic which wraps on overflow, like GNU m4. The result is written in `radix` (from 1 to 36,
+/// default 10) and padded with leading zeros to at least `width` digits.
 pub struct EvalMacro;
 
 impl MacroImplementation for EvalMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
-        let first_arg = frame
-            .args
-            .into_iter()
-            .next()
-            .ok_or_else(|| crate::Error::new(crate::ErrorKind::NotEnoughArguments))?;
-
-        let (_, output) = nom::combinator::all_consuming(nom::combinator::complete(
-            parse_and_evaluate,
-        ))(&first_arg)?;
-        state.input.pushback_string(output.to_string().as_bytes());
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut args = frame.args.into_iter();
+        // `eval()` has no arguments, which is the same as an empty expression.
+        let first_arg = args.next().unwrap_or_default();
+
+        let radix = match args.next() {
+            Some(radix) if !radix.is_empty() => match numeric_argument(&radix) {
+                Some(radix) => radix,
+                None => {
+                    state.warning_message(
+                        stderr,
+                        WarningCategory::Numeric,
+                        "non-numeric argument to builtin `eval'",
+                    )?;
+                    return Ok(state);
+                }
+            },
+            _ => 10,
+        };
+        if !(1..=36).contains(&radix) {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                format!("radix {radix} in builtin `eval' out of range"),
+            )?;
+            return Ok(state);
+        }
+        let width = match args.next() {
+            Some(width) => match numeric_argument(&width) {
+                Some(width) => width,
+                None => {
+                    state.warning_message(
+                        stderr,
+                        WarningCategory::Numeric,
+                        "non-numeric argument to builtin `eval'",
+                    )?;
+                    return Ok(state);
+                }
+            },
+            None => 0,
+        };
+        let Ok(width) = usize::try_from(width) else {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                "negative width to builtin `eval'",
+            )?;
+            return Ok(state);
+        };
+        // GNU m4 uses an `int` for the width. The padding is allocated up front, so a larger one
+        // is diagnosed rather than exhausting the memory.
+        if width > MAX_LENGTH {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                format!("width {width} in builtin `eval' out of range"),
+            )?;
+            return Ok(state);
+        }
+
+        let expression: &[u8] = if first_arg.iter().all(|c| is_whitespace(*c)) {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                "empty string treated as 0 in builtin `eval'",
+            )?;
+            b"0"
+        } else {
+            &first_arg
+        };
+
+        match Evaluator::new(state.mode, expression).evaluate() {
+            // In radix 1 the number of digits is the magnitude itself.
+            Ok(value) if radix == 1 && value.unsigned_abs() as usize > MAX_LENGTH => state
+                .warning_message(
+                    stderr,
+                    WarningCategory::Numeric,
+                    format!("result {value} in radix 1 in builtin `eval' is too long"),
+                )?,
+            Ok(value) => state
+                .input
+                .pushback_string(format_integer(value, radix as u32, width)),
+            Err(error) => state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                format_args!("{error}: {}", String::from_utf8_lossy(&first_arg)),
+            )?,
+        }
         Ok(state)
     }
 }
 
+/// The largest width, and number of digits in radix 1, of the result of [`EvalMacro`], the largest
+/// `int`.
+const MAX_LENGTH: usize = i32::MAX as usize;
+
+/// Parse a whole (optionally padded) macro argument as an integer.
+fn numeric_argument(arg: &[u8]) -> Option<i64> {
+    nom::combinator::all_consuming(padded(parse_integer))(arg)
+        .ok()
+        .map(|(_, i)| i)
+}
+
+/// Format `value` in `radix` (from 1 to 36), with at least `width` digits. Radix 1 writes the
+/// magnitude as a sequence of `1` digits.
+fn format_integer(value: i32, radix: u32, width: usize) -> Vec<u8> {
+    let mut magnitude = value.unsigned_abs();
+    let mut digits = Vec::new();
+    if radix == 1 {
+        digits.resize(magnitude as usize, b'1');
+    } else {
+        loop {
+            let digit = u8::try_from(magnitude % radix).expect("digit is below 36");
+            digits.push(match digit {
+                0..=9 => b'0' + digit,
+                _ => b'a' + digit - 10,
+            });
+            magnitude /= radix;
+            if magnitude == 0 {
+                break;
+            }
+        }
+        digits.reverse();
+    }
+
+    let mut output = Vec::with_capacity(digits.len().max(width) + 1);
+    if value < 0 {
+        output.push(b'-');
+    }
+    output.resize(output.len() + width.saturating_sub(digits.len()), b'0');
+    output.extend(digits);
+    output
+}
+
 /// A complete parser for a negative integer `[`[i64::MIN],[i64::MIN]`]`
 pub(crate) fn parse_integer(input: &[u8]) -> IResult<&[u8], i64> {
     let (remaining, negative) = nom::combinator::opt(nom::bytes::complete::tag(b"-"))(input)?;
@@ -69,16 +189,6 @@ pub(crate) fn parse_positive_integer(input: &[u8]) -> IResult<&[u8], i64> {
     Ok((remaining, number))
 }
 
-pub(crate) fn padded_tag(
-    t: &[u8],
-) -> impl for<'i> Fn(&'i [u8]) -> IResult<&'i [u8], &'i [u8]> + '_ {
-    move |input: &[u8]| {
-        let (remaining, (_, p, _)) =
-            tuple((take_while(is_whitespace), tag(t), take_while(is_whitespace)))(input)?;
-        Ok((remaining, p))
-    }
-}
-
 pub(crate) fn padded<'f, F, O>(f: F) -> impl for<'i> Fn(&'i [u8]) -> IResult<&'i [u8], O> + 'f
 where
     F: for<'i> Fn(&'i [u8]) -> IResult<&'i [u8], O> + 'f,
@@ -91,100 +201,405 @@ where
     }
 }
 
-/// `input` takes the bytes of characters that we want to parse and evaluate.
-/// The output is a result containing either Ok( ) or Err ( )
-///
-/// In the Ok ( ) case, it contains a tuple of (remaining, evaluated_output) of the eval.
-///
-/// For example:
-///
-/// ```ignore
-/// let (remaining, evaluated) = parse_and_evaluate(b"1+2").unwrap();
-/// assert_eq!(evaluated, 3);
-/// assert_eq!(remaining, &[]);
-/// ```
-pub fn parse_and_evaluate(input: &[u8]) -> IResult<&[u8], i64> {
-    if input.is_empty() {
-        return Err(nom::Err::Error(nom::error::Error::new(
+/// An error which stops the evaluation of an expression, it is diagnosed by [`EvalMacro`] along
+/// with the expression rather than being a fatal error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EvalError {
+    /// The expression is not valid, such as a missing operand.
+    BadExpression,
+    /// A valid expression is followed by something which is not an operator.
+    ExcessInput,
+    /// A `(` without a matching `)`.
+    MissingRightParenthesis,
+    /// The right hand side of `**` is negative.
+    NegativeExponent,
+    /// The right hand side of `/` is zero.
+    DivideByZero,
+    /// The right hand side of `%` is zero.
+    ModuloByZero,
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::BadExpression => f.write_str("bad expression in eval"),
+            EvalError::ExcessInput => f.write_str("bad expression in eval (excess input)"),
+            EvalError::MissingRightParenthesis => {
+                f.write_str("bad expression in eval (missing right parenthesis)")
+            }
+            EvalError::NegativeExponent => f.write_str("negative exponent in eval"),
+            EvalError::DivideByZero => f.write_str("divide by zero in eval"),
+            EvalError::ModuloByZero => f.write_str("modulo by zero in eval"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Assoc {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOperator {
+    Power,
+    Multiply,
+    Divide,
+    Modulo,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    LessEqual,
+    Less,
+    GreaterEqual,
+    Greater,
+    Equal,
+    NotEqual,
+    LogicalAnd,
+    LogicalOr,
+    BitwiseAnd,
+    BitwiseXor,
+    BitwiseOr,
+}
+
+/// The binary operators with their binding power and associativity, the same as in C with a
+/// higher binding power binding more tightly. Operators are matched in order, so an operator is
+/// listed before any operator which is a prefix of it (such as `**` before `*`).
+const BINARY_OPERATORS: &[(&[u8], BinaryOperator, u8, Assoc)] = &[
+    (b"**", BinaryOperator::Power, 11, Assoc::Right),
+    (b"*", BinaryOperator::Multiply, 10, Assoc::Left),
+    (b"/", BinaryOperator::Divide, 10, Assoc::Left),
+    (b"%", BinaryOperator::Modulo, 10, Assoc::Left),
+    (b"+", BinaryOperator::Add, 9, Assoc::Left),
+    (b"-", BinaryOperator::Subtract, 9, Assoc::Left),
+    (b"<<", BinaryOperator::ShiftLeft, 8, Assoc::Left),
+    (b">>", BinaryOperator::ShiftRight, 8, Assoc::Left),
+    (b"<=", BinaryOperator::LessEqual, 7, Assoc::Left),
+    (b"<", BinaryOperator::Less, 7, Assoc::Left),
+    (b">=", BinaryOperator::GreaterEqual, 7, Assoc::Left),
+    (b">", BinaryOperator::Greater, 7, Assoc::Left),
+    (b"==", BinaryOperator::Equal, 6, Assoc::Left),
+    (b"!=", BinaryOperator::NotEqual, 6, Assoc::Left),
+    (b"&&", BinaryOperator::LogicalAnd, 2, Assoc::Left),
+    (b"||", BinaryOperator::LogicalOr, 1, Assoc::Left),
+    (b"&", BinaryOperator::BitwiseAnd, 5, Assoc::Left),
+    (b"^", BinaryOperator::BitwiseXor, 4, Assoc::Left),
+    (b"|", BinaryOperator::BitwiseOr, 3, Assoc::Left),
+];
+
+/// The binding power of the prefix operators `-`, `+`, `~` and `!`, which bind more tightly than
+/// any of the [`BINARY_OPERATORS`].
+const PREFIX_POWER: u8 = 12;
+
+impl BinaryOperator {
+    /// Apply the operator, arithmetic wraps on overflow rather than panicking. A shift uses the
+    /// right hand side modulo 32.
+    fn apply(self, lhs: i32, rhs: i32) -> std::result::Result<i32, EvalError> {
+        Ok(match self {
+            BinaryOperator::Power => match u32::try_from(rhs) {
+                Ok(rhs) => power(lhs, rhs),
+                Err(_) => return Err(EvalError::NegativeExponent),
+            },
+            BinaryOperator::Multiply => lhs.wrapping_mul(rhs),
+            BinaryOperator::Divide if rhs == 0 => return Err(EvalError::DivideByZero),
+            BinaryOperator::Divide => lhs.wrapping_div(rhs),
+            BinaryOperator::Modulo if rhs == 0 => return Err(EvalError::ModuloByZero),
+            BinaryOperator::Modulo => lhs.wrapping_rem(rhs),
+            BinaryOperator::Add => lhs.wrapping_add(rhs),
+            BinaryOperator::Subtract => lhs.wrapping_sub(rhs),
+            BinaryOperator::ShiftLeft => lhs.wrapping_shl(rhs as u32),
+            BinaryOperator::ShiftRight => lhs.wrapping_shr(rhs as u32),
+            BinaryOperator::LessEqual => bool_to_int(lhs <= rhs),
+            BinaryOperator::Less => bool_to_int(lhs < rhs),
+            BinaryOperator::GreaterEqual => bool_to_int(lhs >= rhs),
+            BinaryOperator::Greater => bool_to_int(lhs > rhs),
+            BinaryOperator::Equal => bool_to_int(lhs == rhs),
+            BinaryOperator::NotEqual => bool_to_int(lhs != rhs),
+            BinaryOperator::LogicalAnd => bool_to_int(int_to_bool(lhs) && int_to_bool(rhs)),
+            BinaryOperator::LogicalOr => bool_to_int(int_to_bool(lhs) || int_to_bool(rhs)),
+            BinaryOperator::BitwiseAnd => lhs & rhs,
+            BinaryOperator::BitwiseXor => lhs ^ rhs,
+            BinaryOperator::BitwiseOr => lhs | rhs,
+        })
+    }
+}
+
+/// The start of an operand, see [`Evaluator::operand`].
+enum Operand {
+    Value(i32),
+    /// A prefix operator, which applies to the following operand.
+    Prefix(u8),
+    /// A `(`, the start of a parenthesized expression.
+    Parenthesis,
+}
+
+/// An operation which is waiting for the value of its (right hand side) operand, see
+/// [`Evaluator::expression`].
+enum Pending {
+    Prefix(u8),
+    Binary {
+        lhs: i32,
+        operator: BinaryOperator,
+        /// The minimum binding power of the operators in the right hand side.
+        rhs_power: u8,
+        /// The result is already decided by `lhs` (`0 && rhs` or `1 || rhs`), so like C the right
+        /// hand side is parsed but not evaluated, see [`Evaluator::expression`].
+        short_circuit: bool,
+    },
+    Parenthesis,
+}
+
+/// Parses and evaluates `eval` expressions in a single pass, using a Pratt (precedence climbing)
+/// parser driven by [`BINARY_OPERATORS`]. Each operation is evaluated as soon as it has been
+/// parsed, no syntax tree is built.
+pub(crate) struct Evaluator<'e> {
+    /// GNU mode adds the `**` operator.
+    mode: Mode,
+    input: &'e [u8],
+    /// The position of the next byte of `input` to be parsed.
+    position: usize,
+}
+
+impl<'e> Evaluator<'e> {
+    pub fn new(mode: Mode, input: &'e [u8]) -> Self {
+        Self {
+            mode,
             input,
-            nom::error::ErrorKind::NonEmpty,
-        )));
-    }
-    precedence::precedence(
-        // Unary prefix operators
-        alt((
-            unary_op(1, padded_tag(b"-")),
-            unary_op(1, padded_tag(b"+")),
-            unary_op(1, padded_tag(b"~")),
-            unary_op(1, padded_tag(b"!")),
-        )),
-        // No Unary postfix operators
-        fail,
-        // Binary operators
-        alt((
-            binary_op(2, Assoc::Left, padded_tag(b"*")),
-            binary_op(2, Assoc::Left, padded_tag(b"/")),
-            binary_op(2, Assoc::Left, padded_tag(b"%")),
-            binary_op(3, Assoc::Left, padded_tag(b"+")),
-            binary_op(3, Assoc::Left, padded_tag(b"-")),
-            binary_op(5, Assoc::Left, padded_tag(b"<<")),
-            binary_op(5, Assoc::Left, padded_tag(b">>")),
-            binary_op(5, Assoc::Left, padded_tag(b"<=")),
-            binary_op(5, Assoc::Left, padded_tag(b"<")),
-            binary_op(5, Assoc::Left, padded_tag(b">=")),
-            binary_op(5, Assoc::Left, padded_tag(b">")),
-            binary_op(6, Assoc::Left, padded_tag(b"==")),
-            binary_op(6, Assoc::Left, padded_tag(b"!=")),
-            // Moved && and || before & and | for parsing order.
-            binary_op(10, Assoc::Left, padded_tag(b"&&")),
-            binary_op(11, Assoc::Left, padded_tag(b"||")),
-            binary_op(7, Assoc::Left, padded_tag(b"&")),
-            binary_op(8, Assoc::Left, padded_tag(b"^")),
-            binary_op(9, Assoc::Left, padded_tag(b"|")),
-        )),
-        alt((
-            padded(parse_positive_integer),
-            delimited(padded_tag(b"("), parse_and_evaluate, padded_tag(b")")),
-        )),
-        |op: Operation<&[u8], &[u8], &[u8], i64>| match op {
-            Operation::Prefix(b"-", o) => Ok(-o),
-            Operation::Prefix(b"+", o) => Ok(o),
-            Operation::Prefix(b"~", o) => Ok(!o),
-            Operation::Prefix(b"!", o) => Ok(bool_to_int(!int_to_bool(o))),
-            Operation::Binary(lhs, b"*", rhs) => Ok(lhs * rhs),
-            Operation::Binary(lhs, b"/", rhs) => Ok(lhs / rhs),
-            Operation::Binary(lhs, b"%", rhs) => Ok(lhs % rhs),
-            Operation::Binary(lhs, b"<<", rhs) => Ok(lhs << rhs),
-            Operation::Binary(lhs, b">>", rhs) => Ok(lhs >> rhs),
-            Operation::Binary(lhs, b"+", rhs) => Ok(lhs + rhs),
-            Operation::Binary(lhs, b"-", rhs) => Ok(lhs - rhs),
-            Operation::Binary(lhs, b"<", rhs) => Ok(bool_to_int(lhs < rhs)),
-            Operation::Binary(lhs, b"<=", rhs) => Ok(bool_to_int(lhs <= rhs)),
-            Operation::Binary(lhs, b">", rhs) => Ok(bool_to_int(lhs > rhs)),
-            Operation::Binary(lhs, b">=", rhs) => Ok(bool_to_int(lhs >= rhs)),
-            Operation::Binary(lhs, b"==", rhs) => Ok(bool_to_int(lhs == rhs)),
-            Operation::Binary(lhs, b"!=", rhs) => Ok(bool_to_int(lhs != rhs)),
-            Operation::Binary(lhs, b"&", rhs) => Ok(lhs & rhs),
-            Operation::Binary(lhs, b"^", rhs) => Ok(lhs ^ rhs),
-            Operation::Binary(lhs, b"|", rhs) => Ok(lhs | rhs),
-            Operation::Binary(lhs, b"&&", rhs) => {
-                Ok(bool_to_int(int_to_bool(lhs) && int_to_bool(rhs)))
+            position: 0,
+        }
+    }
+
+    /// Evaluate the whole of the input.
+    ///
+    /// For example:
+    ///
+    /// ```ignore
+    /// assert_eq!(Evaluator::new(Mode::Gnu, b"1+2").evaluate(), Ok(3));
+    /// ```
+    pub fn evaluate(mut self) -> std::result::Result<i32, EvalError> {
+        let value = self.expression()?;
+        self.skip_whitespace();
+        if self.position < self.input.len() {
+            return Err(EvalError::ExcessInput);
+        }
+        Ok(value)
+    }
+
+    /// Parse and evaluate an expression.
+    ///
+    /// Rather than recursing for each operand the operations which are waiting for their right
+    /// hand side are kept in `pending`, so that the nesting depth (such as thousands of `(`) is not
+    /// limited by the native stack.
+    ///
+    /// Within the right hand side of a short circuit operation the operations are still applied,
+    /// but an error (such as division by zero) gives 0 instead, because the value is not used.
+    fn expression(&mut self) -> std::result::Result<i32, EvalError> {
+        let mut pending: Vec<Pending> = Vec::new();
+        // The number of short circuit operations in `pending`.
+        let mut short_circuits: usize = 0;
+        loop {
+            let mut value = match self.operand()? {
+                Operand::Value(value) => value,
+                Operand::Prefix(c) => {
+                    pending.push(Pending::Prefix(c));
+                    continue;
+                }
+                Operand::Parenthesis => {
+                    pending.push(Pending::Parenthesis);
+                    continue;
+                }
+            };
+            loop {
+                let min_power = match pending.last() {
+                    Some(Pending::Prefix(_)) => PREFIX_POWER,
+                    Some(Pending::Binary { rhs_power, .. }) => *rhs_power,
+                    Some(Pending::Parenthesis) | None => 0,
+                };
+                if let Some((len, operator, power, assoc)) = self.binary_operator() {
+                    if power >= min_power {
+                        self.position += len;
+                        let short_circuit = match operator {
+                            BinaryOperator::LogicalAnd => !int_to_bool(value),
+                            BinaryOperator::LogicalOr => int_to_bool(value),
+                            _ => false,
+                        };
+                        if short_circuit {
+                            short_circuits += 1;
+                        }
+                        pending.push(Pending::Binary {
+                            lhs: value,
+                            operator,
+                            rhs_power: match assoc {
+                                Assoc::Left => power + 1,
+                                Assoc::Right => power,
+                            },
+                            short_circuit,
+                        });
+                        break;
+                    }
+                }
+                value = match pending.pop() {
+                    None => return Ok(value),
+                    Some(Pending::Prefix(c)) => match c {
+                        b'-' => value.wrapping_neg(),
+                        b'+' => value,
+                        b'~' => !value,
+                        _ => bool_to_int(!int_to_bool(value)),
+                    },
+                    Some(Pending::Binary {
+                        operator,
+                        short_circuit: true,
+                        ..
+                    }) => {
+                        short_circuits -= 1;
+                        bool_to_int(operator == BinaryOperator::LogicalOr)
+                    }
+                    Some(Pending::Binary { lhs, operator, .. }) if short_circuits > 0 => {
+                        operator.apply(lhs, value).unwrap_or(0)
+                    }
+                    Some(Pending::Binary { lhs, operator, .. }) => operator.apply(lhs, value)?,
+                    Some(Pending::Parenthesis) => {
+                        self.skip_whitespace();
+                        if self.input.get(self.position) != Some(&b')') {
+                            return Err(EvalError::MissingRightParenthesis);
+                        }
+                        self.position += 1;
+                        value
+                    }
+                };
+            }
+        }
+    }
+
+    /// Parse the start of an operand: a literal, or the prefix operator or `(` which it starts
+    /// with.
+    fn operand(&mut self) -> std::result::Result<Operand, EvalError> {
+        self.skip_whitespace();
+        let Some(&c) = self.input.get(self.position) else {
+            return Err(EvalError::BadExpression);
+        };
+        match c {
+            b'-' | b'+' | b'~' | b'!' => {
+                self.position += 1;
+                Ok(Operand::Prefix(c))
+            }
+            b'(' => {
+                self.position += 1;
+                Ok(Operand::Parenthesis)
             }
-            Operation::Binary(lhs, b"||", rhs) => {
-                Ok(bool_to_int(int_to_bool(lhs) || int_to_bool(rhs)))
+            _ => {
+                let input = &self.input[self.position..];
+                let (remaining, value) = self
+                    .parse_literal(input)
+                    .map_err(|_| EvalError::BadExpression)?;
+                self.position += input.len() - remaining.len();
+                Ok(Operand::Value(value))
             }
-            _ => Err("Invalid combination"),
-        },
-    )(input)
+        }
+    }
+
+    /// The binary operator at the current position (after any whitespace), along with its length
+    /// and its entry in [`BINARY_OPERATORS`].
+    fn binary_operator(&mut self) -> Option<(usize, BinaryOperator, u8, Assoc)> {
+        self.skip_whitespace();
+        let rest = &self.input[self.position..];
+        BINARY_OPERATORS
+            .iter()
+            // `**` is a GNU extension, otherwise it is parsed as `*` followed by `*`.
+            .filter(|(_, operator, ..)| {
+                self.mode == Mode::Gnu || *operator != BinaryOperator::Power
+            })
+            .find(|(token, ..)| rest.starts_with(token))
+            .map(|&(token, operator, power, assoc)| (token.len(), operator, power, assoc))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.position)
+            .is_some_and(|&c| is_whitespace(c))
+        {
+            self.position += 1;
+        }
+    }
+
+    /// Parse an integer literal: decimal, octal with a leading `0`, or hexadecimal with a `0x`
+    /// prefix. GNU mode also supports binary with a `0b` prefix and `0rRADIX:DIGITS` for a radix
+    /// from 1 to 36. Digits beyond 9 are letters in either case, and values wrap on overflow.
+    fn parse_literal<'i>(&self, input: &'i [u8]) -> IResult<&'i [u8], i32> {
+        let error = |i| nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Digit));
+        let gnu = self.mode == Mode::Gnu;
+        let (radix, digits) = match input {
+            [b'0', b'x' | b'X', rest @ ..] => (16, rest),
+            [b'0', b'b' | b'B', rest @ ..] if gnu => (2, rest),
+            [b'0', b'r' | b'R', rest @ ..] if gnu => {
+                let (rest, radix) = parse_positive_integer(rest)?;
+                let rest = rest.strip_prefix(b":").ok_or_else(|| error(rest))?;
+                if !(1..=36).contains(&radix) {
+                    return Err(error(input));
+                }
+                (radix as u32, rest)
+            }
+            [b'0', rest @ ..] => {
+                let len = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+                if rest[..len].iter().any(|c| !(b'0'..=b'7').contains(c)) {
+                    return Err(error(input));
+                }
+                (8, rest)
+            }
+            [c, ..] if c.is_ascii_digit() => (10, input),
+            _ => return Err(error(input)),
+        };
+
+        let len = digits
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .count();
+        let (digits, remaining) = digits.split_at(len);
+        if digits.is_empty() && radix != 8 {
+            return Err(error(input));
+        }
+        let mut value: i32 = 0;
+        for c in digits {
+            let digit = match radix {
+                // In radix 1 the value is the number of `1` digits.
+                1 if *c == b'1' => 1,
+                1 => return Err(error(input)),
+                _ => match char::from(*c).to_digit(radix) {
+                    Some(digit) => digit as i32,
+                    None => return Err(error(input)),
+                },
+            };
+            value = match radix {
+                1 => value.wrapping_add(digit),
+                _ => value.wrapping_mul(radix as i32).wrapping_add(digit),
+            };
+        }
+        Ok((remaining, value))
+    }
+}
+
+/// `base` to the power of `exponent` by repeated squaring, wrapping on overflow.
+fn power(mut base: i32, mut exponent: u32) -> i32 {
+    let mut result: i32 = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
 }
 
-fn bool_to_int(b: bool) -> i64 {
+fn bool_to_int(b: bool) -> i32 {
     match b {
         true => 1,
         false => 0,
     }
 }
 
-fn int_to_bool(i: i64) -> bool {
+fn int_to_bool(i: i32) -> bool {
     i != 0
 }
@@ -0,0 +1,592 @@
+use std::io::Write;
+
+use crate::{
+    lexer::is_space,
+    state::{StackFrame, State},
+    warning::WarningCategory,
+    Result,
+};
+
+use super::MacroImplementation;
+
+/// GNU extension. `format(format-string, ...)` works like `printf(3)`, the defining text is the
+/// first argument with each conversion specification replaced by the formatted value of the next
+/// argument.
+///
+/// The supported conversions are `%c`, `%s`, the integer conversions `%d`, `%i`, `%o`, `%u`,
+/// `%x` and `%X` and the floating point conversions `%e`, `%E`, `%f`, `%F`, `%g` and `%G`, along
+/// with the flags `-`, `+`, space, `0` and `#`, and a width and precision which can be given by an
+/// argument using `*`. Like GNU m4 the `'` flag and any length modifiers are accepted and ignored.
+///
+/// A missing argument is 0 or null, and numeric arguments use as much of the start of the argument
+/// as is a number, like `atoi(3)` and `atof(3)`. Like GNU m4 both are diagnosed, as is an integer
+/// which does not fit an `int`. An unrecognized specification is diagnosed and removed.
+pub struct FormatMacro;
+
+impl MacroImplementation for FormatMacro {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
+        let mut args = frame.args.into_iter();
+        let format = args.next().unwrap_or_default();
+        let mut args = Arguments {
+            args,
+            exhausted: false,
+        };
+
+        let mut output = Vec::with_capacity(format.len());
+        let mut i = 0;
+        while let Some(&c) = format.get(i) {
+            i += 1;
+            if c != b'%' {
+                output.push(c);
+                continue;
+            }
+            let spec = Specification::parse(&format, &mut i, &mut args, &mut state, stderr)?;
+            if spec.out_of_range {
+                // The argument is still consumed, so that the following conversions use theirs.
+                if !matches!(spec.conversion, None | Some(b'%')) {
+                    args.string(&mut state, stderr)?;
+                }
+                state.warning(
+                    stderr,
+                    WarningCategory::Numeric,
+                    format_args!(
+                        "width or precision out of range in `{}'",
+                        String::from_utf8_lossy(&format)
+                    ),
+                )?;
+                continue;
+            }
+            match spec.conversion {
+                Some(b'%') => output.push(b'%'),
+                Some(b'c') => {
+                    let c = args.int(&mut state, stderr)? as u8;
+                    spec.pad(&[], &[c], &mut output)
+                }
+                Some(b's') => {
+                    let s = args.string(&mut state, stderr)?;
+                    let s = match spec.precision {
+                        Some(precision) if precision < s.len() => &s[..precision],
+                        _ => &s[..],
+                    };
+                    spec.pad(&[], s, &mut output);
+                }
+                Some(conversion @ (b'd' | b'i' | b'o' | b'u' | b'x' | b'X')) => {
+                    let value = args.int(&mut state, stderr)? as i32;
+                    spec.format_integer(conversion, value, &mut output)
+                }
+                Some(conversion @ (b'e' | b'E' | b'f' | b'F' | b'g' | b'G')) => {
+                    let value = args.float(&mut state, stderr)?;
+                    spec.format_float(conversion, value, &mut output)
+                }
+                _ => state.warning(
+                    stderr,
+                    WarningCategory::Other,
+                    format_args!(
+                        "unrecognized specifier in `{}'",
+                        String::from_utf8_lossy(&format)
+                    ),
+                )?,
+            }
+        }
+
+        state.input.pushback_string(output);
+        Ok(state)
+    }
+}
+
+/// The arguments following the format string, consumed by each conversion.
+struct Arguments {
+    args: <crate::state::Arguments as IntoIterator>::IntoIter,
+    /// Whether running out of arguments has been diagnosed, which is only done once.
+    exhausted: bool,
+}
+
+impl Arguments {
+    /// The next argument, or `None` if there are no more, which is diagnosed.
+    fn next(&mut self, state: &mut State, stderr: &mut dyn Write) -> Result<Option<Vec<u8>>> {
+        let arg = self.args.next();
+        if arg.is_none() && !self.exhausted {
+            self.exhausted = true;
+            state.retrievable_warning(
+                stderr,
+                WarningCategory::TooFewArguments,
+                "too few arguments to builtin `format'",
+            )?;
+        }
+        Ok(arg)
+    }
+
+    fn string(&mut self, state: &mut State, stderr: &mut dyn Write) -> Result<Vec<u8>> {
+        Ok(self.next(state, stderr)?.unwrap_or_default())
+    }
+
+    /// The leading decimal integer of the next argument, like `strtol(3)`. It is diagnosed if it
+    /// does not fit an `int`, then the caller wraps it to fit like GNU m4.
+    fn int(&mut self, state: &mut State, stderr: &mut dyn Write) -> Result<i64> {
+        let Some(arg) = self.next(state, stderr)? else {
+            return Ok(0);
+        };
+        let start = arg.iter().position(|c| !is_space(*c)).unwrap_or(arg.len());
+        let (negative, digits) = match arg[start..].split_first() {
+            Some((b'-', rest)) => (true, rest),
+            Some((b'+', rest)) => (false, rest),
+            _ => (false, &arg[start..]),
+        };
+        let length = digits.iter().take_while(|c| c.is_ascii_digit()).count();
+        let value = digits[..length].iter().fold(0i64, |value, c| {
+            let digit = i64::from(c - b'0');
+            if negative {
+                value.saturating_mul(10).saturating_sub(digit)
+            } else {
+                value.saturating_mul(10).saturating_add(digit)
+            }
+        });
+        let numeric = length > 0 && length == digits.len();
+        diagnose_number(state, stderr, &arg, start, numeric)?;
+        if numeric && i32::try_from(value).is_err() {
+            state.warning_message(
+                stderr,
+                WarningCategory::Numeric,
+                "numeric overflow detected in builtin `format'",
+            )?;
+        }
+        Ok(value)
+    }
+
+    /// The leading floating point number of the next argument, like `atof(3)`, including
+    /// hexadecimal numbers such as `0xa.8p1`, `inf` and `nan`.
+    fn float(&mut self, state: &mut State, stderr: &mut dyn Write) -> Result<f64> {
+        let Some(arg) = self.next(state, stderr)? else {
+            return Ok(0.0);
+        };
+        let start = arg.iter().position(|c| !is_space(*c)).unwrap_or(arg.len());
+        let (value, length) = parse_float(&arg[start..]);
+        let numeric = length > 0 && start + length == arg.len();
+        diagnose_number(state, stderr, &arg, start, numeric)?;
+        Ok(value)
+    }
+}
+
+/// Diagnose the numeric argument `arg` like GNU m4, it is `numeric` if all of it after the leading
+/// whitespace (before `start`) is a number.
+fn diagnose_number(
+    state: &mut State,
+    stderr: &mut dyn Write,
+    arg: &[u8],
+    start: usize,
+    numeric: bool,
+) -> Result<()> {
+    let message = if arg.is_empty() {
+        "empty string treated as 0 in builtin `format'"
+    } else if !numeric {
+        "non-numeric argument to builtin `format'"
+    } else if start > 0 {
+        "leading whitespace ignored in builtin `format'"
+    } else {
+        return Ok(());
+    };
+    state.warning_message(stderr, WarningCategory::Numeric, message)
+}
+
+/// The value of the longest prefix of `s` which is a floating point number, and its length.
+fn parse_float(s: &[u8]) -> (f64, usize) {
+    let (negative, sign) = match s.first() {
+        Some(b'-') => (true, 1),
+        Some(b'+') => (false, 1),
+        _ => (false, 0),
+    };
+    let unsigned = &s[sign..];
+    let lower = unsigned.to_ascii_lowercase();
+    let (magnitude, length) = if lower.starts_with(b"infinity") {
+        (f64::INFINITY, 8)
+    } else if lower.starts_with(b"inf") {
+        (f64::INFINITY, 3)
+    } else if lower.starts_with(b"nan") {
+        (f64::NAN, 3)
+    } else if lower.starts_with(b"0x") {
+        match parse_hex_float(&lower[2..]) {
+            (value, 0) => (value, 1),
+            (value, length) => (value, 2 + length),
+        }
+    } else {
+        parse_decimal_float(unsigned)
+    };
+    if length == 0 {
+        return (0.0, 0);
+    }
+    let value = if negative { -magnitude } else { magnitude };
+    (value, sign + length)
+}
+
+/// The value of the longest prefix of `s` which is a decimal floating point number, and its length.
+fn parse_decimal_float(s: &[u8]) -> (f64, usize) {
+    let digits = |i: usize| s[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let mut end = digits(0);
+    let mut mantissa_digits = end;
+    if s.get(end) == Some(&b'.') {
+        let fraction_digits = digits(end + 1);
+        mantissa_digits += fraction_digits;
+        end += 1 + fraction_digits;
+    }
+    if mantissa_digits == 0 {
+        return (0.0, 0);
+    }
+    if matches!(s.get(end), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(s.get(end + 1), Some(b'+' | b'-')));
+        let exponent_digits = digits(end + 1 + sign);
+        if exponent_digits > 0 {
+            end += 1 + sign + exponent_digits;
+        }
+    }
+    let value = std::str::from_utf8(&s[..end])
+        .ok()
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(0.0);
+    (value, end)
+}
+
+/// The value of the longest prefix of `s` (lowercase, after the `0x`) which is a hexadecimal
+/// floating point number, `hex-digits[.hex-digits][p[sign]digits]`, and its length (0 if it has no
+/// digits).
+fn parse_hex_float(s: &[u8]) -> (f64, usize) {
+    let mut value = 0.0;
+    let mut scale = 0i32;
+    let mut i = 0;
+    let mut fraction = false;
+    let mut any_digits = false;
+    while let Some(&c) = s.get(i) {
+        if c == b'.' && !fraction {
+            fraction = true;
+        } else if let Some(digit) = char::from(c).to_digit(16) {
+            any_digits = true;
+            value = value * 16.0 + f64::from(digit);
+            if fraction {
+                scale -= 4;
+            }
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    if !any_digits {
+        return (0.0, 0);
+    }
+    if s.get(i) == Some(&b'p') {
+        let (negative, start) = match s.get(i + 1) {
+            Some(b'-') => (true, i + 2),
+            Some(b'+') => (false, i + 2),
+            _ => (false, i + 1),
+        };
+        let exponent_digits = s[start..].iter().take_while(|c| c.is_ascii_digit()).count();
+        let exponent = s[start..start + exponent_digits]
+            .iter()
+            .fold(0i32, |exponent, c| {
+                exponent
+                    .saturating_mul(10)
+                    .saturating_add(i32::from(c - b'0'))
+            });
+        if exponent_digits > 0 {
+            scale = if negative {
+                scale.saturating_sub(exponent)
+            } else {
+                scale.saturating_add(exponent)
+            };
+            i = start + exponent_digits;
+        }
+    }
+    (value * 2f64.powi(scale), i)
+}
+
+/// A conversion specification, `%[flags][width][.precision][length]conversion`.
+#[derive(Default)]
+struct Specification {
+    /// `-`: left justify within the width.
+    left: bool,
+    /// `+`: always include a sign.
+    plus: bool,
+    /// ` `: include a space in place of a `+` sign.
+    space: bool,
+    /// `0`: pad numbers with zeros rather than spaces.
+    zero: bool,
+    /// `#`: the alternate form.
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    /// The width or precision, in the format string or an argument, is larger than
+    /// [`MAX_WIDTH`], the specification is diagnosed rather than formatted.
+    out_of_range: bool,
+    /// `None` at the end of the format string.
+    conversion: Option<u8>,
+}
+
+/// The largest width or precision, the same as the limit of `printf(3)` which uses an `int`.
+const MAX_WIDTH: usize = i32::MAX as usize;
+
+/// The number of decimal places (or significant digits) beyond which the decimal expansion of
+/// any `f64` only has zeros, larger precisions are formatted with this many and padded with
+/// zeros. [`std::fmt`] does not support precisions beyond [`u16::MAX`].
+const MAX_FLOAT_DIGITS: usize = 1100;
+
+impl Specification {
+    /// Parse the specification in `format` starting at `i` (just after the `%`), leaving `i` just
+    /// after it. A width or precision of `*` uses the next of the `args`.
+    fn parse(
+        format: &[u8],
+        i: &mut usize,
+        args: &mut Arguments,
+        state: &mut State,
+        stderr: &mut dyn Write,
+    ) -> Result<Self> {
+        let mut spec = Self::default();
+        loop {
+            match format.get(*i) {
+                Some(b'-') => spec.left = true,
+                Some(b'+') => spec.plus = true,
+                Some(b' ') => spec.space = true,
+                Some(b'0') => spec.zero = true,
+                Some(b'#') => spec.alternate = true,
+                Some(b'\'') => {}
+                _ => break,
+            }
+            *i += 1;
+        }
+
+        if format.get(*i) == Some(&b'*') {
+            *i += 1;
+            let width = args.int(state, stderr)?;
+            // A negative width is a `-` flag and a positive width.
+            spec.left |= width < 0;
+            spec.width = spec.limit(width.unsigned_abs());
+        } else {
+            spec.width = parse_decimal(format, i, &mut spec.out_of_range);
+        }
+
+        if format.get(*i) == Some(&b'.') {
+            *i += 1;
+            if format.get(*i) == Some(&b'*') {
+                *i += 1;
+                // A negative precision is the same as if it was omitted.
+                let precision = args.int(state, stderr)?;
+                spec.precision = u64::try_from(precision).ok().map(|p| spec.limit(p));
+            } else {
+                spec.precision = Some(parse_decimal(format, i, &mut spec.out_of_range));
+            }
+        }
+
+        while matches!(
+            format.get(*i),
+            Some(b'h' | b'l' | b'L' | b'q' | b'j' | b'z' | b't')
+        ) {
+            *i += 1;
+        }
+
+        spec.conversion = format.get(*i).copied();
+        if spec.conversion.is_some() {
+            *i += 1;
+        }
+        Ok(spec)
+    }
+
+    /// A width or precision argument of `value`, limited to [`MAX_WIDTH`] the same as one in the
+    /// format string.
+    fn limit(&mut self, value: u64) -> usize {
+        if value > MAX_WIDTH as u64 {
+            self.out_of_range = true;
+        }
+        value.min(MAX_WIDTH as u64) as usize
+    }
+
+    /// Append `prefix` (a sign or `0x`) and `body` to `output`, padded to the width with spaces,
+    /// or with zeros between the prefix and the body when `zero_pad`.
+    fn pad_number(&self, prefix: &[u8], body: &[u8], zero_pad: bool, output: &mut Vec<u8>) {
+        let padding = self.width.saturating_sub(prefix.len() + body.len());
+        if self.left {
+            output.extend(prefix);
+            output.extend(body);
+            output.extend(std::iter::repeat_n(b' ', padding));
+        } else if zero_pad {
+            output.extend(prefix);
+            output.extend(std::iter::repeat_n(b'0', padding));
+            output.extend(body);
+        } else {
+            output.extend(std::iter::repeat_n(b' ', padding));
+            output.extend(prefix);
+            output.extend(body);
+        }
+    }
+
+    fn pad(&self, prefix: &[u8], body: &[u8], output: &mut Vec<u8>) {
+        self.pad_number(prefix, body, false, output);
+    }
+
+    fn sign(&self, negative: bool) -> &'static [u8] {
+        if negative {
+            b"-"
+        } else if self.plus {
+            b"+"
+        } else if self.space {
+            b" "
+        } else {
+            b""
+        }
+    }
+
+    fn format_integer(&self, conversion: u8, value: i32, output: &mut Vec<u8>) {
+        let (negative, magnitude) = match conversion {
+            b'd' | b'i' => (value < 0, u64::from(value.unsigned_abs())),
+            // The unsigned conversions reinterpret the `int` as an `unsigned int`.
+            _ => (false, u64::from(value as u32)),
+        };
+        let mut digits = match conversion {
+            b'o' => format!("{magnitude:o}"),
+            b'x' => format!("{magnitude:x}"),
+            b'X' => format!("{magnitude:X}"),
+            _ => magnitude.to_string(),
+        }
+        .into_bytes();
+        match self.precision {
+            // A zero precision with a zero value has no digits.
+            Some(0) if magnitude == 0 => digits.clear(),
+            Some(precision) if precision > digits.len() => {
+                let zeros = precision.min(MAX_WIDTH) - digits.len();
+                digits.splice(0..0, std::iter::repeat_n(b'0', zeros));
+            }
+            _ => {}
+        }
+
+        let prefix: &[u8] = match conversion {
+            b'd' | b'i' => self.sign(negative),
+            b'o' if self.alternate && digits.first() != Some(&b'0') => {
+                digits.insert(0, b'0');
+                b""
+            }
+            b'x' if self.alternate && magnitude != 0 => b"0x",
+            b'X' if self.alternate && magnitude != 0 => b"0X",
+            _ => b"",
+        };
+        // With a precision the `0` flag is ignored.
+        let zero_pad = self.zero && self.precision.is_none();
+        self.pad_number(prefix, &digits, zero_pad, output);
+    }
+
+    fn format_float(&self, conversion: u8, value: f64, output: &mut Vec<u8>) {
+        let upper = conversion.is_ascii_uppercase();
+        let sign = self.sign(value.is_sign_negative() && !value.is_nan());
+        if !value.is_finite() {
+            let body: &[u8] = match (value.is_nan(), upper) {
+                (true, false) => b"nan",
+                (true, true) => b"NAN",
+                (false, false) => b"inf",
+                (false, true) => b"INF",
+            };
+            // Infinity and NaN are never padded with zeros.
+            self.pad(sign, body, output);
+            return;
+        }
+
+        let magnitude = value.abs();
+        let precision = self.precision.unwrap_or(6).min(MAX_WIDTH);
+        let mut body = match conversion.to_ascii_lowercase() {
+            b'f' => self.fixed(magnitude, precision),
+            b'e' => self.exponential(magnitude, precision),
+            _ => {
+                // `%g` uses the style of `%e` if the exponent is less than -4 or not less than
+                // the precision, which is the number of significant digits.
+                let significant = precision.max(1);
+                let exponent = exponent_of(magnitude, significant - 1);
+                let mut body = if exponent < -4 || exponent >= significant as i64 {
+                    self.exponential(magnitude, significant - 1)
+                } else {
+                    let decimals = (significant as i64 - 1 - exponent) as usize;
+                    self.fixed(magnitude, decimals)
+                };
+                if !self.alternate {
+                    strip_trailing_zeros(&mut body);
+                }
+                body
+            }
+        };
+        if upper {
+            body.make_ascii_uppercase();
+        }
+        self.pad_number(sign, &body, self.zero, output);
+    }
+
+    /// `magnitude` in the style `ddd.ddd` with `precision` decimal places.
+    fn fixed(&self, magnitude: f64, precision: usize) -> Vec<u8> {
+        let digits = precision.min(MAX_FLOAT_DIGITS);
+        let mut body = format!("{magnitude:.digits$}").into_bytes();
+        body.resize(body.len() + (precision - digits), b'0');
+        if precision == 0 && self.alternate {
+            body.push(b'.');
+        }
+        body
+    }
+
+    /// `magnitude` in the style `d.ddde+dd` with `precision` decimal places.
+    fn exponential(&self, magnitude: f64, precision: usize) -> Vec<u8> {
+        let digits = precision.min(MAX_FLOAT_DIGITS);
+        let formatted = format!("{magnitude:.digits$e}");
+        let (mantissa, exponent) = formatted
+            .split_once('e')
+            .expect("exponential format has an exponent");
+        let exponent: i64 = exponent.parse().expect("exponent is an integer");
+        let mut body = mantissa.as_bytes().to_vec();
+        body.resize(body.len() + (precision - digits), b'0');
+        if precision == 0 && self.alternate {
+            body.push(b'.');
+        }
+        let sign = if exponent < 0 { '-' } else { '+' };
+        body.extend(format!("e{sign}{:02}", exponent.unsigned_abs()).as_bytes());
+        body
+    }
+}
+
+/// The decimal exponent of `magnitude` once rounded to `precision` decimal places in the
+/// exponential style.
+fn exponent_of(magnitude: f64, precision: usize) -> i64 {
+    let precision = precision.min(MAX_FLOAT_DIGITS);
+    let formatted = format!("{magnitude:.precision$e}");
+    formatted
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Remove trailing zeros from the fractional part of `body`, and the decimal point if nothing is
+/// left after it, without changing any exponent.
+fn strip_trailing_zeros(body: &mut Vec<u8>) {
+    let Some(point) = body.iter().position(|c| *c == b'.') else {
+        return;
+    };
+    let exponent_start = body.iter().position(|c| *c == b'e').unwrap_or(body.len());
+    let mut end = exponent_start;
+    while end > point + 1 && body[end - 1] == b'0' {
+        end -= 1;
+    }
+    if end == point + 1 {
+        end = point;
+    }
+    body.drain(end..exponent_start);
+}
+
+/// Parse the decimal digits in `format` starting at `i`, leaving `i` after them. A value larger
+/// than [`MAX_WIDTH`] sets `out_of_range`.
+fn parse_decimal(format: &[u8], i: &mut usize, out_of_range: &mut bool) -> usize {
+    let mut value: usize = 0;
+    while let Some(c) = format.get(*i).filter(|c| c.is_ascii_digit()) {
+        value = value
+            .saturating_mul(10)
+            .saturating_add(usize::from(c - b'0'));
+        *i += 1;
+    }
+    if value > MAX_WIDTH {
+        *out_of_range = true;
+    }
+    value.min(MAX_WIDTH)
+}
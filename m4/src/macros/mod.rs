@@ -1,19 +1,21 @@
 mod builtin;
 mod eval;
+mod format;
 pub mod trace;
 mod user_defined;
 
-use std::io::Write;
+use std::{io::Write, rc::Rc};
 
 use builtin::*;
 use eval::EvalMacro;
-use trace::{TraceoffMacro, TraceonMacro};
+use format::FormatMacro;
+use trace::{TraceCall, TraceoffMacro, TraceonMacro};
 use user_defined::UserDefinedMacro;
 
 use crate::{
     lexer::{MacroName, MacroParseConfig},
     state::{StackFrame, State},
-    Result,
+    Mode, Result,
 };
 
 macro_rules! macro_enums {
@@ -34,16 +36,66 @@ macro_rules! macro_enums {
         }
 
         impl MacroImplementation for MacroDefinitionImplementation {
+            /// `indir` and `builtin` call another macro, which is done in a loop here rather than
+            /// recursively so that a long chain of them can not overflow the native stack. The traces
+            /// of the calls are completed in reverse order once the final macro has been evaluated.
             fn evaluate(
                 &self,
-                state: State,
+                mut state: State,
                 stderr: &mut dyn Write,
-                f: StackFrame,
+                mut f: StackFrame,
             ) -> Result<State> {
-                state.trace.trace(&state.output.stack, &f, stderr)?;
+                let mut traces = Vec::new();
+                let mut next_definition: Option<Rc<MacroDefinition>> = None;
+                loop {
+                    let definition = next_definition.take();
+                    let implementation = definition.as_ref().map_or(self, |d| &d.implementation);
+                    if state.safer
+                        && implementation.builtin().is_some_and(|builtin| builtin.is_unsafe(&f.args))
+                    {
+                        state.error(
+                            stderr,
+                            "safer",
+                            format!(
+                                "{}: disabled by --safer",
+                                f.definition.parse_config.name
+                            ),
+                        )?;
+                        break;
+                    }
+                    traces.push(TraceCall::pre(&state, &f, stderr)?);
+                    let next = match implementation.builtin() {
+                        Some(BuiltinMacro::Indir) => IndirMacro::frame(&mut state, stderr, f)?,
+                        Some(BuiltinMacro::Builtin) => {
+                            BuiltinMacroMacro::frame(&mut state, stderr, f)?
+                        }
+                        _ => {
+                            state = match implementation {
+                                $(Self::$variant_name(d) => d.evaluate(state, stderr, f)),*,
+                                Self::UserDefined(d) => d.evaluate(state, stderr, f),
+                            }?;
+                            None
+                        }
+                    };
+                    let Some(next) = next else {
+                        break;
+                    };
+                    next_definition = Some(next.definition.clone());
+                    f = next;
+                }
+                for trace in traces.into_iter().rev().flatten() {
+                    trace.post(&state, stderr)?;
+                }
+                Ok(state)
+            }
+        }
+
+        impl MacroDefinitionImplementation {
+            /// The builtin macro that this implements, or `None` if it is user defined.
+            pub fn builtin(&self) -> Option<BuiltinMacro> {
                 match self {
-                    $(Self::$variant_name(d) => d.evaluate(state, stderr, f)),*,
-                    Self::UserDefined(d) => d.evaluate(state, stderr, f),
+                    $(Self::$variant_name(_) => Some(BuiltinMacro::$variant_name)),*,
+                    Self::UserDefined(_) => None,
                 }
             }
         }
@@ -62,12 +114,15 @@ macro_rules! macro_enums {
     };
 }
 
-// Maketemp intentionally left out, it's obsolete apparently.
 macro_enums!(
     #[derive(Clone, Copy)]
     pub enum BuiltinMacroDefinition {
+        Builtin(BuiltinMacroMacro),
         Changecom(ChangecomMacro),
         Changequote(ChangequoteMacro),
+        Changeword(ChangewordMacro),
+        Debugfile(DebugfileMacro),
+        Debugmode(DebugmodeMacro),
         Decr(DecrMacro),
         Define(DefineMacro),
         Defn(DefnMacro),
@@ -76,20 +131,28 @@ macro_enums!(
         Dnl(DnlMacro),
         Dumpdef(DumpdefMacro),
         Errprint(ErrprintMacro),
+        Esyscmd(EsyscmdMacro),
         Eval(EvalMacro),
         File(FileMacro),
+        Format(FormatMacro),
+        Gnu(PlatformMacro),
         Ifdef(IfdefMacro),
         Ifelse(IfelseMacro),
         Include(IncludeMacro),
         Incr(IncrMacro),
         Index(IndexMacro),
+        Indir(IndirMacro),
         Len(LenMacro),
+        Line(LineMacro),
         M4exit(M4exitMacro),
         M4wrap(M4wrapMacro),
-        Maketemp(MkstempMacro),
+        Maketemp(MaketempMacro),
         Mkstemp(MkstempMacro),
+        Patsubst(PatsubstMacro),
         Popdef(PopdefMacro),
+        Program(ProgramMacro),
         Pushdef(PushdefMacro),
+        Regexp(RegexpMacro),
         Shift(ShiftMacro),
         Sinclude(SincludeMacro),
         Substr(SubstrMacro),
@@ -100,6 +163,8 @@ macro_enums!(
         Translit(TranslitMacro),
         Undefine(UndefineMacro),
         Undivert(UndivertMacro),
+        Unix(PlatformMacro),
+        UnixTraditional(PlatformMacro),
     }
 );
 
@@ -107,8 +172,12 @@ impl AsRef<[u8]> for BuiltinMacro {
     fn as_ref(&self) -> &'static [u8] {
         use BuiltinMacro::*;
         match self {
+            Builtin => b"builtin",
             Changecom => b"changecom",
             Changequote => b"changequote",
+            Changeword => b"changeword",
+            Debugfile => b"debugfile",
+            Debugmode => b"debugmode",
             Decr => b"decr",
             Define => b"define",
             Defn => b"defn",
@@ -117,20 +186,28 @@ impl AsRef<[u8]> for BuiltinMacro {
             Dnl => b"dnl",
             Dumpdef => b"dumpdef",
             Errprint => b"errprint",
+            Esyscmd => b"esyscmd",
             Eval => b"eval",
             File => b"__file__",
+            Format => b"format",
+            Gnu => b"__gnu__",
             Ifdef => b"ifdef",
             Ifelse => b"ifelse",
             Include => b"include",
             Incr => b"incr",
             Index => b"index",
+            Indir => b"indir",
             Len => b"len",
+            Line => b"__line__",
             M4exit => b"m4exit",
             M4wrap => b"m4wrap",
             Maketemp => b"maketemp",
             Mkstemp => b"mkstemp",
+            Patsubst => b"patsubst",
             Popdef => b"popdef",
+            Program => b"__program__",
             Pushdef => b"pushdef",
+            Regexp => b"regexp",
             Shift => b"shift",
             Sinclude => b"sinclude",
             Substr => b"substr",
@@ -141,6 +218,8 @@ impl AsRef<[u8]> for BuiltinMacro {
             Translit => b"translit",
             Undefine => b"undefine",
             Undivert => b"undivert",
+            Unix => b"__unix__",
+            UnixTraditional => b"unix",
         }
     }
 }
@@ -150,13 +229,84 @@ impl BuiltinMacro {
         MacroName::try_from_slice(self.as_ref()).expect("Expected valid builtin macro name")
     }
 
+    /// Look up the builtin macro with the specified `name`.
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::enumerate()
+            .iter()
+            .find(|builtin| builtin.as_ref() == name)
+            .copied()
+    }
+
+    /// The builtin token which `defn` expands to for this builtin, see [`BUILTIN_TOKEN_START`].
+    pub fn token(&self) -> Vec<u8> {
+        let mut token = vec![BUILTIN_TOKEN_START];
+        token.extend(self.as_ref());
+        token.push(BUILTIN_TOKEN_END);
+        token
+    }
+
+    /// The builtin if the whole of `text` is its [`BuiltinMacro::token`].
+    pub fn from_token(text: &[u8]) -> Option<Self> {
+        Self::from_name(
+            text.strip_prefix(&[BUILTIN_TOKEN_START])?
+                .strip_suffix(&[BUILTIN_TOKEN_END])?,
+        )
+    }
+
+    /// Whether this builtin is defined in `mode`. The GNU extensions are not defined in
+    /// [`Mode::Traditional`], while `unix` is only defined there (`__unix__` is used instead).
+    pub fn is_defined_in(&self, mode: Mode) -> bool {
+        match mode {
+            Mode::Gnu => !matches!(self, BuiltinMacro::UnixTraditional),
+            Mode::Traditional => !self.is_gnu_extension(),
+        }
+    }
+
+    /// Whether this builtin is a GNU extension, these are not defined in [`crate::Mode::Traditional`].
+    pub fn is_gnu_extension(&self) -> bool {
+        use BuiltinMacro::*;
+        matches!(
+            self,
+            Builtin
+                | Changeword
+                | Debugfile
+                | Debugmode
+                | Esyscmd
+                | File
+                | Format
+                | Gnu
+                | Indir
+                | Line
+                | Patsubst
+                | Program
+                | Regexp
+                | Unix
+        )
+    }
+
+    /// Whether calling this builtin with `args` is disabled by [`crate::Args::safer`], because it
+    /// runs commands or creates files.
+    pub fn is_unsafe(&self, args: &[Vec<u8>]) -> bool {
+        use BuiltinMacro::*;
+        match self {
+            Esyscmd | Syscmd | Maketemp | Mkstemp => true,
+            // Restoring standard error or discarding the debug output is harmless.
+            Debugfile => args.first().is_some_and(|file| !file.is_empty()),
+            _ => false,
+        }
+    }
+
     /// The minimum number of args that this macro requires in order for it to be parsed as a
     /// macro.
     pub fn min_args(&self) -> usize {
         use BuiltinMacro::*;
         match self {
+            Builtin => 1,
             Changecom => 0,
             Changequote => 0,
+            Changeword => 1,
+            Debugfile => 0,
+            Debugmode => 0,
             Decr => 1,
             Define => 1,
             Defn => 1,
@@ -165,20 +315,28 @@ impl BuiltinMacro {
             Dnl => 0,
             Dumpdef => 1,
             Errprint => 1,
+            Esyscmd => 1,
             Eval => 1,
             File => 0,
+            Format => 1,
+            Gnu => 0,
             Ifdef => 1,
             Ifelse => 1,
             Include => 1,
             Incr => 1,
             Index => 1,
+            Indir => 1,
             Len => 1,
+            Line => 0,
             M4exit => 0,
             M4wrap => 1,
             Maketemp => 1,
             Mkstemp => 1,
+            Patsubst => 1,
             Popdef => 1,
+            Program => 0,
             Pushdef => 1,
+            Regexp => 1,
             Shift => 1,
             Sinclude => 1,
             Substr => 1,
@@ -189,6 +347,8 @@ impl BuiltinMacro {
             Translit => 1,
             Undefine => 1,
             Undivert => 0,
+            Unix => 0,
+            UnixTraditional => 0,
         }
     }
 
@@ -200,12 +360,50 @@ impl BuiltinMacro {
     }
 }
 
+/// The start of a builtin token, which is the expansion of `defn` for a builtin macro: this byte,
+/// the name of the builtin, then [`BUILTIN_TOKEN_END`]. The token is only meaningful as the
+/// definition given to `define` or `pushdef`, which then define a macro implemented by the
+/// builtin. Anywhere else it is removed, the same as in GNU m4. See [`BuiltinMacro::token`].
+pub(crate) const BUILTIN_TOKEN_START: u8 = 0x02;
+/// The end of a builtin token, see [`BUILTIN_TOKEN_START`].
+pub(crate) const BUILTIN_TOKEN_END: u8 = 0x03;
+
+/// Remove any builtin tokens (see [`BUILTIN_TOKEN_START`]) from `text`.
+pub(crate) fn strip_builtin_tokens(text: &[u8]) -> std::borrow::Cow<'_, [u8]> {
+    if !text.contains(&BUILTIN_TOKEN_START) {
+        return std::borrow::Cow::Borrowed(text);
+    }
+    let mut stripped = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.iter().position(|c| *c == BUILTIN_TOKEN_START) {
+        stripped.extend(&rest[..start]);
+        rest = match rest[start..].iter().position(|c| *c == BUILTIN_TOKEN_END) {
+            Some(end) => &rest[start + end + 1..],
+            None => &[],
+        };
+    }
+    stripped.extend(rest);
+    std::borrow::Cow::Owned(stripped)
+}
+
 pub(crate) struct MacroDefinition {
     pub parse_config: MacroParseConfig,
     pub implementation: MacroDefinitionImplementation,
 }
 
 impl MacroDefinition {
+    /// A definition of the macro `name` which is implemented by `builtin`, `name` can differ from
+    /// [`BuiltinMacro::name`], for example with [`crate::Args::prefix_builtins`].
+    pub fn new_builtin(name: MacroName, builtin: BuiltinMacro) -> Self {
+        Self {
+            parse_config: MacroParseConfig {
+                name,
+                min_args: builtin.min_args(),
+            },
+            implementation: builtin.implementation(),
+        }
+    }
+
     pub fn new_user_defined(name: MacroName, definition: Vec<u8>) -> Self {
         Self {
             parse_config: MacroParseConfig { name, min_args: 0 },
@@ -1,13 +1,18 @@
 use std::io::Write;
 
 use crate::{
+    debug::{write_debug, write_location_prefix, DebugFlags},
+    input::PushbackMark,
     lexer::MacroName,
     state::{StackFrame, State},
     Result,
 };
 
-use super::MacroImplementation;
+use super::{BuiltinMacro, MacroImplementation};
 
+/// The traceoff macro shall disable tracing for each of the macros specified as arguments, or for
+/// all macros if no arguments are specified. Macros traced because of [`DebugFlags::TRACE_ALL`] are
+/// still traced.
 pub struct TraceoffMacro;
 
 impl MacroImplementation for TraceoffMacro {
@@ -20,10 +25,11 @@ impl MacroImplementation for TraceoffMacro {
         if frame.args.is_empty() {
             state.trace = Trace::default();
         } else {
+            // An argument which is not a macro name (such as an empty one) is ignored.
             for arg in frame.args {
-                let exclude = MacroName::try_from_slice(&arg)?;
-                state.trace.include.retain(|include| include != &exclude);
-                state.trace.exclude.push(exclude);
+                if let Ok(name) = state.parse_config.macro_name(&arg) {
+                    state.trace.disable(&name);
+                }
             }
         }
 
@@ -31,6 +37,12 @@ impl MacroImplementation for TraceoffMacro {
     }
 }
 
+/// The traceon macro shall enable tracing for each of the macros specified as arguments, or, if no
+/// arguments are specified, for all macros.
+///
+/// Like GNU m4, tracing belongs to the macro name, so a name can be traced before it is defined
+/// and remains traced if it is redefined. Without arguments only the macros which are currently
+/// defined are traced, use [`DebugFlags::TRACE_ALL`] to trace every macro.
 pub struct TraceonMacro;
 
 impl MacroImplementation for TraceonMacro {
@@ -41,39 +53,168 @@ impl MacroImplementation for TraceonMacro {
         frame: StackFrame,
     ) -> Result<State> {
         if frame.args.is_empty() {
-            state.trace.all = true;
-            state.trace.exclude.clear();
+            let mut names: Vec<MacroName> = state
+                .macro_definitions
+                .iter()
+                .filter(|(_, definitions)| !definitions.is_empty())
+                .map(|(name, _)| name.clone())
+                .collect();
+            names.sort();
+            for name in names {
+                state.trace.enable(name);
+            }
         } else {
             for arg in frame.args {
-                let include = MacroName::try_from_slice(&arg)?;
-                state.trace.exclude.retain(|exclude| exclude != &include);
-                state.trace.include.push(include);
+                if let Ok(name) = state.parse_config.macro_name(&arg) {
+                    state.trace.enable(name);
+                }
             }
         }
         Ok(state)
     }
 }
 
+/// The macro names which are traced, set using [`TraceonMacro`], [`TraceoffMacro`] and
+/// [`crate::DefineDirective::Trace`].
 #[derive(Default)]
 pub struct Trace {
-    all: bool,
-    exclude: Vec<MacroName>,
     include: Vec<MacroName>,
 }
 
 impl Trace {
-    pub fn trace(
-        &self,
-        stack: &[StackFrame],
-        current_frame: &StackFrame,
-        stderr: &mut dyn Write,
-    ) -> crate::Result<()> {
-        let name = &current_frame.definition.parse_config.name;
-        let level = stack.len() + 1;
-        if (self.all && !self.exclude.contains(name)) || (!self.all && self.include.contains(name))
-        {
-            writeln!(stderr, "m4trace: -{level}- {name}")?;
+    /// Names of the macros that have been individually enabled for tracing.
+    pub fn traced_names(&self) -> &[MacroName] {
+        &self.include
+    }
+
+    /// Enable tracing for the macro `name`.
+    pub fn enable(&mut self, name: MacroName) {
+        if !self.include.contains(&name) {
+            self.include.push(name);
+        }
+    }
+
+    /// Disable tracing for the macro `name`.
+    pub fn disable(&mut self, name: &MacroName) {
+        self.include.retain(|include| include != name);
+    }
+
+    /// Whether calls to the macro `name` are traced, either individually or because all macros
+    /// are being traced.
+    pub fn is_traced(&self, name: &MacroName, debug_flags: DebugFlags) -> bool {
+        debug_flags.contains(DebugFlags::TRACE_ALL) || self.include.contains(name)
+    }
+}
+
+/// Write the start of a trace line, `m4trace:file:line: -level- id N: `.
+fn write_trace_header(state: &State, call_id: usize, line: &mut Vec<u8>) -> std::io::Result<()> {
+    write_location_prefix(state, "m4trace", line)?;
+    let level = state.output.stack.len() + 1;
+    write!(line, " -{level}- ")?;
+    if state.debug_flags.contains(DebugFlags::CALL_ID) {
+        write!(line, "id {call_id}: ")?;
+    }
+    Ok(())
+}
+
+/// Write `s`, surrounded by the current quotes if [`DebugFlags::QUOTE`] is enabled. A builtin
+/// token is written as `<name>` instead, like GNU m4.
+fn write_quoted(state: &State, s: &[u8], line: &mut Vec<u8>) {
+    if let Some(builtin) = BuiltinMacro::from_token(s) {
+        line.push(b'<');
+        line.extend(builtin.as_ref());
+        line.push(b'>');
+        return;
+    }
+    let quote = state.debug_flags.contains(DebugFlags::QUOTE);
+    if quote {
+        line.extend(&state.parse_config.quote_open_tag);
+    }
+    line.extend(s);
+    if quote {
+        line.extend(&state.parse_config.quote_close_tag);
+    }
+}
+
+/// Called when a macro is recognized, before its arguments are collected. Only produces output
+/// with [`DebugFlags::CALL`].
+pub(crate) fn trace_recognized(
+    state: &State,
+    frame: &StackFrame,
+    stderr: &mut dyn Write,
+) -> Result<()> {
+    let name = &frame.definition.parse_config.name;
+    if !state.debug_flags.contains(DebugFlags::CALL)
+        || !state.trace.is_traced(name, state.debug_flags)
+    {
+        return Ok(());
+    }
+    let mut line = Vec::new();
+    write_trace_header(state, frame.call_id, &mut line)?;
+    line.extend(name.as_bytes());
+    line.extend(b" ...\n");
+    write_debug(state, stderr, &line)?;
+    Ok(())
+}
+
+/// The trace of a macro call which is being evaluated, see [`TraceCall::pre`].
+pub(crate) struct TraceCall {
+    /// The trace line so far, it is completed by [`TraceCall::post`].
+    line: Vec<u8>,
+    mark: PushbackMark,
+}
+
+impl TraceCall {
+    /// Called once the arguments for the macro call in `frame` have been collected, just before it
+    /// is evaluated. Returns `None` if the macro is not being traced.
+    pub fn pre(state: &State, frame: &StackFrame, stderr: &mut dyn Write) -> Result<Option<Self>> {
+        let name = &frame.definition.parse_config.name;
+        let flags = state.debug_flags;
+        if !state.trace.is_traced(name, flags) {
+            return Ok(None);
+        }
+
+        let mut line = Vec::new();
+        write_trace_header(state, frame.call_id, &mut line)?;
+        line.extend(name.as_bytes());
+        if flags.contains(DebugFlags::ARGS) && !frame.args.is_empty() {
+            line.push(b'(');
+            for (i, arg) in frame.args.iter().enumerate() {
+                if i > 0 {
+                    line.extend(b", ");
+                }
+                write_quoted(state, arg, &mut line);
+            }
+            line.push(b')');
+        }
+
+        if flags.contains(DebugFlags::CALL) {
+            line.extend(b" -> ???\n");
+            write_debug(state, stderr, &line)?;
+            line.clear();
+            write_trace_header(state, frame.call_id, &mut line)?;
+            line.extend(name.as_bytes());
+            if !frame.args.is_empty() {
+                line.extend(b"(...)");
+            }
+        }
+
+        Ok(Some(Self {
+            line,
+            mark: state.input.pushback_mark(),
+        }))
+    }
+
+    /// Called after the macro has been evaluated, completes the trace line with the expansion if
+    /// [`DebugFlags::EXPANSION`] is enabled.
+    pub fn post(mut self, state: &State, stderr: &mut dyn Write) -> Result<()> {
+        if state.debug_flags.contains(DebugFlags::EXPANSION) {
+            self.line.extend(b" -> ");
+            let expansion = state.input.pushed_back_since(self.mark);
+            write_quoted(state, &expansion, &mut self.line);
         }
+        self.line.push(b'\n');
+        write_debug(state, stderr, &self.line)?;
         Ok(())
     }
 }
@@ -2,7 +2,8 @@ use std::io::Write;
 
 use crate::{
     state::{StackFrame, State},
-    Result,
+    warning::WarningCategory,
+    Mode, Result,
 };
 
 use super::MacroImplementation;
@@ -14,13 +15,19 @@ use super::MacroImplementation;
 /// a string. The string "$*" is replaced by a list of all of the arguments, separated by <comma>
 /// characters. The string "$@" is replaced by a list of all of the arguments separated by <comma>
 /// characters, and each argument is quoted using the current left and right quoting strings. The
-/// string "${" produces unspecified behavior.
+/// string "${" produces unspecified behavior, see [`ArgumentReference`] for how it and references
+/// to more than nine arguments are handled.
 pub struct UserDefinedMacro {
     pub definition: Vec<u8>,
 }
 
 impl MacroImplementation for UserDefinedMacro {
-    fn evaluate(&self, state: State, _stderr: &mut dyn Write, frame: StackFrame) -> Result<State> {
+    fn evaluate(
+        &self,
+        mut state: State,
+        stderr: &mut dyn Write,
+        frame: StackFrame,
+    ) -> Result<State> {
         log::debug!(
             "UserDefinedMacro::evaluate() evaluating {:?}: definition:{:?} args:{:?}",
             frame.definition.parse_config.name.to_string(),
@@ -32,68 +39,136 @@ impl MacroImplementation for UserDefinedMacro {
                 .collect::<Vec<_>>(),
         );
 
-        if self.definition.is_empty() {
-            return Ok(state);
-        }
-
-        let mut i = self.definition.len() - 1;
-        loop {
-            if i == 0 || self.definition[i - 1] != b'$' {
-                state.input.pushback_character(self.definition[i]);
-            } else {
-                let t = self.definition[i];
-                match t {
-                    b'#' => state
-                        .input
-                        .pushback_string(frame.args.len().to_string().as_bytes()),
-                    b'0' => state
-                        .input
-                        .pushback_string(&frame.definition.parse_config.name.0),
-                    b'1'..=b'9' => {
-                        let arg_index = (t - b'1') as usize;
-                        if arg_index < frame.args.len() {
-                            state.input.pushback_string(&frame.args[arg_index]);
-                        }
+        let definition = &self.definition;
+        let mut expansion = state.output.buffers.take();
+        expansion.reserve(definition.len());
+        let mut i = 0;
+        while i < definition.len() {
+            let c = definition[i];
+            i += 1;
+            let Some(&t) = definition.get(i).filter(|_| c == b'$') else {
+                expansion.push(c);
+                continue;
+            };
+            match t {
+                b'#' => {
+                    i += 1;
+                    expansion.extend(frame.args.len().to_string().as_bytes());
+                }
+                b'0'..=b'9' => {
+                    let reference = ArgumentReference::parse(&definition[i..], state.mode);
+                    if let Some(digit) = reference.ambiguous_digit {
+                        state.retrievable_warning(
+                            stderr,
+                            WarningCategory::Other,
+                            format!(
+                                "`${}{}' in the definition of `{}' is argument {} followed by `{}'",
+                                reference.index,
+                                char::from(digit),
+                                frame.definition.parse_config.name,
+                                reference.index,
+                                char::from(digit),
+                            ),
+                        )?;
                     }
-                    b'*' => {
-                        for (arg_index, arg) in frame.args.iter().enumerate().rev() {
-                            state.input.pushback_string(arg);
-                            if arg_index > 0 {
-                                state.input.pushback_character(b',');
-                            }
+                    i += reference.len;
+                    expansion.extend(argument(&frame, reference.index));
+                }
+                b'{' if state.mode == Mode::Gnu => {
+                    match ArgumentReference::parse_braced(&definition[i..]) {
+                        Some(reference) => {
+                            i += reference.len;
+                            expansion.extend(argument(&frame, reference.index));
                         }
+                        None => expansion.push(c),
                     }
-                    b'@' => {
-                        for (arg_index, arg) in frame.args.iter().enumerate().rev() {
-                            state
-                                .input
-                                .pushback_string(&state.parse_config.quote_close_tag);
-                            state.input.pushback_string(arg);
-                            state
-                                .input
-                                .pushback_string(&state.parse_config.quote_open_tag);
-                            if arg_index > 0 {
-                                state.input.pushback_character(b',');
-                            }
+                }
+                b'*' | b'@' => {
+                    i += 1;
+                    for (arg_index, arg) in frame.args.iter().enumerate() {
+                        if arg_index > 0 {
+                            expansion.push(b',');
+                        }
+                        if t == b'@' {
+                            expansion.extend(&state.parse_config.quote_open_tag);
+                            expansion.extend(arg);
+                            expansion.extend(&state.parse_config.quote_close_tag);
+                        } else {
+                            expansion.extend(arg);
                         }
                     }
-                    _ => {
-                        state.input.pushback_character(t);
-                        state.input.pushback_character(b'$');
-                    }
-                }
-                // TODO(performance): this might be able to skip an iteration with i==1?
-                if i == 0 {
-                    break;
                 }
-                i -= 1;
+                // Not a reference, the following character is processed as usual.
+                _ => expansion.push(c),
             }
-            if i == 0 {
-                break;
-            }
-            i -= 1;
         }
+        state.input.pushback_string(expansion);
 
+        state.output.buffers.give_arguments(frame.args);
         Ok(state)
     }
 }
+
+/// The text of the argument reference `$index`, `$0` is the name of the macro. A reference to an
+/// argument which was not provided is empty.
+fn argument(frame: &StackFrame, index: usize) -> &[u8] {
+    match index {
+        0 => frame.definition.parse_config.name.as_bytes(),
+        _ => frame.args.get(index - 1).map_or(&[], Vec::as_slice),
+    }
+}
+
+/// A numbered argument reference in a macro definition, following the `$`.
+///
+/// In [`Mode::Gnu`] all of the digits are part of the reference, so `$10` is the tenth argument,
+/// and `${10}` can be used to make this explicit. In [`Mode::Traditional`] only the first digit
+/// is, so `$10` is the first argument followed by `0`, which is ambiguous enough to be worth a
+/// warning.
+struct ArgumentReference {
+    index: usize,
+    /// The number of bytes of the definition used by the reference, after the `$`.
+    len: usize,
+    /// The digit immediately following a single digit reference in [`Mode::Traditional`].
+    ambiguous_digit: Option<u8>,
+}
+
+impl ArgumentReference {
+    /// Parse the reference at the start of `input`, which starts with a digit.
+    fn parse(input: &[u8], mode: Mode) -> Self {
+        let digits = input.iter().take_while(|c| c.is_ascii_digit()).count();
+        match mode {
+            Mode::Traditional => Self {
+                index: usize::from(input[0] - b'0'),
+                len: 1,
+                ambiguous_digit: input.get(1).copied().filter(u8::is_ascii_digit),
+            },
+            Mode::Gnu => Self {
+                index: parse_index(&input[..digits]),
+                len: digits,
+                ambiguous_digit: None,
+            },
+        }
+    }
+
+    /// Parse a `{digits}` reference at the start of `input`.
+    fn parse_braced(input: &[u8]) -> Option<Self> {
+        let digits = input[1..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || input.get(1 + digits) != Some(&b'}') {
+            return None;
+        }
+        Some(Self {
+            index: parse_index(&input[1..1 + digits]),
+            len: digits + 2,
+            ambiguous_digit: None,
+        })
+    }
+}
+
+/// Parse the decimal `digits`, saturating so that an enormous index refers to a missing argument.
+fn parse_index(digits: &[u8]) -> usize {
+    digits.iter().fold(0usize, |index, digit| {
+        index
+            .saturating_mul(10)
+            .saturating_add(usize::from(digit - b'0'))
+    })
+}
@@ -9,10 +9,9 @@ fn main() -> ExitCode {
     let stdout = std::io::stdout();
     let mut stderr = std::io::stderr();
     if let Err(error) = posixutils_m4::run(stdout, &mut stderr, args) {
-        ExitCode::from(u8::try_from(error.get_exit_code()).unwrap_or_else(|e| {
-            eprintln!("Error casting exit code {e} into platform agnostic u8");
-            1
-        }))
+        // The library reports its own errors, an exit code which does not fit in an exit status
+        // (from a library user constructing one) is simply a failure.
+        ExitCode::from(u8::try_from(error.get_exit_code()).unwrap_or(1))
     } else {
         ExitCode::SUCCESS
     }
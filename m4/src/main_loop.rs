@@ -1,21 +1,65 @@
 use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
 
 use crate::error::{Error, ErrorKind};
-use crate::lexer::{is_alpha, is_space};
-use crate::macros::MacroImplementation;
+use crate::input::TOKEN_CHUNK_SIZE;
+use crate::lexer::{is_alpha, is_alphnumeric, is_space, MacroName};
+use crate::macros::{
+    trace::trace_recognized, MacroDefinition, MacroImplementation, BUILTIN_TOKEN_END,
+    BUILTIN_TOKEN_START,
+};
+use crate::output::OutputState;
 use crate::state::{StackFrame, State};
-use crate::EOF;
+use crate::warning::WarningCategory;
+use crate::DiagnosticsFormat;
 
-/// The main loop, the most important function in this program.
-pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::error::Result<State> {
+/// The main loop, the most important function in this program. Processes the bottom-most input
+/// (and anything it includes) until it reaches end of file.
+///
+/// An error records the location in the input where it occurred, see
+/// [`crate::error::Error::location`].
+pub(crate) fn main_loop(state: State, stderr: &mut dyn Write) -> crate::error::Result<State> {
+    let input = state.input.clone();
+    process(state, stderr).map_err(|error| match input.location() {
+        Some(location) => error.with_location(location, input.included_from()),
+        None => error,
+    })
+}
+
+/// See [`main_loop`].
+fn process(mut state: State, stderr: &mut dyn Write) -> crate::error::Result<State> {
     let mut token: Vec<u8> = Vec::new();
 
     // TODO(style): rename these to something sensible.
-    let mut l: u8 = 0;
+    let mut l: u8;
     let mut t: u8;
+    // See [`byte_classes`], cleared whenever a macro is evaluated as that may change the macro
+    // definitions or the syntax.
+    let mut byte_classes_cache: Option<Box<ByteClasses>> = None;
 
     'main_loop: loop {
-        t = state.input.get_next_character()?;
+        if state.interactive && state.output.stack.is_empty() {
+            // Flush everything expanded so far before possibly waiting for more input, see
+            // [`crate::Args::interactive`].
+            state.output.output.flush()?;
+        }
+        t = match state.input.get_next_character()? {
+            Some(c) => c,
+            None => {
+                if state.input.input_len() == 1 {
+                    if !state.output.stack.is_empty() {
+                        return Err(Error::new(ErrorKind::UnclosedParenthesis)
+                            .with_macros(state.macro_stack()));
+                    }
+                    break 'main_loop;
+                }
+                state.input_pop(stderr)?;
+                continue 'main_loop;
+            }
+        };
+        // The line where the current token started, see [`crate::output::Output::write_text`].
+        let line = state.input.line_number();
         // Strip quotes
         if state
             .input
@@ -23,9 +67,24 @@ pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::erro
         {
             log::trace!("Stripping quotes");
             let mut quotation_level: usize = 1;
+            token.clear();
+            // Within the arguments of a macro call the quoted text is appended to the argument
+            // directly, rather than being copied there once it is complete.
+            let in_argument = !state.output.stack.is_empty();
+            if in_argument {
+                state.output.swap_argument(&mut token);
+            }
 
             'inside_quote: loop {
-                l = state.input.get_next_character()?;
+                l = match state.input.get_next_character()? {
+                    Some(c) => c,
+                    None => {
+                        if !continue_in_includer(&mut state, stderr, "string", line)? {
+                            return Err(crate::Error::new(crate::ErrorKind::UnclosedQuote));
+                        }
+                        continue 'inside_quote;
+                    }
+                };
                 if state
                     .input
                     .look_ahead(l, &state.parse_config.quote_close_tag)?
@@ -33,27 +92,31 @@ pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::erro
                     quotation_level -= 1;
                     if quotation_level > 0 {
                         // Encountered closing quote within the quote, so we output it.
-                        state
-                            .output
-                            .write_all(&state.parse_config.quote_close_tag)?;
+                        token.extend(&state.parse_config.quote_close_tag);
                     }
                 } else if state
                     .input
                     .look_ahead(l, &state.parse_config.quote_open_tag)?
                 {
                     quotation_level += 1;
-                    state.output.write_all(&state.parse_config.quote_open_tag)?;
-                } else if l == EOF {
-                    return Err(crate::Error::new(crate::ErrorKind::UnclosedQuote));
+                    token.extend(&state.parse_config.quote_open_tag);
                 } else if quotation_level > 0 {
-                    log::trace!(
-                        "Writing quoted content to output: {:?}",
-                        String::from_utf8_lossy(&[l])
-                    );
-                    state.output.write_all(&[l])?;
+                    token.push(l);
+                }
+                if !in_argument {
+                    write_token_chunk(&mut state.output, &mut token, line)?;
                 }
 
                 if quotation_level == 0 {
+                    if in_argument {
+                        state.output.swap_argument(&mut token);
+                    } else {
+                        log::trace!(
+                            "Writing quoted content to output: {:?}",
+                            String::from_utf8_lossy(&token)
+                        );
+                        state.output.write_token(&token, line)?;
+                    }
                     log::trace!("Finished stripping quotes");
                     break 'inside_quote;
                 }
@@ -63,69 +126,84 @@ pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::erro
                 .input
                 .look_ahead(t, &state.parse_config.comment_open_tag)?
         {
-            state
-                .output
-                .write_all(&state.parse_config.comment_open_tag)?;
+            token.clear();
+            token.extend(&state.parse_config.comment_open_tag);
 
             'inside_comment: loop {
-                t = state.input.get_next_character()?;
+                t = match state.input.get_next_character()? {
+                    Some(c) => c,
+                    None => {
+                        if continue_in_includer(&mut state, stderr, "comment", line)? {
+                            continue 'inside_comment;
+                        }
+                        break 'inside_comment;
+                    }
+                };
                 if state
                     .input
                     .look_ahead(t, &state.parse_config.comment_close_tag)?
                 {
-                    state
-                        .output
-                        .write_all(&state.parse_config.comment_close_tag)?;
+                    token.extend(&state.parse_config.comment_close_tag);
                     break 'inside_comment;
                 }
-                if t == EOF {
-                    break 'inside_comment;
+                token.push(t);
+                write_token_chunk(&mut state.output, &mut token, line)?;
+            }
+            state.output.write_token(&token, line)?;
+        } else if t == BUILTIN_TOKEN_START {
+            // A builtin token from `defn` is kept whole within macro arguments, so that `define`
+            // can recognize it, and removed from the output.
+            token.clear();
+            token.push(t);
+            while token.last() != Some(&BUILTIN_TOKEN_END) {
+                match state.input.get_next_character()? {
+                    Some(c) => token.push(c),
+                    None => break,
                 }
-                state.output.write_all(&[t])?;
             }
-        } else if t == b'_' || is_alpha(t) {
+            if !state.output.stack.is_empty() {
+                state.output.write_all(&token)?;
+            }
+        } else if state.parse_config.is_word_start(t)
+            && (!state.output.stack.is_empty()
+                || byte_classes(&state, &mut byte_classes_cache)[usize::from(t)] & MACRO_START != 0)
+        {
             // Possibly a macro to be evaluated.
             let definition = state.parse_macro(t, &mut token)?;
+            let mut next = None;
             if definition.is_some() {
-                l = state.input.get_next_character()?;
-                state.input.pushback_character(l);
+                next = state.input.get_next_character()?;
+                if let Some(c) = next {
+                    state.input.pushback_character(c);
+                }
             }
 
             // Check to see whether it's currently defined macro or it needs some arguments but
             // there's no open bracket.
-            if definition.is_none()
-                || (l != b'(' && (definition.as_ref().unwrap().parse_config.min_args > 0))
-            {
-                state.output.write_all(&token)?;
-            } else {
-                let definition = definition.unwrap();
-
-                let frame = StackFrame::new(0, definition.clone());
+            match definition {
+                Some(definition) if next == Some(b'(') || definition.parse_config.min_args == 0 => {
+                    check_nesting_limit(&state, &definition)?;
+                    state.macro_calls += 1;
+                    let args = state.output.buffers.take_arguments();
+                    let frame = StackFrame::new(0, definition.clone(), state.macro_calls, args);
+                    trace_recognized(&state, &frame, stderr)?;
 
-                if l == b'(' {
-                    state.output.stack.push(frame);
-                } else {
-                    state = definition.implementation.evaluate(state, stderr, frame)?;
-                }
-            }
-        } else if t == EOF {
-            if state.input.input_len() == 1 {
-                if !state.output.stack.is_empty() {
-                    return Err(Error::new(ErrorKind::UnclosedParenthesis));
+                    if next == Some(b'(') {
+                        state.output.stack.push(frame);
+                    } else {
+                        state = evaluate(state, stderr, &definition, frame)?;
+                        byte_classes_cache = None;
+                    }
                 }
-                break 'main_loop;
-            }
-            state.input.input_pop();
-            log::debug!("EOF synclines");
-            if state.input.sync_lines() {
-                state
-                    .input
-                    .emit_syncline(&mut *state.output.output.stdout().borrow_mut(), false)?;
+                _ => state.output.write_all(&token)?,
             }
-            continue 'main_loop;
         } else if state.output.stack.is_empty() {
-            // not in a macro
-            state.output.write_all(&[t])?;
+            // Not in a macro, so the text up until the start of the next token is output as is.
+            state.output.write_token(&[t], line)?;
+            if t != b'\n' {
+                let classes = byte_classes(&state, &mut byte_classes_cache);
+                read_plain_text(&mut state, t, classes)?;
+            }
         } else {
             match t {
                 b'(' => {
@@ -133,16 +211,19 @@ pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::erro
                         state.output.write_all(&[t])?;
                     }
                     'skip_whitespace: loop {
-                        l = state.input.get_next_character()?;
-                        if is_space(l) {
-                            if state.output.stack.last_mut().unwrap().parenthesis_level > 0 {
-                                state.output.write_all(&[l])?;
+                        match state.input.get_next_character()? {
+                            Some(c) if is_space(c) => {
+                                if state.output.stack.last_mut().unwrap().parenthesis_level > 0 {
+                                    state.output.write_all(&[c])?;
+                                }
+                            }
+                            Some(c) => {
+                                state.input.pushback_character(c);
+                                break 'skip_whitespace;
                             }
-                        } else {
-                            break 'skip_whitespace;
+                            None => break 'skip_whitespace,
                         }
                     }
-                    state.input.pushback_character(l);
                     state.output.stack.last_mut().unwrap().parenthesis_level += 1;
                 }
                 b')' => {
@@ -152,47 +233,54 @@ pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::erro
                     } else {
                         // end of argument list
                         let frame = state.output.stack.pop().unwrap();
-                        state = frame
-                            .definition
-                            .clone()
-                            .implementation
-                            .evaluate(state, stderr, frame)?;
+                        let definition = frame.definition.clone();
+                        state = evaluate(state, stderr, &definition, frame)?;
+                        byte_classes_cache = None;
                     }
                 }
                 b',' => {
                     if state.output.stack.last().unwrap().parenthesis_level == 1 {
                         // Skip spaces after comma
                         loop {
-                            l = state.input.get_next_character()?;
-                            if !is_space(l) {
-                                break;
+                            match state.input.get_next_character()? {
+                                Some(c) if is_space(c) => {}
+                                Some(c) => {
+                                    state.input.pushback_character(c);
+                                    break;
+                                }
+                                None => break,
                             }
                         }
-                        state.input.pushback_character(l);
-                        let args = &mut state.output.stack.last_mut().unwrap().args;
+                        let output = &mut state.output;
+                        let args = &mut output.stack.last_mut().unwrap().args;
                         if args.is_empty() {
-                            args.push(Vec::new());
+                            args.push(output.buffers.take());
                         }
-                        args.push(Vec::new());
+                        args.push(output.buffers.take());
                     } else {
                         state.output.write_all(&[t])?;
                     }
                 }
                 _ => {
                     // Output comment
-                    if state
-                        .input
-                        .look_ahead(t, &state.parse_config.comment_open_tag)?
+                    if state.parse_config.comment_enabled
+                        && state
+                            .input
+                            .look_ahead(t, &state.parse_config.comment_open_tag)?
                     {
                         state
                             .output
                             .write_all(&state.parse_config.comment_open_tag)?;
                         'comment: loop {
-                            t = state.input.get_next_character()?;
-                            if t == EOF {
-                                // TODO: What happens with multiple inputs?
-                                break 'comment;
-                            }
+                            t = match state.input.get_next_character()? {
+                                Some(c) => c,
+                                None => {
+                                    if continue_in_includer(&mut state, stderr, "comment", line)? {
+                                        continue 'comment;
+                                    }
+                                    break 'comment;
+                                }
+                            };
                             if state
                                 .input
                                 .look_ahead(t, &state.parse_config.comment_close_tag)?
@@ -212,12 +300,288 @@ pub(crate) fn main_loop(mut state: State, stderr: &mut dyn Write) -> crate::erro
         }
     }
 
-    state.output.output.divert(0)?;
-    state.output.output.undivert_all()?;
+    Ok(state)
+}
+
+/// How each byte is treated by [`read_plain_text`], a combination of [`WORD_CHAR`],
+/// [`WORD_START`], [`MACRO_START`] and [`TOKEN_START`].
+type ByteClasses = [u8; 256];
 
-    for wrap in &state.m4wrap {
-        state.output.write_all(wrap)?;
+/// The byte can continue a word in the default macro name syntax.
+const WORD_CHAR: u8 = 1;
+/// The byte can start a word, see [`crate::lexer::ParseConfig::is_word_start`].
+const WORD_START: u8 = 2;
+/// A word starting with the byte can be a macro. A word which can not is output as plain text by
+/// [`read_plain_text`].
+const MACRO_START: u8 = 4;
+/// The byte starts something which needs to be parsed, unless it continues a word: a `<newline>`,
+/// a builtin token, a quoted string, a comment or a word which can be a macro.
+const TOKEN_START: u8 = 8;
+
+/// The [`ByteClasses`] for the current syntax and macro definitions, computed if `cache` is
+/// empty.
+fn byte_classes<'c>(state: &State, cache: &'c mut Option<Box<ByteClasses>>) -> &'c ByteClasses {
+    // The classes in the default macro name syntax (see `ParseConfig::is_word_start`), which only
+    // depend on the characters.
+    static DEFAULT_CLASSES: OnceLock<ByteClasses> = OnceLock::new();
+    let default_classes = DEFAULT_CLASSES.get_or_init(|| {
+        let mut classes = [0; 256];
+        for (c, class) in (0..=u8::MAX).zip(classes.iter_mut()) {
+            if is_alphnumeric(c) || c == b'_' {
+                *class |= WORD_CHAR;
+            }
+            if is_alpha(c) || c == b'_' {
+                *class |= WORD_START;
+            }
+        }
+        classes
+    });
+    cache.get_or_insert_with(|| {
+        let parse_config = &state.parse_config;
+        let mut classes = Box::new(*default_classes);
+        if parse_config.word_regex.is_some() {
+            // Words are only matched one byte at a time using the regular expression, so every
+            // word start is parsed as a possible macro.
+            for (c, class) in (0..=u8::MAX).zip(classes.iter_mut()) {
+                *class &= !WORD_START;
+                if parse_config.is_word_start(c) {
+                    *class |= WORD_START | MACRO_START | TOKEN_START;
+                }
+            }
+        }
+        // Collected separately rather than updating `classes` for every name, which is slower
+        // when many names start with the same byte.
+        let mut defined = [false; 256];
+        for (name, definitions) in &state.macro_definitions {
+            match name.as_bytes().first() {
+                Some(c) if !definitions.is_empty() => defined[usize::from(*c)] = true,
+                _ => {}
+            }
+        }
+        for (class, defined) in classes.iter_mut().zip(defined) {
+            if defined && *class & WORD_START != 0 {
+                *class |= MACRO_START | TOKEN_START;
+            }
+        }
+        classes[usize::from(b'\n')] |= TOKEN_START;
+        classes[usize::from(BUILTIN_TOKEN_START)] |= TOKEN_START;
+        if let Some(c) = parse_config.quote_open_tag.first() {
+            classes[usize::from(*c)] |= TOKEN_START;
+        }
+        if let Some(c) = parse_config
+            .comment_open_tag
+            .first()
+            .filter(|_| parse_config.comment_enabled)
+        {
+            classes[usize::from(*c)] |= TOKEN_START;
+        }
+        classes
+    })
+}
+
+/// Write the plain text following `t` (which has already been written), up until the start of the
+/// next token which needs parsing (see [`ByteClasses`]). It is written straight from the input
+/// (see [`crate::output::OutputRef::write_continuation`]), a word which can not be a macro is
+/// included in its entirety, so that mostly literal input is copied through in bulk.
+fn read_plain_text(state: &mut State, t: u8, classes: &ByteClasses) -> crate::error::Result<()> {
+    // Whether the last plain character was part of a word.
+    let mut in_word = classes[usize::from(t)] & WORD_START != 0;
+    let output = &mut state.output.output;
+    loop {
+        state.input.read_plain(
+            |text| {
+                for (i, c) in text.iter().enumerate() {
+                    let class = classes[usize::from(*c)];
+                    if in_word && class & WORD_CHAR != 0 {
+                        continue;
+                    }
+                    if class & TOKEN_START != 0 {
+                        return i;
+                    }
+                    in_word = class & WORD_START != 0;
+                }
+                text.len()
+            },
+            |text| Ok(output.write_continuation(text)?),
+        )?;
+        if !in_word {
+            return Ok(());
+        }
+        // Stopped in the middle of a word at the end of the buffered input, the rest of it still
+        // needs to be included.
+        match state.input.get_next_character()? {
+            Some(c) if classes[usize::from(c)] & WORD_CHAR != 0 => {
+                output.write_continuation(&[c])?;
+            }
+            Some(c) => {
+                state.input.pushback_character(c);
+                return Ok(());
+            }
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Write the part of a quoted string or comment read so far (`token`, which started on input
+/// `line`) once it is [`TOKEN_CHUNK_SIZE`] bytes, so that a token of any size can be processed in
+/// bounded memory. It is only split after a character other than `<newline>`, so that the pieces
+/// are written with the same line synchronization as the whole token would be.
+fn write_token_chunk(
+    output: &mut OutputState,
+    token: &mut Vec<u8>,
+    line: Option<usize>,
+) -> crate::error::Result<()> {
+    if token.len() >= TOKEN_CHUNK_SIZE && token.last() != Some(&b'\n') {
+        output.write_token(token, line)?;
+        token.clear();
+    }
+    Ok(())
+}
+
+/// Called when the end of the current input is reached inside a quoted string or comment (`what`)
+/// which started on line `start_line`. Like GNU m4, it continues in the input which included this
+/// one, but a (retrievable) warning is printed for an included file because the quote or comment
+/// was most likely left unterminated by mistake. Returns `false` if this is the last input.
+fn continue_in_includer(
+    state: &mut State,
+    stderr: &mut dyn Write,
+    what: &str,
+    start_line: Option<usize>,
+) -> crate::error::Result<bool> {
+    if state.input.input_len() <= 1 {
+        return Ok(false);
+    }
+    if !state.input.is_wrapped() {
+        let name = state.input.name().unwrap_or_default();
+        state.retrievable_warning(
+            stderr,
+            WarningCategory::EofInQuote,
+            format_args!(
+                "end of file `{}' in {what} starting on line {}, continuing in the including file",
+                String::from_utf8_lossy(&name),
+                start_line.unwrap_or_default(),
+            ),
+        )?;
+    }
+    state.input_pop(stderr)?;
+    Ok(true)
+}
+
+/// Returns an error if expanding `definition` would exceed [`State::nesting_limit`] nested macro
+/// expansions (those collecting arguments in [`crate::output::OutputState::stack`]).
+pub(crate) fn check_nesting_limit(
+    state: &State,
+    definition: &MacroDefinition,
+) -> crate::error::Result<()> {
+    let limit = state.nesting_limit;
+    if limit == 0 || state.output.stack.len() < limit {
+        return Ok(());
     }
+    Err(Error::new(ErrorKind::NestingLimitExceeded {
+        limit,
+        macros: macro_chain(state, definition),
+    }))
+}
+
+/// Evaluate the macro call `frame`, then check the [`State::expansion_limit`]. With
+/// `--diagnostics-format=json` the macros being expanded are recorded with an error, see
+/// [`crate::error::Error::macros`].
+fn evaluate(
+    mut state: State,
+    stderr: &mut dyn Write,
+    definition: &MacroDefinition,
+    frame: StackFrame,
+) -> crate::error::Result<State> {
+    if state.diagnostics_format == DiagnosticsFormat::Json {
+        // The state is gone if the evaluation fails, so the macros are recorded up front.
+        let macros = macro_chain(&state, definition);
+        state.evaluating = Some(definition.parse_config.name.clone());
+        state = evaluate_profiled(state, stderr, definition, frame)
+            .map_err(|error| error.with_macros(macros))?;
+        state.evaluating = None;
+    } else {
+        state = evaluate_profiled(state, stderr, definition, frame)?;
+    }
+    check_expansion_limit(&state, definition)?;
+    Ok(state)
+}
+
+/// Evaluate the macro call `frame`, recording it in the [`crate::profile::Profile`] if enabled.
+fn evaluate_profiled(
+    mut state: State,
+    stderr: &mut dyn Write,
+    definition: &MacroDefinition,
+    frame: StackFrame,
+) -> crate::error::Result<State> {
+    if state.profile.is_none() {
+        state = definition.implementation.evaluate(state, stderr, frame)?;
+    } else {
+        let expanded = state.input.expanded();
+        let start = std::time::Instant::now();
+        state = definition.implementation.evaluate(state, stderr, frame)?;
+        let time = start.elapsed();
+        let bytes = state.input.expanded() - expanded;
+        if let Some(profile) = &mut state.profile {
+            profile.record(&definition.parse_config.name, time, bytes);
+        }
+    }
+    Ok(state)
+}
+
+/// Returns an error if the total size of the macro expansions, after expanding `definition`,
+/// exceeds [`State::expansion_limit`].
+fn check_expansion_limit(state: &State, definition: &MacroDefinition) -> crate::error::Result<()> {
+    let limit = state.expansion_limit;
+    if limit == 0 || state.input.expanded() <= limit {
+        return Ok(());
+    }
+    Err(Error::new(ErrorKind::ExpansionLimitExceeded {
+        limit,
+        macros: macro_chain(state, definition),
+    }))
+}
+
+/// The names of the macros collecting arguments in [`crate::output::OutputState::stack`],
+/// followed by `definition`.
+fn macro_chain(state: &State, definition: &MacroDefinition) -> Vec<MacroName> {
+    state
+        .output
+        .stack
+        .iter()
+        .map(|frame| frame.definition.parse_config.name.clone())
+        .chain(std::iter::once(definition.parse_config.name.clone()))
+        .collect()
+}
+
+/// Called once after all the inputs have been processed by [`main_loop`], rescans the text saved
+/// by [`crate::macros::M4wrapMacro`] and then flushes the diversions to the output.
+///
+/// If `freeze_state` is specified then the diversions are instead saved along with the rest of the
+/// state to the frozen file, see [`crate::Args::freeze_state`].
+pub(crate) fn finish(
+    mut state: State,
+    stderr: &mut dyn Write,
+    freeze_state: Option<&Path>,
+) -> crate::error::Result<State> {
+    // The saved text is processed in the order it was saved (FIFO). It may itself call `m4wrap`,
+    // in which case that text is processed in another round.
+    while !state.m4wrap.is_empty() {
+        // Each saved text is its own input so that it keeps its location, the first one saved is
+        // pushed last so that it is read first.
+        for wrapped in std::mem::take(&mut state.m4wrap).into_iter().rev() {
+            state.input_push(wrapped, stderr)?;
+        }
+        state = main_loop(state, stderr)?;
+        state.input_pop(stderr)?;
+    }
+
+    if let Some(path) = freeze_state {
+        crate::freeze::freeze_to_file(&state, path)?;
+        return Ok(state);
+    }
+
+    state.output.output.divert(0)?;
+    state.output.output.undivert_all()?;
 
     Ok(state)
 }
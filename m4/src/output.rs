@@ -1,40 +1,124 @@
-use crate::{error::Result, input::InputStateRef, state::StackFrame};
-use std::{
-    cell::RefCell,
-    io::{Seek, Write},
-    rc::Rc,
+use crate::{
+    diversions::{Diversions, DivertBufferNumber},
+    error::Result,
+    input::InputStateRef,
+    state::{Arguments, StackFrame},
+    OutputBuffering,
 };
+use std::{cell::RefCell, io::Write, rc::Rc};
 
 #[derive(Default)]
 pub struct OutputState {
     pub output: OutputRef,
     pub stack: Vec<StackFrame>,
+    /// Spare buffers for macro arguments and expansions, see [`BufferPool`].
+    pub buffers: BufferPool,
+}
+
+/// Empty byte buffers which are reused rather than allocated for each macro call, for the
+/// arguments collected in a [`StackFrame`] and for the expansion of a user defined macro. A
+/// buffer is checked out using [`BufferPool::take`] and returned once it is no longer needed
+/// using [`BufferPool::give`]; a buffer which is never returned (for example an argument kept by
+/// `define`) is simply not reused. The same goes for the [`Arguments`] of a frame, see
+/// [`BufferPool::take_arguments`].
+#[derive(Default)]
+pub struct BufferPool {
+    buffers: Vec<Vec<u8>>,
+    arguments: Vec<Arguments>,
+}
+
+/// At most this many buffers are kept in a [`BufferPool`].
+const BUFFER_POOL_SIZE: usize = 32;
+
+/// Buffers which have grown larger than this are freed rather than kept in a [`BufferPool`], so
+/// that one large expansion does not hold on to its memory.
+const BUFFER_POOL_MAX_CAPACITY: usize = 64 * 1024;
+
+/// [`Arguments`] with room for more than this many arguments are freed rather than kept in a
+/// [`BufferPool`].
+const ARGUMENTS_POOL_MAX_CAPACITY: usize = 64;
+
+impl BufferPool {
+    /// An empty buffer, reusing a previously returned one if there is one.
+    pub fn take(&mut self) -> Vec<u8> {
+        self.buffers.pop().unwrap_or_default()
+    }
+
+    /// Return `buffer` so that it can be reused.
+    pub fn give(&mut self, mut buffer: Vec<u8>) {
+        if self.buffers.len() < BUFFER_POOL_SIZE
+            && buffer.capacity() > 0
+            && buffer.capacity() <= BUFFER_POOL_MAX_CAPACITY
+        {
+            buffer.clear();
+            self.buffers.push(buffer);
+        }
+    }
+
+    /// An empty argument list for a new [`StackFrame`], reusing the storage of a previously
+    /// returned one if there is one.
+    pub fn take_arguments(&mut self) -> Arguments {
+        self.arguments.pop().unwrap_or_default()
+    }
+
+    /// Return `args` and the buffers of the arguments in it so that they can be reused. Only an
+    /// argument list which has outgrown its inline storage is kept, the others cost nothing to
+    /// create.
+    pub fn give_arguments(&mut self, mut args: Arguments) {
+        for arg in args.drain(..) {
+            self.give(arg);
+        }
+        if args.spilled()
+            && args.capacity() <= ARGUMENTS_POOL_MAX_CAPACITY
+            && self.arguments.len() < BUFFER_POOL_SIZE
+        {
+            self.arguments.push(args);
+        }
+    }
 }
 
 impl OutputState {
     /// Write either to output, or to the buffer for the macro arg currently being parsed.
     pub fn write_all(&mut self, buf: &[u8]) -> crate::Result<()> {
+        let line = self.output.input_line_number();
+        self.write_token(buf, line)
+    }
+
+    /// The same as [`OutputState::write_all`], but `buf` is a single token (e.g. a quoted string
+    /// or a comment) which started on input `line`, used for emitting line synchronization
+    /// directives.
+    pub fn write_token(&mut self, buf: &[u8], line: Option<usize>) -> crate::Result<()> {
         if self.stack.is_empty() {
             log::trace!("Writing to output: {}", String::from_utf8_lossy(buf));
-            self.output.write_all(buf)?;
+            match line {
+                Some(line) => self.output.write_text(buf, line)?,
+                None => self.output.write_all(buf)?,
+            }
         } else {
             log::trace!(
                 "Writing to macro arg in stack: {:?}",
                 String::from_utf8_lossy(buf)
             );
 
-            let frame = self.stack.last_mut().expect("Stack not empty");
-            let arg_buffer = if let Some(arg_buffer) = frame.args.last_mut() {
-                arg_buffer
-            } else {
-                frame.args.push(Vec::new());
-                frame.args.last_mut().expect("At least one arg")
-            };
-
-            arg_buffer.extend(buf);
+            self.argument().extend(buf);
         }
         Ok(())
     }
+
+    /// Swap the text of the macro argument currently being collected with `text`, so that it can
+    /// be appended to without copying it from another buffer and then swapped back.
+    pub fn swap_argument(&mut self, text: &mut Vec<u8>) {
+        std::mem::swap(self.argument(), text);
+    }
+
+    /// The macro argument currently being collected, the stack must not be empty.
+    fn argument(&mut self) -> &mut Vec<u8> {
+        let frame = self.stack.last_mut().expect("Stack not empty");
+        if frame.args.is_empty() {
+            frame.args.push(self.buffers.take());
+        }
+        frame.args.last_mut().expect("At least one arg")
+    }
 }
 
 /// A reference counted reference to [`Output`] which can be cloned.
@@ -58,8 +142,32 @@ impl OutputRef {
         self.0.borrow_mut().undivert(buffer_number)
     }
 
-    pub fn stdout(&self) -> Rc<RefCell<dyn Write>> {
-        self.0.borrow().stdout()
+    pub fn undivert_file(&mut self, contents: &[u8]) -> Result<()> {
+        self.0.borrow_mut().undivert_file(contents)
+    }
+
+    pub fn flush_stdout(&mut self) -> std::io::Result<()> {
+        self.0.borrow_mut().flush_stdout()
+    }
+
+    pub fn write_text(&mut self, buf: &[u8], line: usize) -> std::io::Result<()> {
+        self.0.borrow_mut().write_text(buf, line)
+    }
+
+    /// Write `buf`, which continues an output line started by [`OutputRef::write_text`] (it does
+    /// not contain `<newline>`), so there is no line synchronization to check. Unlike the other
+    /// writes it does not use the input, so it can be called while the input is being read, see
+    /// [`crate::input::InputState::read_plain`].
+    pub fn write_continuation(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.borrow_mut().write_raw(buf)
+    }
+
+    pub fn for_each_diversion(&self, f: impl FnMut(i64, &[u8]) -> Result<()>) -> Result<()> {
+        self.0.borrow_mut().for_each_diversion(f)
+    }
+
+    fn input_line_number(&self) -> Option<usize> {
+        self.0.borrow().input.line_number()
     }
 }
 
@@ -74,31 +182,52 @@ impl Write for OutputRef {
 }
 
 /// Output that implements [`Write`] and will write to stdout if [`Output::divert_number`] is 0, to
-/// one of the [`Output::divert_buffers`] if [`Output::divert_number`] is greater than 0, and will
+/// one of the [`Output::diversions`] if [`Output::divert_number`] is greater than 0, and will
 /// discard if it is < 0.
-///
-/// NOTE: This currently uses an in-memory set of divert buffers, other implementations use
-/// temporary files, so this might change in the future, or become an optional feature.
 pub struct Output {
-    /// Divert buffers 1 through to 9. See [`DivertMacro`].
-    ///
-    /// NOTE: [`Rc`] and [`RefCell`] required because buffers can be diverted into each other via
-    /// [`Output`].
-    divert_buffers: [Rc<RefCell<DivertableBuffer>>; 9],
+    diversions: Diversions,
     /// See [`DivertMacro`].
     divert_number: i64,
     /// The real output, usually [`std::io::stdout`].
     stdout: Rc<RefCell<dyn Write>>,
     input: InputStateRef,
+    /// See [`Synclines`].
+    synclines: Synclines,
+}
+
+/// State used to emit the `#line NUM "FILE"` line synchronization directives, following the same
+/// algorithm as GNU m4. A check is only performed when a token starts a new output line, if the
+/// output line number is out of sync with the input line number where the token started, then a
+/// directive is emitted.
+struct Synclines {
+    /// The next token written will start a new line of output.
+    start_of_output_line: bool,
+    /// The input line number that the current line of output corresponds to. `None` if it has
+    /// been invalidated (by changing input or diversion), in which case the next directive also
+    /// includes the file name.
+    output_line: Option<usize>,
+    /// The value of [`crate::input::InputState::changes`] when the last token was written.
+    input_changes: usize,
+}
+
+impl Default for Synclines {
+    fn default() -> Self {
+        Self {
+            start_of_output_line: true,
+            output_line: None,
+            input_changes: 0,
+        }
+    }
 }
 
 impl Default for Output {
     fn default() -> Self {
         Self {
-            divert_buffers: Default::default(),
+            diversions: Diversions::default(),
             divert_number: Default::default(),
             stdout: Rc::new(RefCell::new(std::io::stdout())),
             input: InputStateRef::default(),
+            synclines: Synclines::default(),
         }
     }
 }
@@ -108,15 +237,12 @@ impl Output {
         Self {
             stdout,
             input,
-            divert_buffers: Default::default(),
+            diversions: Diversions::default(),
             divert_number: Default::default(),
+            synclines: Synclines::default(),
         }
     }
 
-    pub fn stdout(&self) -> Rc<RefCell<dyn Write>> {
-        self.stdout.clone()
-    }
-
     pub fn into_ref(self) -> OutputRef {
         OutputRef(Rc::new(RefCell::new(self)))
     }
@@ -126,124 +252,256 @@ impl Output {
     }
 
     pub fn divert_buffer_number(&self) -> Option<DivertBufferNumber> {
-        DivertBufferNumber::try_from(usize::try_from(self.divert_number).ok()?).ok()
+        DivertBufferNumber::try_from(self.divert_number).ok()
     }
 
+    /// Divert future output to `divert_number`, a negative number discards it.
     pub fn divert(&mut self, divert_number: i64) -> Result<()> {
-        if divert_number > 9 {
-            return Err(crate::error::Error::new(
-                crate::ErrorKind::InvalidDivertNumber(divert_number),
-            ));
-        }
         self.divert_number = divert_number;
+        self.synclines.output_line = None;
         Ok(())
     }
 
+    /// Undivert all of the diversions, in numerical order.
     pub fn undivert_all(&mut self) -> Result<()> {
-        for buffer_number in (1..=self.divert_buffers.len()).map(DivertBufferNumber::try_from) {
-            self.undivert(buffer_number?)?;
+        if self.divert_number == 0 {
+            // As at exit, see [`Diversions::write_all_to`]. There is no current diversion to skip.
+            self.diversions
+                .write_all_to(&mut *self.stdout.borrow_mut())?;
+            self.synclines.output_line = None;
+            return Ok(());
+        }
+        for buffer_number in self.diversions.numbers() {
+            self.undivert(buffer_number)?;
         }
         Ok(())
     }
 
     pub fn undivert(&mut self, buffer_number: DivertBufferNumber) -> Result<()> {
         if self.divert_buffer_number() == Some(buffer_number) {
-            // It would cause a panic anyway on buffer.borrow_mut()
-            log::warn!("Skipping recursive divert");
+            log::debug!("Output::undivert({buffer_number:?}): Skipping the current diversion.");
             return Ok(());
         }
-        let buffer = self.divert_buffers[buffer_number.index()].clone();
-        let mut buffer = buffer.borrow_mut();
-        buffer.0.rewind()?;
-        let n = std::io::copy(&mut buffer.0, self)?;
-        log::debug!("Output::undivert({buffer_number:?}): Undiverted {n} bytes.");
-        buffer.0.get_mut().clear();
-        debug_assert!(buffer.0.get_ref().is_empty());
+        let Some(mut buffer) = self.diversions.remove(buffer_number) else {
+            return Ok(());
+        };
+        // The diverted text already contains any line synchronization directives that it needs.
+        buffer.for_each_chunk(|chunk| self.write_raw(chunk))?;
+        log::debug!("Output::undivert({buffer_number:?}): Undiverted.");
+        self.synclines.output_line = None;
         Ok(())
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub struct DivertBufferNumber(usize);
+    /// Copy the `contents` of a file to the current diversion, the same as undiverting a
+    /// diversion buffer, see [`crate::macros::UndivertMacro`].
+    pub fn undivert_file(&mut self, contents: &[u8]) -> Result<()> {
+        self.write_raw(contents)?;
+        self.synclines.output_line = None;
+        Ok(())
+    }
 
-impl DivertBufferNumber {
-    fn index(&self) -> usize {
-        self.0 - 1
+    /// Flush the real output, even when the current diversion is not 0, so that anything already
+    /// written comes before the output of a child process, see [`crate::macros::SyscmdMacro`].
+    pub fn flush_stdout(&mut self) -> std::io::Result<()> {
+        self.stdout.borrow_mut().flush()
     }
-}
 
-impl std::fmt::Display for DivertBufferNumber {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+    /// Call `f` with the number and contents of each non-empty diversion buffer, in numerical
+    /// order.
+    pub fn for_each_diversion(&mut self, f: impl FnMut(i64, &[u8]) -> Result<()>) -> Result<()> {
+        self.diversions.for_each(f)
     }
-}
 
-impl TryFrom<usize> for DivertBufferNumber {
-    type Error = crate::Error;
+    /// Write a token which started on input `line`, emitting a line synchronization directive
+    /// beforehand if required, see [`Synclines`].
+    pub fn write_text(&mut self, buf: &[u8], line: usize) -> std::io::Result<()> {
+        if !self.input.sync_lines() || self.divert_number < 0 {
+            return self.write_raw(buf);
+        }
 
-    fn try_from(value: usize) -> std::prelude::v1::Result<Self, Self::Error> {
-        if !(1..=9).contains(&value) {
-            return Err(
-                crate::Error::new(crate::ErrorKind::Parsing).add_context(format!(
-                    "Unexpected buffer number: {value}. Needs to be from 1 to 9"
-                )),
-            );
+        let input_changes = self.input.changes();
+        if self.synclines.input_changes != input_changes {
+            self.synclines.input_changes = input_changes;
+            self.synclines.output_line = None;
         }
-        Ok(Self(value))
-    }
-}
 
-impl Write for Output {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let output: &mut dyn Write = match self.divert_number {
-            0 => &mut *self.stdout.borrow_mut(),
-            1..=9 => {
-                &mut self.divert_buffers[self
-                    .divert_buffer_number()
-                    .expect("valid divert buffer number")
-                    .index()]
-                .borrow_mut()
-                .0
+        if self.synclines.start_of_output_line {
+            self.synclines.start_of_output_line = false;
+            let output_line = self.synclines.output_line.map(|l| l + 1);
+            if output_line != Some(line) {
+                let mut directive = format!("#line {line}").into_bytes();
+                if output_line.is_none() {
+                    if let Some(name) = self.input.name() {
+                        directive.extend(b" \"");
+                        directive.extend(name);
+                        directive.extend(b"\"");
+                    }
+                }
+                if self.input.crlf() {
+                    directive.push(b'\r');
+                }
+                directive.push(b'\n');
+                self.write_raw(&directive)?;
             }
-            i if i < 0 => return Ok(buf.len()),
-            _ => unreachable!("unreachable, was checked in Self::divert()"),
-        };
+            self.synclines.output_line = Some(line);
+        }
 
+        // Newlines inside the token still advance the output line, but only a trailing newline
+        // causes the next token to be checked.
+        let mut newlines = buf.iter().filter(|c| **c == b'\n').count();
+        if buf.last() == Some(&b'\n') {
+            self.synclines.start_of_output_line = true;
+            newlines -= 1;
+        }
+        self.synclines.output_line = self.synclines.output_line.map(|l| l + newlines);
+
+        self.write_raw(buf)
+    }
+
+    /// Write directly to the current diversion, without any line synchronization.
+    fn write_raw(&mut self, buf: &[u8]) -> std::io::Result<()> {
         log::debug!(
             "writing[{}] {:?}",
             self.divert_number,
             String::from_utf8_lossy(buf)
         );
 
-        if self.input.sync_lines() {
-            let mut n = 0;
-            for c in buf {
-                n += output.write(&[*c])?;
-                if *c == b'\n' {
-                    self.input.emit_syncline(output, true)?;
-                }
-            }
-            Ok(n)
-        } else {
-            output.write(buf)
+        match self.divert_number {
+            0 => self.stdout.borrow_mut().write_all(buf),
+            i if i < 0 => Ok(()),
+            i => self.diversions.write_all(DivertBufferNumber(i), buf),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.input.line_number() {
+            Some(line) => self.write_text(buf, line)?,
+            None => self.write_raw(buf)?,
         }
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
         match self.divert_number {
             0 => self.stdout.borrow_mut().flush(),
-            1..=9 => self.divert_buffers[self
-                .divert_buffer_number()
-                .expect("valid divert buffer number")
-                .index()]
-            .borrow_mut()
-            .0
-            .flush(),
-            i if i < 0 => Ok(()),
-            _ => unreachable!("unreachable, was checked in Self::divert()"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The `stdout` passed to [`crate::run`], buffered according to an [`OutputBuffering`].
+pub(crate) struct BufferedStdout<W: Write> {
+    stdout: W,
+    buffering: OutputBuffering,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> BufferedStdout<W> {
+    pub fn new(stdout: W, buffering: OutputBuffering) -> Self {
+        Self {
+            stdout,
+            buffering,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.stdout.write_all(&self.buffer)?;
+            self.buffer.clear();
         }
+        Ok(())
     }
 }
 
-#[derive(Default, Clone)]
-pub(crate) struct DivertableBuffer(std::io::Cursor<Vec<u8>>);
+impl<W: Write> Write for BufferedStdout<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.buffering {
+            OutputBuffering::Unbuffered => {
+                self.stdout.write_all(buf)?;
+                self.stdout.flush()?;
+            }
+            OutputBuffering::Line => match buf.iter().rposition(|c| *c == b'\n') {
+                Some(end) => {
+                    self.write_buffer()?;
+                    self.stdout.write_all(&buf[..=end])?;
+                    self.stdout.flush()?;
+                    self.buffer.extend(&buf[end + 1..]);
+                }
+                None => self.buffer.extend(buf),
+            },
+            OutputBuffering::Block(size) => {
+                self.buffer.extend(buf);
+                if self.buffer.len() >= size {
+                    self.write_buffer()?;
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Only [`OutputBuffering::Line`] needs to look at the contents, otherwise `bufs` are passed
+    /// on together (unless they fit in the buffer).
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        match self.buffering {
+            OutputBuffering::Line => {
+                let buf = bufs.iter().find(|buf| !buf.is_empty());
+                self.write(buf.map_or(&[], |buf| buf))
+            }
+            OutputBuffering::Block(size) if self.buffer.len() + len < size => {
+                bufs.iter().for_each(|buf| self.buffer.extend(&**buf));
+                Ok(len)
+            }
+            OutputBuffering::Unbuffered | OutputBuffering::Block(_) => {
+                self.write_buffer()?;
+                let n = self.stdout.write_vectored(bufs)?;
+                if self.buffering == OutputBuffering::Unbuffered {
+                    self.stdout.flush()?;
+                }
+                Ok(n)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        self.stdout.flush()
+    }
+}
+
+impl<W: Write> Drop for BufferedStdout<W> {
+    fn drop(&mut self) {
+        // Errors have already been reported by the final flush, see [`crate::run_impl`].
+        let _ = self.flush();
+    }
+}
+
+/// The `stderr` passed to [`crate::run`], which flushes [`BufferedStdout`] before anything is
+/// written to it. So a diagnostic comes after all of the output which preceded it, when both are
+/// written to the same place.
+pub(crate) struct OrderedStderr<W: Write> {
+    stderr: W,
+    stdout: Rc<RefCell<dyn Write>>,
+}
+
+impl<W: Write> OrderedStderr<W> {
+    pub fn new(stderr: W, stdout: Rc<RefCell<dyn Write>>) -> Self {
+        Self { stderr, stdout }
+    }
+}
+
+impl<W: Write> Write for OrderedStderr<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Ok(mut stdout) = self.stdout.try_borrow_mut() {
+            stdout.flush()?;
+        }
+        self.stderr.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stderr.flush()
+    }
+}
@@ -0,0 +1,59 @@
+//! Per macro profiling, enabled using `--profile`, see [`Profile`].
+
+use std::{io::Write, time::Duration};
+
+use crate::{hash::FxHashMap, lexer::MacroName};
+
+/// What has been recorded for one macro, see [`Profile`].
+#[derive(Default, Clone, Copy)]
+struct MacroProfile {
+    calls: u64,
+    time: Duration,
+    bytes: usize,
+}
+
+/// The number of calls, the time spent in the macro itself and the number of bytes of expansion
+/// (the text pushed back to be rescanned) for each macro, see [`crate::Args::profile`].
+///
+/// The time does not include rescanning the expansion, any macros called by it are recorded
+/// separately. A macro called through `indir` or `builtin` is recorded as part of that call.
+#[derive(Default)]
+pub(crate) struct Profile {
+    macros: FxHashMap<MacroName, MacroProfile>,
+}
+
+impl Profile {
+    /// Record a call to the macro `name`.
+    pub fn record(&mut self, name: &MacroName, time: Duration, bytes: usize) {
+        let profile = match self.macros.get_mut(name) {
+            Some(profile) => profile,
+            None => self.macros.entry(name.clone()).or_default(),
+        };
+        profile.calls += 1;
+        profile.time += time;
+        profile.bytes += bytes;
+    }
+
+    /// Write the report, one line per macro with the most time spent first.
+    pub fn write_report(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        let mut macros: Vec<_> = self.macros.iter().collect();
+        macros.sort_by(|(a_name, a), (b_name, b)| b.time.cmp(&a.time).then(a_name.cmp(b_name)));
+
+        let mut report = Vec::new();
+        writeln!(
+            report,
+            "m4profile: {:>10} {:>12} {:>12}  macro",
+            "calls", "time (ms)", "bytes"
+        )?;
+        for (name, profile) in macros {
+            writeln!(
+                report,
+                "m4profile: {:>10} {:>12.3} {:>12}  {name}",
+                profile.calls,
+                profile.time.as_secs_f64() * 1000.0,
+                profile.bytes,
+            )?;
+        }
+        w.write_all(&report)
+    }
+}
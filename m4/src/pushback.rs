@@ -0,0 +1,165 @@
+//! The text pushed back in front of an input, see [`Pushback`].
+
+/// Text to be read before the rest of an [`crate::input::Input`], such as the expansion of a
+/// macro. It is a stack of segments which are each read from front to back, the top one first.
+/// Pushing text back in front of what is still to be read never moves the rest of it: an owned
+/// buffer becomes a new segment without being copied, and short text is copied into the space
+/// left in front of the top segment by the characters already read from it.
+///
+/// Pushed back text is rescanned, it does not advance [`crate::input::Input::line_number`] (so
+/// `__line__` reports the line of the input where the macro was called).
+#[derive(Default)]
+pub struct Pushback {
+    /// Each segment has at least one character still to be read.
+    segments: Vec<Segment>,
+    /// The number of characters still to be read from all of the segments.
+    len: usize,
+    /// Buffers of segments which have been read, reused for new segments.
+    spare: Vec<Vec<u8>>,
+}
+
+struct Segment {
+    text: Vec<u8>,
+    /// The next character to be read from `text`, the characters before it have been read already
+    /// and their space is reused by [`Pushback::push_slice`].
+    position: usize,
+}
+
+/// Text at most this long is copied into the space in front of the top segment, or into a new
+/// segment with this much space in front for more, rather than becoming a segment of its own.
+const SEGMENT_HEADROOM: usize = 64;
+
+/// At most this many buffers are kept for reuse by a [`Pushback`].
+const SPARE_SEGMENTS: usize = 8;
+
+/// Buffers which have grown larger than this are freed rather than reused, so that one large
+/// expansion does not hold on to its memory.
+const SPARE_MAX_CAPACITY: usize = 64 * 1024;
+
+impl Pushback {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of characters still to be read.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Read the next character.
+    pub fn pop(&mut self) -> Option<u8> {
+        let segment = self.segments.last_mut()?;
+        let c = segment.text[segment.position];
+        segment.position += 1;
+        self.len -= 1;
+        if segment.position == segment.text.len() {
+            self.pop_segment();
+        }
+        Some(c)
+    }
+
+    /// Push back a single character, to be read next.
+    pub fn push(&mut self, c: u8) {
+        self.push_slice(&[c]);
+    }
+
+    /// Push back a copy of `text`, to be read next.
+    pub fn push_slice(&mut self, text: &[u8]) {
+        if text.is_empty() {
+            return;
+        }
+        self.len += text.len();
+        if let Some(segment) = self
+            .segments
+            .last_mut()
+            .filter(|segment| segment.position >= text.len())
+        {
+            segment.position -= text.len();
+            segment.text[segment.position..segment.position + text.len()].copy_from_slice(text);
+            return;
+        }
+        let headroom = if text.len() <= SEGMENT_HEADROOM {
+            SEGMENT_HEADROOM
+        } else {
+            0
+        };
+        let mut buffer = self.spare.pop().unwrap_or_default();
+        buffer.resize(headroom, 0);
+        buffer.extend_from_slice(text);
+        self.segments.push(Segment {
+            text: buffer,
+            position: headroom,
+        });
+    }
+
+    /// Push back `text`, to be read next. Unless it is short, it becomes a segment of its own
+    /// without being copied.
+    pub fn push_owned(&mut self, text: Vec<u8>) {
+        if text.len() <= SEGMENT_HEADROOM {
+            self.push_slice(&text);
+            self.give_spare(text);
+            return;
+        }
+        self.len += text.len();
+        self.segments.push(Segment { text, position: 0 });
+    }
+
+    /// Pass the characters to be read next to `write` for as long as they are plain, see
+    /// [`crate::input::InputState::read_plain`].
+    pub fn read_plain(
+        &mut self,
+        mut plain_len: impl FnMut(&[u8]) -> usize,
+        mut write: impl FnMut(&[u8]) -> crate::error::Result<()>,
+    ) -> crate::error::Result<()> {
+        while let Some(segment) = self.segments.last_mut() {
+            let available = &segment.text[segment.position..];
+            let n = plain_len(available);
+            write(&available[..n])?;
+            segment.position += n;
+            self.len -= n;
+            if segment.position < segment.text.len() {
+                return Ok(());
+            }
+            self.pop_segment();
+        }
+        Ok(())
+    }
+
+    /// The first `n` characters to be read, or all of them if there are fewer.
+    pub fn prefix(&self, n: usize) -> Vec<u8> {
+        let mut prefix = Vec::with_capacity(n.min(self.len));
+        for segment in self.segments.iter().rev() {
+            let remaining = n - prefix.len();
+            if remaining == 0 {
+                break;
+            }
+            let available = &segment.text[segment.position..];
+            prefix.extend_from_slice(&available[..remaining.min(available.len())]);
+        }
+        prefix
+    }
+
+    fn pop_segment(&mut self) {
+        if let Some(segment) = self.segments.pop() {
+            self.give_spare(segment.text);
+        }
+    }
+
+    fn give_spare(&mut self, mut buffer: Vec<u8>) {
+        if self.spare.len() < SPARE_SEGMENTS
+            && buffer.capacity() > 0
+            && buffer.capacity() <= SPARE_MAX_CAPACITY
+        {
+            buffer.clear();
+            self.spare.push(buffer);
+        }
+    }
+}
+
+impl From<Vec<u8>> for Pushback {
+    fn from(text: Vec<u8>) -> Self {
+        let mut pushback = Self::default();
+        pushback.push_owned(text);
+        pushback
+    }
+}
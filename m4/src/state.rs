@@ -1,24 +1,71 @@
-use std::{cell::RefCell, collections::HashMap, io::Write, process::ExitStatus, rc::Rc};
+use std::{cell::RefCell, io::Write, path::PathBuf, process::ExitStatus, rc::Rc};
 
 use crate::{
+    debug::{debug_message, DebugFile, DebugFlags},
+    diagnostics::{Diagnostic, Severity},
+    hash::FxHashMap,
     input::{Input, InputState, InputStateRef},
     lexer::{is_alphnumeric, MacroName, ParseConfig},
     macros::{trace::Trace, BuiltinMacro, MacroDefinition},
     output::{Output, OutputState},
-    EOF,
+    profile::Profile,
+    warning::{WarningAction, WarningCategory, WarningControls},
+    DiagnosticsFormat, FatalWarnings, Mode, Verbosity,
 };
 
 pub struct State {
-    pub macro_definitions: HashMap<MacroName, Vec<Rc<MacroDefinition>>>,
+    /// The definition stack of each macro, builtins included because they can be renamed,
+    /// undefined or pushed over just like user macros. It is looked up for every word which could
+    /// be a macro name, hence the [`FxHashMap`].
+    pub macro_definitions: FxHashMap<MacroName, Vec<Rc<MacroDefinition>>>,
     pub parse_config: ParseConfig,
     /// Whether the process should exit with an error once processing has completed.
     pub exit_error: bool,
-    /// See [`M4wrapMacro`].
-    pub m4wrap: Vec<Vec<u8>>,
+    /// The text saved by [`crate::macros::M4wrapMacro`], see [`Input::wrapped`].
+    pub m4wrap: Vec<Input>,
     pub last_syscmd_status: Option<ExitStatus>,
     pub output: OutputState,
     pub input: InputStateRef,
     pub trace: Trace,
+    /// See [`crate::Args::include`].
+    pub include_paths: Vec<PathBuf>,
+    /// See [`crate::Args::nesting_limit`].
+    pub nesting_limit: usize,
+    /// See [`crate::Args::expansion_limit`].
+    pub expansion_limit: usize,
+    /// See [`crate::Args::fatal_warnings`].
+    pub fatal_warnings: FatalWarnings,
+    /// See [`crate::Args::warnings`].
+    pub warnings: WarningControls,
+    /// See [`crate::Args::diagnostics_format`].
+    pub diagnostics_format: DiagnosticsFormat,
+    /// The macro whose expansion is being evaluated, for the `macros` of
+    /// [`DiagnosticsFormat::Json`]. It is only recorded with that format.
+    pub evaluating: Option<MacroName>,
+    /// See [`crate::Args::debug_flags`].
+    pub debug_flags: DebugFlags,
+    /// See [`crate::Args::debugfile`].
+    pub debug_file: DebugFile,
+    /// See [`crate::Args::warn_macro_sequence`].
+    pub warn_macro_sequence: Option<regex::bytes::Regex>,
+    /// See [`crate::Args::interactive`].
+    pub interactive: bool,
+    /// See [`crate::Args::memory_map`], used for included files.
+    pub memory_map: crate::MemoryMap,
+    /// See [`crate::Args::safer`].
+    pub safer: bool,
+    /// See [`crate::Args::utf8`].
+    pub utf8: bool,
+    /// See [`crate::Args::profile`].
+    pub profile: Option<Profile>,
+    /// See [`crate::Args::verbosity`].
+    pub verbosity: Verbosity,
+    /// See [`crate::Args::mode`], use [`State::set_mode`] to change it.
+    pub mode: Mode,
+    /// The number of macro calls so far, used for [`StackFrame::call_id`].
+    pub macro_calls: usize,
+    /// See [`crate::Args::program_name`].
+    pub program_name: Vec<u8>,
 }
 
 impl State {
@@ -29,7 +76,7 @@ impl State {
     ) -> crate::Result<Self> {
         let input_state = InputStateRef::new(InputState::new(line_synchronization));
         for i in input {
-            input_state.input_push(i, &mut *stdout.borrow_mut())?;
+            input_state.input_push(i);
         }
         Ok(Self {
             output: OutputState {
@@ -41,30 +88,318 @@ impl State {
         })
     }
 
+    /// Print a warning of `category` to `stderr`. Depending on [`State::warnings`] and
+    /// [`State::fatal_warnings`] it may be ignored, or it may also cause the process to exit with
+    /// an error once processing has completed, or to halt immediately.
+    pub fn warning(
+        &mut self,
+        stderr: &mut dyn Write,
+        category: WarningCategory,
+        message: impl std::fmt::Display,
+    ) -> crate::Result<()> {
+        self.report_warning(stderr, category, "Warning: ", message)
+    }
+
+    /// A [`State::warning`] which is printed without the `Warning:` label, such as for `dumpdef`
+    /// of an undefined macro.
+    pub fn warning_message(
+        &mut self,
+        stderr: &mut dyn Write,
+        category: WarningCategory,
+        message: impl std::fmt::Display,
+    ) -> crate::Result<()> {
+        self.report_warning(stderr, category, "", message)
+    }
+
+    fn report_warning(
+        &mut self,
+        stderr: &mut dyn Write,
+        category: WarningCategory,
+        label: &str,
+        message: impl std::fmt::Display,
+    ) -> crate::Result<()> {
+        let action = self.warnings.get(category).unwrap_or({
+            if self.fatal_warnings >= FatalWarnings::ExitStatus {
+                WarningAction::Error
+            } else {
+                WarningAction::Warn
+            }
+        });
+        if action == WarningAction::Ignore {
+            return Ok(());
+        }
+        let severity = match action {
+            WarningAction::Error => Severity::Error,
+            _ => Severity::Warning,
+        };
+        self.write_diagnostic(stderr, severity, category.name(), label, message)?;
+        if action == WarningAction::Error {
+            self.exit_error = true;
+            return self.halt_if_fatal();
+        }
+        Ok(())
+    }
+
+    /// Print an error which is diagnosed like a [`State::warning_message`] (such as a file which
+    /// can not be included), processing continues (unless [`FatalWarnings::Halt`]) but the
+    /// process will exit with an error once it has completed. `code` is used for
+    /// [`DiagnosticsFormat::Json`], like [`crate::Error::code`].
+    pub fn error_message(
+        &mut self,
+        stderr: &mut dyn Write,
+        code: &str,
+        message: impl std::fmt::Display,
+    ) -> crate::Result<()> {
+        self.write_diagnostic(stderr, Severity::Error, code, "", message)?;
+        self.exit_error = true;
+        self.halt_if_fatal()
+    }
+
+    fn write_diagnostic(
+        &self,
+        stderr: &mut dyn Write,
+        severity: Severity,
+        code: &str,
+        label: &str,
+        message: impl std::fmt::Display,
+    ) -> std::io::Result<()> {
+        if self.diagnostics_format == DiagnosticsFormat::Json {
+            return self.write_json_diagnostic(stderr, severity, code, message);
+        }
+        // GNU m4 includes the location where the warning occurred, see
+        // [`State::diagnostic_location`].
+        let mut line = self.diagnostic_location();
+        line.extend(b": ");
+        writeln!(line, "{label}{message}")?;
+        stderr.write_all(&line)
+    }
+
+    fn write_json_diagnostic(
+        &self,
+        stderr: &mut dyn Write,
+        severity: Severity,
+        code: &str,
+        message: impl std::fmt::Display,
+    ) -> std::io::Result<()> {
+        Diagnostic {
+            severity,
+            code,
+            message: &message.to_string(),
+            location: self.input.location().as_ref(),
+            included_from: &self.input.included_from(),
+            macros: &self.macro_stack(),
+        }
+        .write_json(stderr)
+    }
+
+    /// The macros being expanded, outermost first: those collecting arguments in
+    /// [`OutputState::stack`], followed by [`State::evaluating`].
+    pub fn macro_stack(&self) -> Vec<MacroName> {
+        self.output
+            .stack
+            .iter()
+            .map(|frame| frame.definition.parse_config.name.clone())
+            .chain(self.evaluating.clone())
+            .collect()
+    }
+
+    /// The prefix of a diagnostic, `m4:file:line` with the current location in [`Mode::Gnu`] like
+    /// GNU m4, otherwise just `m4`.
+    pub fn diagnostic_location(&self) -> Vec<u8> {
+        let mut location = b"m4".to_vec();
+        if self.mode == Mode::Gnu {
+            if let (Some(name), Some(number)) = (self.input.name(), self.input.line_number()) {
+                location.push(b':');
+                location.extend(name);
+                location.extend(format!(":{number}").as_bytes());
+            }
+        }
+        location
+    }
+
+    /// A [`State::warning`] whose effect can be retrieved otherwise, such as a builtin being called
+    /// with too few or excess arguments. It is suppressed entirely with [`Verbosity::Quiet`],
+    /// unless `category` was specified in [`State::warnings`].
+    pub fn retrievable_warning(
+        &mut self,
+        stderr: &mut dyn Write,
+        category: WarningCategory,
+        message: impl std::fmt::Display,
+    ) -> crate::Result<()> {
+        if self.verbosity == Verbosity::Quiet && self.warnings.get(category).is_none() {
+            return Ok(());
+        }
+        self.warning(stderr, category, message)
+    }
+
+    /// Warn about each (non-empty) match of [`State::warn_macro_sequence`] in the definition of the
+    /// macro `name`.
+    pub fn check_macro_sequence(
+        &mut self,
+        stderr: &mut dyn Write,
+        name: &MacroName,
+        definition: &[u8],
+    ) -> crate::Result<()> {
+        let Some(regex) = &self.warn_macro_sequence else {
+            return Ok(());
+        };
+        let sequences: Vec<String> = regex
+            .find_iter(definition)
+            .filter(|m| !m.is_empty())
+            .map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned())
+            .collect();
+        for sequence in sequences {
+            self.warning(
+                stderr,
+                WarningCategory::MacroSequence,
+                format!("definition of `{name}' contains sequence `{sequence}'"),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Change the [`Mode`], the builtins which are not defined in `mode` are removed (see
+    /// [`BuiltinMacro::is_defined_in`]), such as the GNU extensions in [`Mode::Traditional`].
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.macro_definitions.retain(|_, definitions| {
+            !definitions.iter().any(|definition| {
+                definition
+                    .implementation
+                    .builtin()
+                    .is_some_and(|builtin| !builtin.is_defined_in(mode))
+            })
+        });
+    }
+
+    /// Rename every builtin macro to have the `m4_` prefix (e.g. `define` becomes `m4_define`),
+    /// the unprefixed names are no longer defined. See [`crate::Args::prefix_builtins`].
+    pub fn prefix_builtins(&mut self) {
+        self.macro_definitions = std::mem::take(&mut self.macro_definitions)
+            .into_iter()
+            .map(|(name, definitions)| {
+                let is_builtin = definitions
+                    .last()
+                    .is_some_and(|definition| definition.implementation.builtin().is_some());
+                if !is_builtin {
+                    return (name, definitions);
+                }
+                let name = MacroName([b"m4_", name.as_bytes()].concat().into());
+                let definitions = definitions
+                    .iter()
+                    .map(|definition| match definition.implementation.builtin() {
+                        Some(builtin) => {
+                            Rc::new(MacroDefinition::new_builtin(name.clone(), builtin))
+                        }
+                        None => definition.clone(),
+                    })
+                    .collect();
+                (name, definitions)
+            })
+            .collect();
+    }
+
+    /// Write the [`Profile`] report to `stderr`, if profiling is enabled.
+    pub fn write_profile(&self, stderr: &mut dyn Write) -> std::io::Result<()> {
+        match &self.profile {
+            Some(profile) => profile.write_report(stderr),
+            None => Ok(()),
+        }
+    }
+
+    /// Print a non-fatal error to `stderr` as `m4: message`, without the location even in
+    /// [`Mode::Gnu`]. Processing continues (unless [`FatalWarnings::Halt`]) but the process will
+    /// exit with an error once it has completed. `code` is used for [`DiagnosticsFormat::Json`].
+    pub fn error(
+        &mut self,
+        stderr: &mut dyn Write,
+        code: &str,
+        message: impl std::fmt::Display,
+    ) -> crate::Result<()> {
+        match self.diagnostics_format {
+            DiagnosticsFormat::Text => writeln!(stderr, "m4: {message}")?,
+            DiagnosticsFormat::Json => {
+                self.write_json_diagnostic(stderr, Severity::Error, code, message)?
+            }
+        }
+        self.exit_error = true;
+        self.halt_if_fatal()
+    }
+
+    fn halt_if_fatal(&self) -> crate::Result<()> {
+        if self.fatal_warnings == FatalWarnings::Halt {
+            return Err(crate::Error::new(crate::ErrorKind::Exit(1)));
+        }
+        Ok(())
+    }
+
+    /// Push `input` to be read next, see [`DebugFlags::INPUT`].
+    pub fn input_push(&mut self, input: Input, stderr: &mut dyn Write) -> crate::Result<()> {
+        if self.debug_flags.contains(DebugFlags::INPUT) {
+            debug_message(
+                self,
+                stderr,
+                format_args!("input read from {}", String::from_utf8_lossy(input.name())),
+            )?;
+        }
+        self.input.input_push(input);
+        Ok(())
+    }
+
+    /// Pop the current input once it has been read to the end, see [`DebugFlags::INPUT`].
+    pub fn input_pop(&mut self, stderr: &mut dyn Write) -> crate::Result<()> {
+        self.input.input_pop();
+        if self.debug_flags.contains(DebugFlags::INPUT) {
+            match (self.input.name(), self.input.line_number()) {
+                (Some(name), Some(line)) => debug_message(
+                    self,
+                    stderr,
+                    format_args!(
+                        "input reverted to {}, line {line}",
+                        String::from_utf8_lossy(&name)
+                    ),
+                )?,
+                _ => debug_message(self, stderr, "input exhausted")?,
+            }
+        }
+        Ok(())
+    }
+
     /// Attempt to parse `state.input` as a macro name, into `token`. If it is a current macro name in
     /// `state.macro_definitions`, then it will return `Some` of [`MacroDefinition`].
     pub fn parse_macro(
         &mut self,
-        mut c: u8,
+        c: u8,
         token: &mut Vec<u8>,
     ) -> crate::Result<Option<Rc<MacroDefinition>>> {
         token.clear();
         token.push(c);
 
-        loop {
-            c = self.input.get_next_character()?;
-            if !(is_alphnumeric(c) || c == b'_') {
+        let word_regex = self.parse_config.word_regex.as_ref();
+        while let Some(c) = self.input.get_next_character()? {
+            let is_word = match word_regex {
+                Some(word_regex) => {
+                    token.push(c);
+                    let is_word = word_regex.is_match(token);
+                    token.pop();
+                    is_word
+                }
+                None => is_alphnumeric(c) || c == b'_',
+            };
+            if !is_word {
+                self.input.pushback_character(c);
                 break;
             }
             token.push(c)
         }
-        if c != EOF {
-            self.input.pushback_character(c);
-        }
 
+        let name = match word_regex {
+            Some(word_regex) => word_regex.name(token),
+            None => token.as_slice(),
+        };
         Ok(self
             .macro_definitions
-            .get(&MacroName::try_from_slice(token).expect("valid macro name"))
+            .get(name)
             .map(|v| v.last())
             .unwrap_or_default()
             .cloned())
@@ -103,22 +438,60 @@ impl Default for State {
             output: OutputState::default(),
             input: InputStateRef::default(),
             trace: Trace::default(),
+            include_paths: Vec::new(),
+            nesting_limit: crate::DEFAULT_NESTING_LIMIT,
+            expansion_limit: 0,
+            fatal_warnings: FatalWarnings::default(),
+            warnings: WarningControls::default(),
+            diagnostics_format: DiagnosticsFormat::default(),
+            evaluating: None,
+            debug_flags: DebugFlags::default(),
+            debug_file: DebugFile::default(),
+            warn_macro_sequence: None,
+            interactive: false,
+            memory_map: crate::MemoryMap::default(),
+            safer: false,
+            utf8: false,
+            profile: None,
+            verbosity: Verbosity::default(),
+            mode: Mode::default(),
+            macro_calls: 0,
+            program_name: b"m4".to_vec(),
         }
     }
 }
 
+/// The arguments of a macro call. Most calls have very few arguments, so these are stored inline
+/// in the [`StackFrame`] rather than allocating, and the storage for more of them is reused, see
+/// [`crate::output::BufferPool::take_arguments`].
+pub type Arguments = smallvec::SmallVec<[Vec<u8>; 3]>;
+
 pub struct StackFrame {
     pub parenthesis_level: usize,
-    pub args: Vec<Vec<u8>>,
+    /// The arguments collected so far. They are written while the input is streamed (and may
+    /// contain the output of other macros), so they can not borrow from the input, but a quoted
+    /// string is appended to the argument directly. The frame is moved into
+    /// [`crate::macros::MacroImplementation::evaluate`], so a macro which stores an argument (for
+    /// example `define`) takes it without copying, and so does one which expands to an argument
+    /// (for example `ifelse`), see [`crate::input::InputState::pushback_string`].
+    pub args: Arguments,
     pub definition: Rc<MacroDefinition>,
+    /// Unique identifier for this macro call, see [`crate::debug::DebugFlags::CALL_ID`].
+    pub call_id: usize,
 }
 
 impl StackFrame {
-    pub fn new(parenthesis_level: usize, definition: Rc<MacroDefinition>) -> Self {
+    pub fn new(
+        parenthesis_level: usize,
+        definition: Rc<MacroDefinition>,
+        call_id: usize,
+        args: Arguments,
+    ) -> Self {
         Self {
             parenthesis_level,
-            args: Vec::new(),
+            args,
             definition,
+            call_id,
         }
     }
 }
@@ -0,0 +1,130 @@
+//! Named warning categories, which can be individually controlled using `--warning`, see
+//! [`WarningControls`].
+
+/// A named category of warnings, see [`WarningControls`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningCategory {
+    /// `undefined-macro`: A macro which is not defined was named, such as by `dumpdef`, `indir`
+    /// or `builtin`.
+    UndefinedMacro,
+    /// `excess-args`: A builtin was called with more arguments than it uses.
+    ExcessArguments,
+    /// `too-few-args`: A builtin was called with fewer arguments than it needs.
+    TooFewArguments,
+    /// `eof-in-quote`: An included file ended inside a quoted string, a comment or an argument
+    /// list, which continues in the including file.
+    EofInQuote,
+    /// `macro-sequence`: A macro definition contains a match of
+    /// [`crate::Args::warn_macro_sequence`].
+    MacroSequence,
+    /// `numeric`: A numeric argument (or an `eval` expression) which is empty, invalid or out of
+    /// range.
+    Numeric,
+    /// `regex`: A regular expression which is invalid, or a replacement which refers to a group it
+    /// does not have.
+    Regex,
+    /// `other`: Every other warning.
+    Other,
+}
+
+impl WarningCategory {
+    /// All of the categories, in the order they are documented.
+    pub const ALL: [Self; 8] = [
+        Self::UndefinedMacro,
+        Self::ExcessArguments,
+        Self::TooFewArguments,
+        Self::EofInQuote,
+        Self::MacroSequence,
+        Self::Numeric,
+        Self::Regex,
+        Self::Other,
+    ];
+
+    /// The name used for `--warning`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UndefinedMacro => "undefined-macro",
+            Self::ExcessArguments => "excess-args",
+            Self::TooFewArguments => "too-few-args",
+            Self::EofInQuote => "eof-in-quote",
+            Self::MacroSequence => "macro-sequence",
+            Self::Numeric => "numeric",
+            Self::Regex => "regex",
+            Self::Other => "other",
+        }
+    }
+
+    /// The category named `name`, see [`WarningCategory::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+    }
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|category| *category == self)
+            .expect("all categories are listed")
+    }
+}
+
+/// What happens when a warning of a [`WarningCategory`] occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningAction {
+    /// The warning is not printed.
+    Ignore,
+    /// The warning is printed, processing continues and the exit status is unaffected.
+    Warn,
+    /// The warning is printed and the exit status will be non-zero, like `-E` for just this
+    /// category. Processing continues unless `-E` was specified twice.
+    Error,
+}
+
+/// The action for each [`WarningCategory`] specified using `--warning`, the categories which are
+/// not specified follow [`crate::Args::fatal_warnings`] and [`crate::Args::verbosity`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarningControls {
+    actions: [Option<WarningAction>; WarningCategory::ALL.len()],
+}
+
+impl WarningControls {
+    /// The action specified for `category`, if any.
+    pub fn get(&self, category: WarningCategory) -> Option<WarningAction> {
+        self.actions[category.index()]
+    }
+
+    /// Specify the action for `category`.
+    pub fn set(&mut self, category: WarningCategory, action: WarningAction) {
+        self.actions[category.index()] = Some(action);
+    }
+
+    /// Apply the value of a `--warning` option, which is one of:
+    ///
+    /// * `CATEGORY`: print the warnings of the category.
+    /// * `no-CATEGORY`: ignore the warnings of the category.
+    /// * `error=CATEGORY`: treat the warnings of the category as errors.
+    ///
+    /// `all` can be used as the category to refer to all of them, and `error` by itself is the
+    /// same as `error=all`. On failure returns the unrecognized category.
+    pub fn apply(&mut self, value: &str) -> std::result::Result<(), String> {
+        let (action, name) = if value == "error" {
+            (WarningAction::Error, "all")
+        } else if let Some(name) = value.strip_prefix("error=") {
+            (WarningAction::Error, name)
+        } else if let Some(name) = value.strip_prefix("no-") {
+            (WarningAction::Ignore, name)
+        } else {
+            (WarningAction::Warn, value)
+        };
+        if name == "all" {
+            for category in WarningCategory::ALL {
+                self.set(category, action);
+            }
+            return Ok(());
+        }
+        let category = WarningCategory::from_name(name).ok_or_else(|| name.to_owned())?;
+        self.set(category, action);
+        Ok(())
+    }
+}
@@ -105,13 +105,14 @@ fn run_command(input: &Path) -> std::process::Output {
     }
 }
 
-#[ignore]
 #[test]
-fn test_bsd() {
+fn test_argument_collection() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/bsd.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/argument_collection.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/bsd.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/argument_collection.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -131,13 +132,14 @@ fn test_bsd() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_bsd_math() {
+fn test_argument_pushback() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/bsd_math.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/argument_pushback.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/bsd_math.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/argument_pushback.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -158,11 +160,13 @@ fn test_bsd_math() {
 }
 
 #[test]
-fn test_changecom() {
+fn test_argument_references() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/changecom.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/argument_references.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/changecom.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/argument_references.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -183,11 +187,14 @@ fn test_changecom() {
 }
 
 #[test]
-fn test_changequote() {
+fn test_argument_references_traditional() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/changequote.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/argument_references_traditional.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/changequote.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/argument_references_traditional.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -207,12 +214,13 @@ fn test_changequote() {
     );
 }
 
+#[ignore]
 #[test]
-fn test_decr() {
+fn test_bsd() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/decr.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/bsd.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/decr.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/bsd.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -232,12 +240,13 @@ fn test_decr() {
     );
 }
 
+#[ignore]
 #[test]
-fn test_define() {
+fn test_bsd_math() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/define.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/bsd_math.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/bsd_math.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -258,11 +267,11 @@ fn test_define() {
 }
 
 #[test]
-fn test_define_args() {
+fn test_builtin_call() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/define_args.args"));
+    let output = run_command(&Path::new("fixtures/integration_tests/builtin_call.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_args.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/builtin_call.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -282,15 +291,14 @@ fn test_define_args() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_define_eval_order_unquoted() {
+fn test_builtin_prefixed() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_eval_order_unquoted.m4",
+        "fixtures/integration_tests/builtin_prefixed.args",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_eval_order_unquoted.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/builtin_prefixed.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -310,16 +318,12 @@ fn test_define_eval_order_unquoted() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_define_eval_syntax_order_quoted_evaluated() {
+fn test_changecom() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_eval_syntax_order_quoted_evaluated.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/changecom.m4"));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/define_eval_syntax_order_quoted_evaluated.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/changecom.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -340,14 +344,11 @@ fn test_define_eval_syntax_order_quoted_evaluated() {
 }
 
 #[test]
-fn test_define_eval_syntax_order_quoted_unevaluated() {
+fn test_changecom_forms() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_eval_syntax_order_quoted_unevaluated.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/changecom_forms.m4"));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/define_eval_syntax_order_quoted_unevaluated.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/changecom_forms.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -367,16 +368,14 @@ fn test_define_eval_syntax_order_quoted_unevaluated() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_define_eval_syntax_order_unquoted() {
+fn test_changecom_traditional() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_eval_syntax_order_unquoted.m4",
+        "fixtures/integration_tests/changecom_traditional.args",
     ));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/define_eval_syntax_order_unquoted.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/changecom_traditional.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -397,13 +396,11 @@ fn test_define_eval_syntax_order_unquoted() {
 }
 
 #[test]
-fn test_define_hanging_quotes() {
+fn test_changequote() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_hanging_quotes.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/changequote.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_hanging_quotes.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/changequote.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -424,13 +421,14 @@ fn test_define_hanging_quotes() {
 }
 
 #[test]
-fn test_define_invalid_macro_name() {
+fn test_changequote_changecom_mid_line() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_invalid_macro_name.m4",
+        "fixtures/integration_tests/changequote_changecom_mid_line.m4",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_invalid_macro_name.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/changequote_changecom_mid_line.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -451,11 +449,13 @@ fn test_define_invalid_macro_name() {
 }
 
 #[test]
-fn test_define_iterative() {
+fn test_changequote_forms() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/define_iterative.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/changequote_forms.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_iterative.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/changequote_forms.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -476,13 +476,14 @@ fn test_define_iterative() {
 }
 
 #[test]
-fn test_define_iterative_2() {
+fn test_changequote_partial_delimiter() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_iterative_2.m4",
+        "fixtures/integration_tests/changequote_partial_delimiter.m4",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_iterative_2.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/changequote_partial_delimiter.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -503,11 +504,11 @@ fn test_define_iterative_2() {
 }
 
 #[test]
-fn test_define_nested() {
+fn test_changeword() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/define_nested.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/changeword.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_nested.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/changeword.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -527,15 +528,14 @@ fn test_define_nested() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_define_nested_first_arg() {
+fn test_comment_in_arguments() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_nested_first_arg.m4",
+        "fixtures/integration_tests/comment_in_arguments.m4",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_nested_first_arg.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/comment_in_arguments.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -556,13 +556,11 @@ fn test_define_nested_first_arg() {
 }
 
 #[test]
-fn test_define_number_parsing() {
+fn test_crlf() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_number_parsing.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/crlf.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_number_parsing.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/crlf.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -575,19 +573,21 @@ fn test_define_number_parsing() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_define_order_defined() {
+fn test_debug_flags_default() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_order_defined.m4",
+        "fixtures/integration_tests/debug_flags_default.args",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_order_defined.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/debug_flags_default.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -608,13 +608,13 @@ fn test_define_order_defined() {
 }
 
 #[test]
-fn test_define_order_undefined() {
+fn test_debug_flags_trace_all() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_order_undefined.m4",
+        "fixtures/integration_tests/debug_flags_trace_all.args",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_order_undefined.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/debug_flags_trace_all.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -635,13 +635,11 @@ fn test_define_order_undefined() {
 }
 
 #[test]
-fn test_define_parse_brackets() {
+fn test_debugfile() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_parse_brackets.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/debugfile.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_parse_brackets.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/debugfile.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -654,19 +652,21 @@ fn test_define_parse_brackets() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_define_pushpopdef_undefine() {
+fn test_debugfile_discard() {
     init();
     let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_pushpopdef_undefine.m4",
+        "fixtures/integration_tests/debugfile_discard.args",
     ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_pushpopdef_undefine.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/debugfile_discard.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -687,14 +687,11 @@ fn test_define_pushpopdef_undefine() {
 }
 
 #[test]
-fn test_define_quoted_number_stacked() {
+fn test_debugmode() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_quoted_number_stacked.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/debugmode.m4"));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/define_quoted_number_stacked.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/debugmode.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -707,17 +704,19 @@ fn test_define_quoted_number_stacked() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_define_stacked() {
+fn test_decr() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/define_stacked.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/decr.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_stacked.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/decr.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -738,13 +737,11 @@ fn test_define_stacked() {
 }
 
 #[test]
-fn test_define_undefine_order() {
+fn test_deep_nesting() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_undefine_order.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/deep_nesting.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_undefine_order.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/deep_nesting.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -765,13 +762,11 @@ fn test_define_undefine_order() {
 }
 
 #[test]
-fn test_define_unquoted_number_arg() {
+fn test_define() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/define_unquoted_number_arg.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/define.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/define_unquoted_number_arg.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -784,17 +779,19 @@ fn test_define_unquoted_number_arg() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_defn() {
+fn test_define_args() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/defn.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/define_args.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/defn.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_args.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -814,12 +811,15 @@ fn test_defn() {
     );
 }
 
+#[ignore]
 #[test]
-fn test_divert() {
+fn test_define_eval_order_unquoted() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/divert.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_eval_order_unquoted.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/divert.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_eval_order_unquoted.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -840,11 +840,14 @@ fn test_divert() {
 }
 
 #[test]
-fn test_divert_nested() {
+fn test_define_eval_syntax_order_quoted_evaluated() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_eval_syntax_order_quoted_evaluated.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/define_eval_syntax_order_quoted_evaluated.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -865,11 +868,14 @@ fn test_divert_nested() {
 }
 
 #[test]
-fn test_divert_nested_2() {
+fn test_define_eval_syntax_order_quoted_unevaluated() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested_2.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_eval_syntax_order_quoted_unevaluated.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested_2.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/define_eval_syntax_order_quoted_unevaluated.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -890,11 +896,14 @@ fn test_divert_nested_2() {
 }
 
 #[test]
-fn test_divert_nested_3() {
+fn test_define_eval_syntax_order_unquoted() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested_3.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_eval_syntax_order_unquoted.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested_3.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/define_eval_syntax_order_unquoted.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -915,11 +924,13 @@ fn test_divert_nested_3() {
 }
 
 #[test]
-fn test_divert_nested_4() {
+fn test_define_hanging_quotes() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested_4.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_hanging_quotes.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested_4.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_hanging_quotes.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -940,11 +951,13 @@ fn test_divert_nested_4() {
 }
 
 #[test]
-fn test_dnl() {
+fn test_define_invalid_macro_name() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/dnl.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_invalid_macro_name.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/dnl.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_invalid_macro_name.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -965,11 +978,3387 @@ fn test_dnl() {
 }
 
 #[test]
-fn test_dnl_nested() {
+fn test_define_iterative() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/dnl_nested.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/define_iterative.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/dnl_nested.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_iterative.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_iterative_2() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_iterative_2.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_iterative_2.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_nested() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/define_nested.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_nested.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[ignore]
+#[test]
+fn test_define_nested_first_arg() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_nested_first_arg.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_nested_first_arg.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_number_parsing() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_number_parsing.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_number_parsing.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_define_order_defined() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_order_defined.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_order_defined.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_order_undefined() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_order_undefined.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_order_undefined.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_parse_brackets() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_parse_brackets.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_parse_brackets.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_define_pushpopdef_undefine() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_pushpopdef_undefine.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_pushpopdef_undefine.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_quoted_number_stacked() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_quoted_number_stacked.m4",
+    ));
+
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/define_quoted_number_stacked.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_define_stacked() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/define_stacked.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_stacked.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_undefine_order() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_undefine_order.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_undefine_order.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_define_unquoted_number_arg() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/define_unquoted_number_arg.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/define_unquoted_number_arg.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_defn() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/defn.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/defn.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_defn_builtin() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/defn_builtin.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/defn_builtin.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_defn_empty() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/defn_empty.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/defn_empty.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_diagnostics_json() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/diagnostics_json.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/diagnostics_json.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert_nested() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert_nested_2() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested_2.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested_2.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert_nested_3() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested_3.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested_3.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert_nested_4() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert_nested_4.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_nested_4.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert_spill() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert_spill.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_spill.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divert_unlimited() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divert_unlimited.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divert_unlimited.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_divnum_sync() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/divnum_sync.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/divnum_sync.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dnl() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dnl.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dnl.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dnl_eof() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dnl_eof.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dnl_eof.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dnl_eof_fatal() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dnl_eof_fatal.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dnl_eof_fatal.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dnl_nested() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dnl_nested.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dnl_nested.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dumpdef() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dumpdef_empty() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef_empty.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef_empty.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dumpdef_notexist() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef_notexist.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef_notexist.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_dumpdef_quoted() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef_quoted.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef_quoted.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_dumpdef_sorted() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef_sorted.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef_sorted.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_error_location_include() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/error_location_include.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/error_location_include.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_errprint_location() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/errprint_location.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/errprint_location.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_esyscmd() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/esyscmd.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/esyscmd.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_esyscmd_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/esyscmd_traditional.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/esyscmd_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_32bit() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_32bit.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_32bit.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_bad_expression() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/eval_bad_expression.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_bad_expression.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_divide_by_zero() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/eval_divide_by_zero.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_divide_by_zero.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_divide_by_zero_fatal() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/eval_divide_by_zero_fatal.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_divide_by_zero_fatal.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_empty() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_empty.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_empty.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_exponent() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_exponent.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_exponent.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_limits() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_limits.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_limits.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_literals() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_literals.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_literals.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_literals_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/eval_literals_traditional.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_literals_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_precedence() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_precedence.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_precedence.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_radix_width() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/eval_radix_width.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_radix_width.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_eval_short_circuit() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/eval_short_circuit.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/eval_short_circuit.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_evaluation_order() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/evaluation_order.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/evaluation_order.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_expansion_limit() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/expansion_limit.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/expansion_limit.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_fatal_warnings_once() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/fatal_warnings_once.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/fatal_warnings_once.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_fatal_warnings_twice() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/fatal_warnings_twice.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/fatal_warnings_twice.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_file() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/file.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/file.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_forloop_nested() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/forloop_nested.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/forloop_nested.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_forloop_simple() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/forloop_simple.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/forloop_simple.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_format() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/format.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/format.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_format_arguments() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/format_arguments.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/format_arguments.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_format_limits() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/format_limits.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/format_limits.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_freeze_order() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/freeze_order.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/freeze_order.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_freeze_reload_same_file() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/freeze_reload_same_file.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/freeze_reload_same_file.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_freeze_state() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/freeze_state.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/freeze_state.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_ifdef() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/ifdef.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/ifdef.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_ifdef_forms() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/ifdef_forms.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/ifdef_forms.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_ifelse() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/ifelse.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/ifelse.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_ifelse_chains() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/ifelse_chains.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/ifelse_chains.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_include() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/include.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/include.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_include_dirs() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/include_dirs.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/include_dirs.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_include_divert() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/include_divert.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/include_divert.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_include_errors() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/include_errors.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/include_errors.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_include_unterminated() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/include_unterminated.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/include_unterminated.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_incr() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/incr.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/incr.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_incr_decr_forms() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/incr_decr_forms.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/incr_decr_forms.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_incr_decr_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/incr_decr_traditional.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/incr_decr_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_index() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/index.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/index.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_index_forms() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/index_forms.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/index_forms.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_index_too_few_args() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/index_too_few_args.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/index_too_few_args.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_indir() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/indir.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/indir.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_indir_trace() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/indir_trace.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/indir_trace.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_interactive() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/interactive.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/interactive.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_len() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/len.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/len.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_len_forms() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/len_forms.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/len_forms.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_location_macros() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/location_macros.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/location_macros.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_location_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/location_traditional.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/location_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_long_tokens() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/long_tokens.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/long_tokens.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    let r = regex_lite::Regex::new(
+        r"^#line 7 [^
+]*
+131072 131072
+(abcdefg
+)+#line 8
+#(abcdefgh)+
+end
+$",
+    )
+    .unwrap();
+    assert!(
+        r.is_match(&String::from_utf8(output.stdout).unwrap()),
+        "stdout doesn't match regex: r\"{}\"",
+        "^#line 7 [^
+]*
+131072 131072
+(abcdefg
+)+#line 8
+#(abcdefgh)+
+end
+$"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_discards() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_discards.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_discards.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_error() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_error.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_error.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_no_args() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_no_args.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_no_args.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_non_numeric() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/m4exit_non_numeric.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_non_numeric.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_out_of_range() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/m4exit_out_of_range.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_out_of_range.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_status() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_status.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_status.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4exit_success() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_success.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_success.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4wrap() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4wrap.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4wrap.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_m4wrap_rounds() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/m4wrap_rounds.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/m4wrap_rounds.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_macro_errprint_evaluation() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/macro_errprint_evaluation.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/macro_errprint_evaluation.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_macro_errprint_no_evaluation() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/macro_errprint_no_evaluation.m4",
+    ));
+
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/macro_errprint_no_evaluation.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_macro_errprint_no_evaluation_quoted() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/macro_errprint_no_evaluation_quoted.m4",
+    ));
+
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/macro_errprint_no_evaluation_quoted.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_macro_name_tokens() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/macro_name_tokens.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/macro_name_tokens.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_maketemp() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/maketemp.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/maketemp.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    let r = regex_lite::Regex::new(r"^/tmp/m4-.{6}$").unwrap();
+    assert!(
+        r.is_match(&String::from_utf8(output.stdout).unwrap()),
+        "stdout doesn't match regex: r\"{}\"",
+        "^/tmp/m4-.{6}$"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_maketemp_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/maketemp_traditional.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/maketemp_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    let r = regex_lite::Regex::new(r"^/tmp/m4-[0-9]{6}$").unwrap();
+    assert!(
+        r.is_match(&String::from_utf8(output.stdout).unwrap()),
+        "stdout doesn't match regex: r\"{}\"",
+        "^/tmp/m4-[0-9]{6}$"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_many_diversions() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/many_diversions.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/many_diversions.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_memory_map() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/memory_map.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/memory_map.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_missing_operand() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/missing_operand.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/missing_operand.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_mkstemp() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/mkstemp.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/mkstemp.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    let r = regex_lite::Regex::new(r"^/tmp/m4-.{6}$").unwrap();
+    assert!(
+        r.is_match(&String::from_utf8(output.stdout).unwrap()),
+        "stdout doesn't match regex: r\"{}\"",
+        "^/tmp/m4-.{6}$"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_mkstemp_error() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/mkstemp_error.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/mkstemp_error.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_mode_default() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/mode_default.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/mode_default.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_mode_gnu_override() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/mode_gnu_override.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/mode_gnu_override.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_mode_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/mode_traditional.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/mode_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_nesting_limit() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/nesting_limit.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/nesting_limit.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_nul_bytes() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/nul_bytes.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/nul_bytes.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_obsolete_options() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/obsolete_options.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/obsolete_options.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_output_buffering() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/output_buffering.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/output_buffering.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_patsubst() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/patsubst.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/patsubst.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_plain_word_buffer() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/plain_word_buffer.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/plain_word_buffer.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_plain_words() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/plain_words.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/plain_words.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_platform_macros() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/platform_macros.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/platform_macros.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_platform_macros_traditional() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/platform_macros_traditional.args",
+    ));
+
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/platform_macros_traditional.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_prefix_builtins() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/prefix_builtins.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/prefix_builtins.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_profile() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/profile.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/profile.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_pushback_segments() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/pushback_segments.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/pushback_segments.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_pushdef_stack() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/pushdef_stack.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/pushdef_stack.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_quiet() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/quiet.args"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/quiet.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_quoted_nested_eof_in_string() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/quoted_nested_eof_in_string.m4",
+    ));
+
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/quoted_nested_eof_in_string.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    if !test.stderr.is_empty() {
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn test_recurse() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/recurse.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/recurse.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_recursive_defines() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/recursive_defines.m4",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/recursive_defines.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_recursive_macros() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/recursive_macros.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/recursive_macros.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_recursive_nesting_limit() {
+    init();
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/recursive_nesting_limit.args",
+    ));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/recursive_nesting_limit.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_redefine_inbuilt() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/redefine_inbuilt.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/redefine_inbuilt.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_regex_errors() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/regex_errors.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/regex_errors.out");
+    assert_eq!(
+        output.status,
+        std::process::ExitStatus::from_raw(test.status),
+        "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
+}
+
+#[test]
+fn test_regexp() {
+    init();
+    let output = run_command(&Path::new("fixtures/integration_tests/regexp.m4"));
+
+    let test: TestSnapshot = read_test("fixtures/integration_tests/regexp.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -990,11 +4379,11 @@ fn test_dnl_nested() {
 }
 
 #[test]
-fn test_dumpdef() {
+fn test_reload_state() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/reload_state.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/reload_state.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1015,11 +4404,13 @@ fn test_dumpdef() {
 }
 
 #[test]
-fn test_dumpdef_notexist() {
+fn test_reload_state_v2() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/dumpdef_notexist.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/reload_state_v2.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/dumpdef_notexist.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/reload_state_v2.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1032,17 +4423,21 @@ fn test_dumpdef_notexist() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_eval() {
+fn test_reload_state_version() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/eval.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/reload_state_version.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/eval.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/reload_state_version.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1063,11 +4458,11 @@ fn test_eval() {
 }
 
 #[test]
-fn test_evaluation_order() {
+fn test_rescan_expansion() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/evaluation_order.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/rescan_expansion.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/evaluation_order.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/rescan_expansion.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1088,11 +4483,11 @@ fn test_evaluation_order() {
 }
 
 #[test]
-fn test_file() {
+fn test_reverse() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/file.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/reverse.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/file.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/reverse.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1113,11 +4508,11 @@ fn test_file() {
 }
 
 #[test]
-fn test_forloop_nested() {
+fn test_safer() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/forloop_nested.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/safer.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/forloop_nested.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/safer.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1138,11 +4533,11 @@ fn test_forloop_nested() {
 }
 
 #[test]
-fn test_forloop_simple() {
+fn test_shared_state() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/forloop_simple.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/shared_state.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/forloop_simple.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/shared_state.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1163,11 +4558,11 @@ fn test_forloop_simple() {
 }
 
 #[test]
-fn test_ifdef() {
+fn test_shift() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/ifdef.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/shift.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/ifdef.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/shift.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1188,11 +4583,11 @@ fn test_ifdef() {
 }
 
 #[test]
-fn test_ifelse() {
+fn test_shift_quoting() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/ifelse.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/shift_quoting.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/ifelse.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/shift_quoting.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1213,11 +4608,11 @@ fn test_ifelse() {
 }
 
 #[test]
-fn test_include() {
+fn test_sinclude() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/include.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/sinclude.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/include.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/sinclude.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1238,11 +4633,11 @@ fn test_include() {
 }
 
 #[test]
-fn test_include_divert() {
+fn test_stdin_operand() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/include_divert.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/stdin_operand.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/include_divert.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/stdin_operand.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1263,11 +4658,11 @@ fn test_include_divert() {
 }
 
 #[test]
-fn test_incr() {
+fn test_substr() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/incr.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/substr.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/incr.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/substr.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1288,11 +4683,13 @@ fn test_incr() {
 }
 
 #[test]
-fn test_index() {
+fn test_substr_edge_cases() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/index.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/substr_edge_cases.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/index.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/substr_edge_cases.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1313,13 +4710,11 @@ fn test_index() {
 }
 
 #[test]
-fn test_index_too_few_args() {
+fn test_synclines_1() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/index_too_few_args.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/synclines_1.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/index_too_few_args.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/synclines_1.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1332,17 +4727,19 @@ fn test_index_too_few_args() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_len() {
+fn test_synclines_2() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/len.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/synclines_2.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/len.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/synclines_2.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1363,11 +4760,13 @@ fn test_len() {
 }
 
 #[test]
-fn test_m4exit_error() {
+fn test_syscmd_passthrough() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_error.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/syscmd_passthrough.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_error.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/syscmd_passthrough.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1387,12 +4786,13 @@ fn test_m4exit_error() {
     );
 }
 
+#[ignore]
 #[test]
-fn test_m4exit_no_args() {
+fn test_syscmd_sysval() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_no_args.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/syscmd_sysval.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_no_args.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/syscmd_sysval.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1413,11 +4813,11 @@ fn test_m4exit_no_args() {
 }
 
 #[test]
-fn test_m4exit_success() {
+fn test_sysval_status() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/m4exit_success.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/sysval_status.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/m4exit_success.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/sysval_status.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1438,11 +4838,13 @@ fn test_m4exit_success() {
 }
 
 #[test]
-fn test_m4wrap() {
+fn test_sysval_traditional() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/m4wrap.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/sysval_traditional.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/m4wrap.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/sysval_traditional.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1463,13 +4865,11 @@ fn test_m4wrap() {
 }
 
 #[test]
-fn test_macro_errprint_evaluation() {
+fn test_trace() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/macro_errprint_evaluation.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/trace.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/macro_errprint_evaluation.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/trace.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1490,14 +4890,11 @@ fn test_macro_errprint_evaluation() {
 }
 
 #[test]
-fn test_macro_errprint_no_evaluation() {
+fn test_trace_deferred() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/macro_errprint_no_evaluation.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/trace_deferred.args"));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/macro_errprint_no_evaluation.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/trace_deferred.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1518,14 +4915,11 @@ fn test_macro_errprint_no_evaluation() {
 }
 
 #[test]
-fn test_macro_errprint_no_evaluation_quoted() {
+fn test_trace_empty() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/macro_errprint_no_evaluation_quoted.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/trace_empty.m4"));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/macro_errprint_no_evaluation_quoted.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/trace_empty.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1546,22 +4940,23 @@ fn test_macro_errprint_no_evaluation_quoted() {
 }
 
 #[test]
-fn test_maketemp() {
+fn test_trace_file_discard() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/maketemp.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/trace_file_discard.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/maketemp.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/trace_file_discard.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
         "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    let r = regex_lite::Regex::new(r"^/tmp/m4-.{6}$").unwrap();
-    assert!(
-        r.is_match(&String::from_utf8(output.stdout).unwrap()),
-        "stdout doesn't match regex: r\"{}\"",
-        "^/tmp/m4-.{6}$"
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
     assert_eq!(
@@ -1572,22 +4967,21 @@ fn test_maketemp() {
 }
 
 #[test]
-fn test_mkstemp() {
+fn test_trace_order() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/mkstemp.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/trace_order.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/mkstemp.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/trace_order.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
         "status (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    let r = regex_lite::Regex::new(r"^/tmp/m4-.{6}$").unwrap();
-    assert!(
-        r.is_match(&String::from_utf8(output.stdout).unwrap()),
-        "stdout doesn't match regex: r\"{}\"",
-        "^/tmp/m4-.{6}$"
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        test.stdout,
+        "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
     assert_eq!(
@@ -1598,14 +4992,11 @@ fn test_mkstemp() {
 }
 
 #[test]
-fn test_quoted_nested_eof_in_string() {
+fn test_trace_toggle() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/quoted_nested_eof_in_string.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/trace_toggle.args"));
 
-    let test: TestSnapshot =
-        read_test("fixtures/integration_tests/quoted_nested_eof_in_string.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/trace_toggle.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1618,17 +5009,21 @@ fn test_quoted_nested_eof_in_string() {
         "stdout (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
     );
 
-    if !test.stderr.is_empty() {
-        assert!(!output.stderr.is_empty());
-    }
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        test.stderr,
+        "stderr (\x1b[31mcurrent\x1b[0m|\x1b[32mexpected\x1b[0m)"
+    );
 }
 
 #[test]
-fn test_recurse() {
+fn test_traditional_gnu_option() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/recurse.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/traditional_gnu_option.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/recurse.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/traditional_gnu_option.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1649,13 +5044,11 @@ fn test_recurse() {
 }
 
 #[test]
-fn test_recursive_defines() {
+fn test_translit() {
     init();
-    let output = run_command(&Path::new(
-        "fixtures/integration_tests/recursive_defines.m4",
-    ));
+    let output = run_command(&Path::new("fixtures/integration_tests/translit.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/recursive_defines.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/translit.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1676,11 +5069,11 @@ fn test_recursive_defines() {
 }
 
 #[test]
-fn test_redefine_inbuilt() {
+fn test_translit_ranges() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/redefine_inbuilt.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/translit_ranges.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/redefine_inbuilt.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/translit_ranges.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1701,11 +5094,11 @@ fn test_redefine_inbuilt() {
 }
 
 #[test]
-fn test_reverse() {
+fn test_two_files() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/reverse.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/two_files.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/reverse.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/two_files.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1726,11 +5119,13 @@ fn test_reverse() {
 }
 
 #[test]
-fn test_shift() {
+fn test_unclosed_argument_list() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/shift.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/unclosed_argument_list.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/shift.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/unclosed_argument_list.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1751,11 +5146,11 @@ fn test_shift() {
 }
 
 #[test]
-fn test_sinclude() {
+fn test_undivert() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/sinclude.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/undivert.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/sinclude.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1776,11 +5171,11 @@ fn test_sinclude() {
 }
 
 #[test]
-fn test_substr() {
+fn test_undivert_2() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/substr.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/undivert_2.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/substr.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_2.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1800,13 +5195,12 @@ fn test_substr() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_synclines_1() {
+fn test_undivert_current() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/synclines_1.args"));
+    let output = run_command(&Path::new("fixtures/integration_tests/undivert_current.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/synclines_1.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_current.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1826,13 +5220,12 @@ fn test_synclines_1() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_synclines_2() {
+fn test_undivert_file() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/synclines_2.args"));
+    let output = run_command(&Path::new("fixtures/integration_tests/undivert_file.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/synclines_2.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_file.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1852,13 +5245,14 @@ fn test_synclines_2() {
     );
 }
 
-#[ignore]
 #[test]
-fn test_syscmd_sysval() {
+fn test_undivert_file_traditional() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/syscmd_sysval.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/undivert_file_traditional.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/syscmd_sysval.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_file_traditional.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1879,11 +5273,11 @@ fn test_syscmd_sysval() {
 }
 
 #[test]
-fn test_trace() {
+fn test_undivert_nested() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/trace.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/undivert_nested.m4"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/trace.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_nested.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1904,11 +5298,13 @@ fn test_trace() {
 }
 
 #[test]
-fn test_translit() {
+fn test_undivert_selected() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/translit.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/undivert_selected.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/translit.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_selected.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1929,11 +5325,11 @@ fn test_translit() {
 }
 
 #[test]
-fn test_two_files() {
+fn test_utf8_strings() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/two_files.args"));
+    let output = run_command(&Path::new("fixtures/integration_tests/utf8_strings.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/two_files.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/utf8_strings.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1954,11 +5350,13 @@ fn test_two_files() {
 }
 
 #[test]
-fn test_undivert() {
+fn test_warn_macro_sequence() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/undivert.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/warn_macro_sequence.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/warn_macro_sequence.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -1979,11 +5377,13 @@ fn test_undivert() {
 }
 
 #[test]
-fn test_undivert_2() {
+fn test_warning_categories() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/undivert_2.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/warning_categories.args",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_2.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/warning_categories.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -2004,11 +5404,14 @@ fn test_undivert_2() {
 }
 
 #[test]
-fn test_undivert_current() {
+fn test_whitespace_before_parenthesis() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/undivert_current.m4"));
+    let output = run_command(&Path::new(
+        "fixtures/integration_tests/whitespace_before_parenthesis.m4",
+    ));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_current.out");
+    let test: TestSnapshot =
+        read_test("fixtures/integration_tests/whitespace_before_parenthesis.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),
@@ -2029,11 +5432,11 @@ fn test_undivert_current() {
 }
 
 #[test]
-fn test_undivert_nested() {
+fn test_word_regexp() {
     init();
-    let output = run_command(&Path::new("fixtures/integration_tests/undivert_nested.m4"));
+    let output = run_command(&Path::new("fixtures/integration_tests/word_regexp.args"));
 
-    let test: TestSnapshot = read_test("fixtures/integration_tests/undivert_nested.out");
+    let test: TestSnapshot = read_test("fixtures/integration_tests/word_regexp.out");
     assert_eq!(
         output.status,
         std::process::ExitStatus::from_raw(test.status),